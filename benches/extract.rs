@@ -0,0 +1,102 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use yabe::diff::{
+    compute_diff_unhashed, compute_diff_with, compute_diffs_unhashed, compute_diffs_with, diff_and_common_multiple_unhashed,
+    diff_and_common_multiple_with, DiffOptions,
+};
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// Lines each service of a generated values file takes.
+const LINES_PER_SERVICE: usize = 20;
+
+/// Generates a values file of about `lines` lines. Files share most values; every tenth
+/// service differs per file and every third one per group of files.
+fn values_file(file: usize, lines: usize) -> Yaml {
+    let mut text = String::new();
+    for service in 0..lines / LINES_PER_SERVICE {
+        let tag = if service % 10 == 0 { format!("v{}", file) } else { "v1".to_string() };
+        let replicas = if service % 3 == 0 { file % 4 } else { 2 };
+        text.push_str(&format!(
+            "service{service}:
+  enabled: true
+  replicaCount: {replicas}
+  image:
+    repository: registry.example.com/service{service}
+    tag: {tag}
+    pullPolicy: IfNotPresent
+  resources:
+    limits:
+      cpu: 500m
+      memory: 512Mi
+    requests:
+      cpu: 100m
+      memory: 128Mi
+  env:
+    - name: LOG_LEVEL
+      value: info
+    - name: SERVICE_ID
+      value: \"{service}\"
+  ports:
+    - 8080
+    - 9090
+"
+        ));
+    }
+    YamlLoader::load_from_str(&text).unwrap().into_iter().next().unwrap()
+}
+
+fn bench_common(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diff_and_common_multiple");
+    group.sample_size(10);
+    for (files, lines) in [(20, 500), (50, 5000), (200, 5000)] {
+        let docs: Vec<Yaml> = (0..files).map(|file| values_file(file, lines)).collect();
+        let objs: Vec<&Yaml> = docs.iter().collect();
+        let options = DiffOptions::default();
+        let size = format!("{}x{}", files, lines);
+        group.bench_with_input(BenchmarkId::new("hashed", &size), &objs, |b, objs| {
+            b.iter(|| diff_and_common_multiple_with(objs, 0.5, &options))
+        });
+        // The comparisons without hashes, as before the inputs were hashed
+        group.bench_with_input(BenchmarkId::new("unhashed", &size), &objs, |b, objs| {
+            b.iter(|| diff_and_common_multiple_unhashed(objs, 0.5, &options))
+        });
+    }
+    group.finish();
+}
+
+fn bench_diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compute_diff");
+    for lines in [500, 5000, 50000] {
+        let (obj, helm) = (values_file(1, lines), values_file(2, lines));
+        let options = DiffOptions::default();
+        group.bench_with_input(BenchmarkId::new("hashed", lines), &(obj.clone(), helm.clone()), |b, (obj, helm)| {
+            b.iter(|| compute_diff_with(obj, helm, &options))
+        });
+        group.bench_with_input(BenchmarkId::new("unhashed", lines), &(obj, helm), |b, (obj, helm)| {
+            b.iter(|| compute_diff_unhashed(obj, helm, &options))
+        });
+    }
+    group.finish();
+}
+
+/// Many input files diffed against one read-base, as `--read-base` does.
+fn bench_read_base(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compute_diffs");
+    group.sample_size(10);
+    for (files, lines) in [(20, 5000), (200, 5000)] {
+        let read_base = values_file(0, lines);
+        let docs: Vec<Yaml> = (1..=files).map(|file| values_file(file, lines)).collect();
+        let objs: Vec<&Yaml> = docs.iter().collect();
+        let options = DiffOptions::default();
+        let size = format!("{}x{}", files, lines);
+        group.bench_with_input(BenchmarkId::new("hashed", &size), &objs, |b, objs| {
+            b.iter(|| compute_diffs_with(objs, &read_base, &options))
+        });
+        group.bench_with_input(BenchmarkId::new("unhashed", &size), &objs, |b, objs| {
+            b.iter(|| compute_diffs_unhashed(objs, &read_base, &options))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_common, bench_diff, bench_read_base);
+criterion_main!(benches);
\ No newline at end of file
@@ -0,0 +1,300 @@
+use std::str::FromStr;
+
+use log::{info, warn};
+use yaml_rust2::yaml::{Array, Hash, Yaml};
+
+use crate::manifest::ResourceId;
+
+const API_VERSION: &str = "argoproj.io/v1alpha1";
+
+/// The `ref` of the values repository source, so value files are written `$values/<path>`.
+pub const VALUES_REF: &str = "values";
+
+/// Placeholder for the environment name in the `destination` settings.
+const ENV_PLACEHOLDER: &str = "{env}";
+
+/// Which ArgoCD manifests wire the layers together.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ManifestKind {
+    /// One `Application` per environment.
+    #[default]
+    Application,
+    /// One `ApplicationSet` with a list generator element per environment.
+    ApplicationSet,
+}
+
+impl FromStr for ManifestKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "application" => Ok(ManifestKind::Application),
+            "application-set" | "applicationSet" => Ok(ManifestKind::ApplicationSet),
+            other => Err(format!("unknown ArgoCD manifest kind '{}'", other)),
+        }
+    }
+}
+
+/// The `argocd` section of the configuration file.
+#[derive(Clone, Debug)]
+pub struct ArgoConfig {
+    /// Applications are named `<name>-<env>`.
+    pub name: String,
+    /// Namespace of the Application resources.
+    pub namespace: String,
+    pub project: String,
+    /// Where the working directory sits in the values repository, prepended to value file paths.
+    pub path_prefix: String,
+    /// The chart source (`repoURL`, `chart`, `targetRevision`, ...), copied as is.
+    pub chart: Yaml,
+    /// The values repository source (`repoURL`, `targetRevision`), referenced as `$values`.
+    pub values: Yaml,
+    /// The destination; `{env}` in its values is replaced by the environment name.
+    pub destination: Yaml,
+}
+
+impl Default for ArgoConfig {
+    fn default() -> Self {
+        ArgoConfig {
+            name: "app".to_string(),
+            namespace: "argocd".to_string(),
+            project: "default".to_string(),
+            path_prefix: String::new(),
+            chart: Yaml::Hash(Hash::new()),
+            values: Yaml::Hash(Hash::new()),
+            destination: map([("server", Yaml::String("https://kubernetes.default.svc".into()))]),
+        }
+    }
+}
+
+impl ArgoConfig {
+    /// Reads the `argocd` section; the name defaults to the chart name.
+    pub fn from_yaml(yaml: &Yaml) -> Self {
+        let mut config = ArgoConfig::default();
+        for (key, setting) in [
+            ("name", &mut config.name),
+            ("namespace", &mut config.namespace),
+            ("project", &mut config.project),
+            ("pathPrefix", &mut config.path_prefix),
+        ] {
+            match &yaml[key] {
+                Yaml::String(s) => *setting = s.clone(),
+                Yaml::BadValue => {}
+                other => warn!("Ignoring invalid argocd {}: {:?}", key, other),
+            }
+        }
+        for (key, setting) in [
+            ("chart", &mut config.chart),
+            ("values", &mut config.values),
+            ("destination", &mut config.destination),
+        ] {
+            match &yaml[key] {
+                value @ Yaml::Hash(_) => *setting = value.clone(),
+                Yaml::BadValue => {}
+                other => warn!("Ignoring invalid argocd {}: {:?}", key, other),
+            }
+        }
+        if yaml["name"].as_str().is_none() {
+            if let Some(chart) = config.chart["chart"].as_str() {
+                config.name = chart.to_string();
+            }
+        }
+        config
+    }
+
+    /// The `$values` reference of a file, relative to the values repository.
+    pub fn value_file(&self, path: &str) -> String {
+        let path = path.trim_start_matches("./");
+        let prefix = self.path_prefix.trim_matches('/');
+        if prefix.is_empty() {
+            format!("${}/{}", VALUES_REF, path)
+        } else {
+            format!("${}/{}/{}", VALUES_REF, prefix, path)
+        }
+    }
+}
+
+/// An environment: an input file and the diff file written for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Environment {
+    pub name: String,
+    pub diff_file: String,
+    /// Whether the diff file was written; an environment without a diff only gets the shared files.
+    pub has_diff: bool,
+}
+
+/// Generates one Application per environment. `shared` are the value files below the diff files,
+/// lowest first: the chart values, the layers and the base.
+pub fn applications(config: &ArgoConfig, shared: &[String], environments: &[Environment]) -> Vec<Yaml> {
+    environments
+        .iter()
+        .map(|env| {
+            let mut value_files: Vec<String> = shared.iter().map(|path| config.value_file(path)).collect();
+            if env.has_diff {
+                value_files.push(config.value_file(&env.diff_file));
+            }
+            let spec = spec(config, value_files, false, &env.name);
+            resource("Application", &format!("{}-{}", config.name, env.name), config, spec)
+        })
+        .collect()
+}
+
+/// Generates an ApplicationSet with a list generator element per environment.
+pub fn application_set(config: &ArgoConfig, shared: &[String], environments: &[Environment]) -> Yaml {
+    let mut value_files: Vec<String> = shared.iter().map(|path| config.value_file(path)).collect();
+    value_files.push(format!("${}/{{{{.diffFile}}}}", VALUES_REF));
+    let elements: Array = environments
+        .iter()
+        .map(|env| {
+            let diff_file = config.value_file(&env.diff_file);
+            let diff_file = diff_file.trim_start_matches(&format!("${}/", VALUES_REF)).to_string();
+            map([("env", Yaml::String(env.name.clone())), ("diffFile", Yaml::String(diff_file))])
+        })
+        .collect();
+    let template = map([
+        (
+            "metadata",
+            map([
+                ("name", Yaml::String(format!("{}-{{{{.env}}}}", config.name))),
+                ("namespace", Yaml::String(config.namespace.clone())),
+            ]),
+        ),
+        ("spec", spec(config, value_files, true, "{{.env}}")),
+    ]);
+    let spec = map([
+        ("goTemplate", Yaml::Boolean(true)),
+        ("generators", Yaml::Array(vec![map([("list", map([("elements", Yaml::Array(elements))]))])])),
+        ("template", template),
+    ]);
+    resource("ApplicationSet", &config.name, config, spec)
+}
+
+/// Updates `existing` manifests to the generated ones: a manifest that already exists keeps its
+/// other settings and gets the generated value files, list generator and `$values` source;
+/// new ones are added and those no longer generated (e.g. of renamed files) are dropped.
+pub fn update_manifests(existing: &[Yaml], generated: Vec<Yaml>) -> Vec<Yaml> {
+    let updated: Vec<Yaml> = generated
+        .into_iter()
+        .map(|manifest| {
+            let id = ResourceId::of(&manifest);
+            match existing.iter().find(|doc| id.is_some() && ResourceId::of(doc) == id) {
+                Some(doc) => update_manifest(doc.clone(), &manifest),
+                None => manifest,
+            }
+        })
+        .collect();
+    for doc in existing {
+        let id = ResourceId::of(doc);
+        if !updated.iter().any(|manifest| ResourceId::of(manifest) == id) {
+            match id {
+                Some(id) => info!("Removing {}, which is no longer generated.", id),
+                None => info!("Removing a manifest without a kind and name."),
+            }
+        }
+    }
+    updated
+}
+
+fn update_manifest(mut doc: Yaml, generated: &Yaml) -> Yaml {
+    if let Some(spec) = hash_mut(&mut doc, "spec") {
+        if generated["kind"].as_str() == Some("ApplicationSet") {
+            spec.insert(Yaml::String("generators".into()), generated["spec"]["generators"].clone());
+            match spec.get_mut(&Yaml::String("template".into())) {
+                Some(template) => {
+                    if let Some(template_spec) = hash_mut(template, "spec") {
+                        update_sources(template_spec, &generated["spec"]["template"]["spec"]["sources"]);
+                    }
+                }
+                None => {
+                    spec.insert(Yaml::String("template".into()), generated["spec"]["template"].clone());
+                }
+            }
+        } else {
+            update_sources(spec, &generated["spec"]["sources"]);
+        }
+    }
+    doc
+}
+
+/// Sets the value files of the chart source, the first one with `helm` settings or a chart,
+/// and adds the `$values` source if it is missing.
+fn update_sources(spec: &mut Hash, generated: &Yaml) {
+    let key = Yaml::String("sources".into());
+    let Some(Yaml::Array(sources)) = spec.get_mut(&key) else {
+        spec.remove(&Yaml::String("source".into()));
+        spec.insert(key, generated.clone());
+        return;
+    };
+    let value_files = generated[0]["helm"]["valueFiles"].clone();
+    match sources.iter_mut().find(|source| !source["helm"].is_badvalue() || !source["chart"].is_badvalue()) {
+        Some(source) => {
+            if let Some(source) = source.as_mut_hash() {
+                let helm = source
+                    .entry(Yaml::String("helm".into()))
+                    .or_insert_with(|| Yaml::Hash(Hash::new()));
+                if let Some(helm) = helm.as_mut_hash() {
+                    helm.insert(Yaml::String("valueFiles".into()), value_files);
+                }
+            }
+        }
+        None => sources.insert(0, generated[0].clone()),
+    }
+    if !sources.iter().any(|source| source["ref"].as_str() == Some(VALUES_REF)) {
+        sources.push(generated[1].clone());
+    }
+}
+
+/// The spec of an Application: the chart source with the value files and the values repository.
+fn spec(config: &ArgoConfig, value_files: Vec<String>, ignore_missing: bool, env: &str) -> Yaml {
+    let mut helm = Hash::new();
+    helm.insert(
+        Yaml::String("valueFiles".into()),
+        Yaml::Array(value_files.into_iter().map(Yaml::String).collect()),
+    );
+    if ignore_missing {
+        // Environments without a diff have no diff file
+        helm.insert(Yaml::String("ignoreMissingValueFiles".into()), Yaml::Boolean(true));
+    }
+    let mut chart = config.chart.as_hash().cloned().unwrap_or_default();
+    chart.insert(Yaml::String("helm".into()), Yaml::Hash(helm));
+    let mut values = config.values.as_hash().cloned().unwrap_or_default();
+    values.insert(Yaml::String("ref".into()), Yaml::String(VALUES_REF.into()));
+    map([
+        ("project", Yaml::String(config.project.clone())),
+        ("sources", Yaml::Array(vec![Yaml::Hash(chart), Yaml::Hash(values)])),
+        ("destination", with_env(&config.destination, env)),
+    ])
+}
+
+fn resource(kind: &str, name: &str, config: &ArgoConfig, spec: Yaml) -> Yaml {
+    map([
+        ("apiVersion", Yaml::String(API_VERSION.into())),
+        ("kind", Yaml::String(kind.into())),
+        (
+            "metadata",
+            map([
+                ("name", Yaml::String(name.into())),
+                ("namespace", Yaml::String(config.namespace.clone())),
+            ]),
+        ),
+        ("spec", spec),
+    ])
+}
+
+/// Replaces the `{env}` placeholder in the strings of `value`.
+fn with_env(value: &Yaml, env: &str) -> Yaml {
+    match value {
+        Yaml::String(s) => Yaml::String(s.replace(ENV_PLACEHOLDER, env)),
+        Yaml::Hash(hash) => Yaml::Hash(hash.iter().map(|(k, v)| (k.clone(), with_env(v, env))).collect()),
+        Yaml::Array(array) => Yaml::Array(array.iter().map(|v| with_env(v, env)).collect()),
+        other => other.clone(),
+    }
+}
+
+fn hash_mut<'y>(doc: &'y mut Yaml, key: &str) -> Option<&'y mut Hash> {
+    doc.as_mut_hash()?.get_mut(&Yaml::String(key.into()))?.as_mut_hash()
+}
+
+fn map<const N: usize>(entries: [(&str, Yaml); N]) -> Yaml {
+    Yaml::Hash(entries.into_iter().map(|(key, value)| (Yaml::String(key.into()), value)).collect())
+}
\ No newline at end of file
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use yaml_rust2::yaml::{Hash, Yaml};
+use yaml_rust2::YamlLoader;
+
+use crate::merge::{merge_yaml_with, MergeOptions, NullMode};
+
+/// The files of a chart by their path relative to the chart root, e.g. `charts/redis/values.yaml`.
+type Files = BTreeMap<String, Vec<u8>>;
+
+/// An error reading a chart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartError(String);
+
+impl fmt::Display for ChartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot read chart: {}", self.0)
+    }
+}
+
+impl std::error::Error for ChartError {}
+
+/// Checks whether `path` is a Helm chart: a directory or a packaged `.tgz` archive.
+pub fn is_chart(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+    path.is_dir() || name.ends_with(".tgz") || name.ends_with(".tar.gz")
+}
+
+/// Reads the default values of the chart at `path`, a directory or a `.tgz` archive.
+///
+/// With `subcharts`, the defaults of the charts under `charts/` are folded in like Helm does:
+/// under the alias or name the parent chart gives them, with the parent's values taking
+/// precedence, and their `global` values merged below the parent's.
+pub fn load_chart_values(path: &Path, subcharts: bool) -> Result<Yaml, ChartError> {
+    chart_values(&read_chart(path)?, subcharts)
+}
+
+/// Reads the `values.schema.json` of the chart at `path`, if it has one.
+pub fn load_chart_schema(path: &Path) -> Result<Option<Yaml>, ChartError> {
+    match read_chart(path)?.get("values.schema.json") {
+        Some(content) => load_yaml(content, "values.schema.json").map(Some),
+        None => Ok(None),
+    }
+}
+
+fn read_chart(path: &Path) -> Result<Files, ChartError> {
+    if path.is_dir() {
+        let mut files = Files::new();
+        read_dir(path, "", &mut files)?;
+        Ok(files)
+    } else {
+        let bytes = fs::read(path).map_err(|e| ChartError(format!("{}: {}", path.display(), e)))?;
+        read_archive(&bytes)
+    }
+}
+
+fn read_dir(dir: &Path, prefix: &str, files: &mut Files) -> Result<(), ChartError> {
+    let error = |e: std::io::Error| ChartError(format!("{}: {}", dir.display(), e));
+    for entry in fs::read_dir(dir).map_err(error)? {
+        let entry = entry.map_err(error)?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.path().is_dir() {
+            read_dir(&entry.path(), &format!("{}/", name), files)?;
+        } else {
+            files.insert(name, fs::read(entry.path()).map_err(error)?);
+        }
+    }
+    Ok(())
+}
+
+/// Unpacks a chart archive, whose files sit in a directory named after the chart.
+fn read_archive(bytes: &[u8]) -> Result<Files, ChartError> {
+    let error = |e: std::io::Error| ChartError(format!("invalid archive: {}", e));
+    let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+    let mut files = Files::new();
+    for entry in archive.entries().map_err(error)? {
+        let mut entry = entry.map_err(error)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(error)?;
+        let relative: Vec<String> = path.components().skip(1).map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).map_err(error)?;
+        files.insert(relative.join("/"), content);
+    }
+    Ok(files)
+}
+
+fn chart_values(files: &Files, subcharts: bool) -> Result<Yaml, ChartError> {
+    let mut values = match files.get("values.yaml") {
+        Some(content) => load_yaml(content, "values.yaml")?,
+        None => Yaml::Hash(Hash::new()),
+    };
+    if !subcharts {
+        return Ok(values);
+    }
+    let chart = match files.get("Chart.yaml") {
+        Some(content) => load_yaml(content, "Chart.yaml")?,
+        None => Yaml::Null,
+    };
+    let options = MergeOptions::default().with_null_mode(NullMode::Delete);
+    for subchart in subchart_files(files)? {
+        let name = match subchart.get("Chart.yaml") {
+            Some(content) => load_yaml(content, "Chart.yaml")?["name"].as_str().unwrap_or_default().to_string(),
+            None => continue,
+        };
+        let mut defaults = chart_values(&subchart, true)?;
+        let globals = match defaults.as_mut_hash() {
+            Some(defaults) => defaults.remove(&Yaml::String("global".into())),
+            None => None,
+        };
+        let parent = values.as_mut_hash().ok_or_else(|| ChartError("values.yaml is not a map".into()))?;
+        for key in subchart_keys(&chart, &name) {
+            let key = Yaml::String(key);
+            let merged = match parent.get(&key) {
+                Some(overrides) => merge_yaml_with(&defaults, overrides, &options).into_owned(),
+                None => defaults.clone(),
+            };
+            parent.insert(key, merged);
+        }
+        if let Some(globals) = globals {
+            let key = Yaml::String("global".into());
+            let merged = match parent.get(&key) {
+                Some(overrides) => merge_yaml_with(&globals, overrides, &options).into_owned(),
+                None => globals,
+            };
+            parent.insert(key, merged);
+        }
+    }
+    Ok(values)
+}
+
+/// The subcharts under `charts/`, as directories or archives.
+fn subchart_files(files: &Files) -> Result<Vec<Files>, ChartError> {
+    let mut directories: BTreeMap<&str, Files> = BTreeMap::new();
+    let mut subcharts = Vec::new();
+    for (path, content) in files {
+        let Some(rest) = path.strip_prefix("charts/") else { continue };
+        match rest.split_once('/') {
+            Some((dir, file)) => {
+                directories.entry(dir).or_default().insert(file.to_string(), content.clone());
+            }
+            None if rest.ends_with(".tgz") || rest.ends_with(".tar.gz") => subcharts.push(read_archive(content)?),
+            None => {}
+        }
+    }
+    subcharts.extend(directories.into_values());
+    Ok(subcharts)
+}
+
+/// The keys of the parent values a subchart is configured under: the aliases of the
+/// dependencies on it, or its name.
+fn subchart_keys(chart: &Yaml, name: &str) -> Vec<String> {
+    let aliases: Vec<String> = chart["dependencies"]
+        .as_vec()
+        .into_iter()
+        .flatten()
+        .filter(|dependency| dependency["name"].as_str() == Some(name))
+        .map(|dependency| dependency["alias"].as_str().unwrap_or(name).to_string())
+        .collect();
+    if aliases.is_empty() {
+        vec![name.to_string()]
+    } else {
+        aliases
+    }
+}
+
+fn load_yaml(content: &[u8], file: &str) -> Result<Yaml, ChartError> {
+    let content = std::str::from_utf8(content).map_err(|e| ChartError(format!("{}: {}", file, e)))?;
+    let docs = YamlLoader::load_from_str(content).map_err(|e| ChartError(format!("{}: {}", file, e)))?;
+    Ok(docs.into_iter().next().filter(|doc| !doc.is_null()).unwrap_or_else(|| Yaml::Hash(Hash::new())))
+}
\ No newline at end of file
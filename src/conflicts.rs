@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use yaml_rust2::Yaml;
+
+use crate::merge::{item_key, MergeOptions, MergeStrategy};
+use crate::path::{key_to_string, PathSegment, YamlPath};
+use crate::strategic::is_directive;
+
+/// How type conflicts between files are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    Ignore,
+    /// Report the conflicts and carry on (the default).
+    #[default]
+    Warn,
+    /// Report the conflicts and fail.
+    Error,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(ConflictPolicy::Ignore),
+            "warn" => Ok(ConflictPolicy::Warn),
+            "error" => Ok(ConflictPolicy::Error),
+            other => Err(format!(
+                "unknown type conflict policy '{}', expected 'ignore', 'warn' or 'error'",
+                other
+            )),
+        }
+    }
+}
+
+/// A location where files disagree on the kind of value, e.g. a map in one and a string in another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeConflict {
+    pub path: YamlPath,
+    /// The kind of value each file has at the path, for the files that set it.
+    pub kinds: Vec<(String, &'static str)>,
+}
+
+/// Renders the conflict as `path: map in a.yaml, string in b.yaml`.
+impl fmt::Display for TypeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_root() {
+            write!(f, "document root: ")?;
+        } else {
+            write!(f, "{}: ", self.path)?;
+        }
+        let kinds: Vec<String> = self.kinds.iter().map(|(file, kind)| format!("{} in {}", kind, file)).collect();
+        write!(f, "{}", kinds.join(", "))
+    }
+}
+
+/// The kind of a value as far as conflicts are concerned: `map`, `list`, `string`, `number`
+/// or `boolean`. Nulls have none; they unset values rather than change their type.
+pub fn kind_of(value: &Yaml) -> Option<&'static str> {
+    match value {
+        Yaml::Hash(_) => Some("map"),
+        Yaml::Array(_) => Some("list"),
+        Yaml::String(_) => Some("string"),
+        Yaml::Integer(_) | Yaml::Real(_) => Some("number"),
+        Yaml::Boolean(_) => Some("boolean"),
+        Yaml::Null | Yaml::Alias(_) | Yaml::BadValue => None,
+    }
+}
+
+/// Finds the paths where the given documents, named by file, have values of different kinds.
+/// Maps are compared key by key and lists item by item, and nothing below a conflict is reported.
+pub fn find_type_conflicts(docs: &[(&str, &Yaml)]) -> Vec<TypeConflict> {
+    find_type_conflicts_with(docs, &MergeOptions::default())
+}
+
+/// Finds type conflicts like [`find_type_conflicts`], matching the items of lists merged by key
+/// on that key rather than on their position.
+pub fn find_type_conflicts_with(docs: &[(&str, &Yaml)], options: &MergeOptions) -> Vec<TypeConflict> {
+    let mut conflicts = Vec::new();
+    find_in(docs, options, &mut YamlPath::root(), &mut conflicts);
+    conflicts
+}
+
+fn find_in(docs: &[(&str, &Yaml)], options: &MergeOptions, path: &mut YamlPath, conflicts: &mut Vec<TypeConflict>) {
+    let kinds: Vec<(String, &'static str)> = docs
+        .iter()
+        .filter_map(|(file, value)| kind_of(value).map(|kind| (file.to_string(), kind)))
+        .collect();
+    let distinct: HashSet<&str> = kinds.iter().map(|(_, kind)| *kind).collect();
+    if distinct.len() > 1 {
+        conflicts.push(TypeConflict {
+            path: path.clone(),
+            kinds,
+        });
+        return;
+    }
+    if distinct.contains("list") {
+        return find_in_items(docs, options, path, conflicts);
+    }
+    if !distinct.contains("map") {
+        return;
+    }
+
+    let mut seen_keys = HashSet::new();
+    let keys: Vec<&Yaml> = docs
+        .iter()
+        .filter_map(|(_, value)| value.as_hash())
+        .flat_map(|hash| hash.keys())
+        .filter(|key| !is_directive(key) && seen_keys.insert(*key))
+        .collect();
+    for key in keys {
+        let children: Vec<(&str, &Yaml)> = docs
+            .iter()
+            .filter_map(|(file, value)| value.as_hash()?.get(key).map(|child| (*file, child)))
+            .collect();
+        path.push(PathSegment::Key(key_to_string(key)));
+        find_in(&children, options, path, conflicts);
+        path.pop();
+    }
+}
+
+/// Compares the items of lists: those with the same merge key value for lists merged by key, at
+/// the position of the first file that has the item, and the items at the same position otherwise.
+fn find_in_items(docs: &[(&str, &Yaml)], options: &MergeOptions, path: &mut YamlPath, conflicts: &mut Vec<TypeConflict>) {
+    let lists: Vec<(&str, &[Yaml])> =
+        docs.iter().filter_map(|(file, value)| Some((*file, value.as_vec()?.as_slice()))).collect();
+    let groups: Vec<(usize, Vec<(&str, &Yaml)>)> = match options.strategy(path) {
+        MergeStrategy::MergeByKey(key) => {
+            let mut seen = HashSet::new();
+            let mut groups = Vec::new();
+            for (_, items) in &lists {
+                for (position, item) in items.iter().enumerate() {
+                    let Some(value) = item_key(item, key).filter(|value| seen.insert(*value)) else {
+                        continue;
+                    };
+                    let matching = lists
+                        .iter()
+                        .filter_map(|(file, items)| Some((*file, items.iter().find(|i| item_key(i, key) == Some(value))?)))
+                        .collect();
+                    groups.push((position, matching));
+                }
+            }
+            groups
+        }
+        _ => {
+            let longest = lists.iter().map(|(_, items)| items.len()).max().unwrap_or(0);
+            (0..longest)
+                .map(|position| {
+                    let items = lists.iter().filter_map(|(file, items)| Some((*file, items.get(position)?))).collect();
+                    (position, items)
+                })
+                .collect()
+        }
+    };
+    for (position, items) in groups {
+        path.push(PathSegment::Index(position));
+        find_in(&items, options, path, conflicts);
+        path.pop();
+    }
+}
\ No newline at end of file
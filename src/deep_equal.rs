@@ -1,29 +1,154 @@
-use yaml_rust2::Yaml;
+use log::warn;
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::path::{key_to_string, PathSegment, YamlPath};
+use crate::quantity::{is_quantity_path, quantity_equal};
+
+/// Which differences between values are not significant. The default compares values exactly,
+/// reals by their text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EqualityPolicy {
+    /// Compare numbers by value, so `1`, `1.0`, `1.00` and `1e0` are equal.
+    pub numeric: bool,
+    /// Compare a string with a number or boolean by the value the string would load as, so `"80"` equals `80`.
+    pub coerce_strings: bool,
+    /// Compare strings ignoring leading, trailing and repeated whitespace, including trailing newlines.
+    pub ignore_whitespace: bool,
+    /// Treat a key whose value is an empty map like a missing key.
+    pub empty_map_is_absent: bool,
+    /// Compare Kubernetes resource quantities by value, so `1000m` equals `1` and `1Gi` equals `1024Mi`.
+    /// Applies to `resources` and other quantity fields, where diffs know the path of a value.
+    pub quantities: bool,
+}
+
+impl EqualityPolicy {
+    /// Reads the `equality` section of the configuration file, e.g. `{numeric: true}`.
+    /// Missing settings are off; invalid ones are reported and ignored.
+    pub fn from_yaml(yaml: &Yaml) -> Self {
+        let mut policy = EqualityPolicy::default();
+        for (key, flag) in [
+            ("numeric", &mut policy.numeric),
+            ("coerceStrings", &mut policy.coerce_strings),
+            ("ignoreWhitespace", &mut policy.ignore_whitespace),
+            ("emptyMapIsAbsent", &mut policy.empty_map_is_absent),
+            ("quantities", &mut policy.quantities),
+        ] {
+            match &yaml[key] {
+                Yaml::Boolean(b) => *flag = *b,
+                Yaml::BadValue => {}
+                other => warn!("Ignoring invalid equality {}: {:?}", key, other),
+            }
+        }
+        policy
+    }
+
+    /// Checks whether a map value counts as missing.
+    pub fn is_absent(&self, value: &Yaml) -> bool {
+        self.empty_map_is_absent && value.as_hash().is_some_and(|hash| hash.is_empty())
+    }
+}
 
 /// Recursively checks if two Yaml values are deeply equal.
 pub fn deep_equal(a: &Yaml, b: &Yaml) -> bool {
+    deep_equal_with(a, b, &EqualityPolicy::default())
+}
+
+/// Checks if two Yaml values are equal under the given policy.
+pub fn deep_equal_with(a: &Yaml, b: &Yaml, policy: &EqualityPolicy) -> bool {
     match (a, b) {
-        (Yaml::Real(a_str), Yaml::Real(b_str)) => a_str == b_str,
+        (Yaml::Real(a_str), Yaml::Real(b_str)) if !policy.numeric => a_str == b_str,
         (Yaml::Integer(a_int), Yaml::Integer(b_int)) => a_int == b_int,
-        (Yaml::String(a_str), Yaml::String(b_str)) => a_str == b_str,
+        (Yaml::Integer(_) | Yaml::Real(_), Yaml::Integer(_) | Yaml::Real(_)) if policy.numeric => number(a) == number(b),
+        (Yaml::String(a_str), Yaml::String(b_str)) => {
+            if policy.ignore_whitespace {
+                a_str.split_whitespace().eq(b_str.split_whitespace())
+            } else {
+                a_str == b_str
+            }
+        }
+        (Yaml::String(s), other @ (Yaml::Integer(_) | Yaml::Real(_) | Yaml::Boolean(_)))
+        | (other @ (Yaml::Integer(_) | Yaml::Real(_) | Yaml::Boolean(_)), Yaml::String(s))
+            if policy.coerce_strings =>
+        {
+            let loaded = Yaml::from_str(s.trim());
+            !matches!(loaded, Yaml::String(_)) && deep_equal_with(&loaded, other, policy)
+        }
         (Yaml::Boolean(a_bool), Yaml::Boolean(b_bool)) => a_bool == b_bool,
         (Yaml::Array(a_vec), Yaml::Array(b_vec)) => {
             if a_vec.len() != b_vec.len() {
                 false
             } else {
-                a_vec.iter().zip(b_vec.iter()).all(|(a_item, b_item)| deep_equal(a_item, b_item))
+                a_vec.iter().zip(b_vec.iter()).all(|(a_item, b_item)| deep_equal_with(a_item, b_item, policy))
             }
         }
+        (Yaml::Hash(a_hash), Yaml::Hash(b_hash)) if policy.empty_map_is_absent => {
+            let matches = |from: &Hash, to: &Hash| {
+                from.iter().all(|(key, value)| match to.get(key) {
+                    Some(other) => deep_equal_with(value, other, policy),
+                    None => policy.is_absent(value),
+                })
+            };
+            matches(a_hash, b_hash) && matches(b_hash, a_hash)
+        }
         (Yaml::Hash(a_hash), Yaml::Hash(b_hash)) => {
             if a_hash.len() != b_hash.len() {
                 false
             } else {
                 a_hash.iter().all(|(a_key, a_value)| {
-                    b_hash.get(a_key).map_or(false, |b_value| deep_equal(a_value, b_value))
+                    b_hash.get(a_key).is_some_and(|b_value| deep_equal_with(a_value, b_value, policy))
                 })
             }
         }
         (Yaml::Null, Yaml::Null) => true,
         _ => false,
     }
+}
+
+/// Checks if two values at `path` are equal under the given policy. Unlike [`deep_equal_with`],
+/// it compares the resource quantities below `path` by value when the policy says so.
+pub fn deep_equal_at(a: &Yaml, b: &Yaml, policy: &EqualityPolicy, path: &YamlPath) -> bool {
+    if !policy.quantities {
+        return deep_equal_with(a, b, policy);
+    }
+    equal_at(a, b, policy, &mut path.clone())
+}
+
+fn equal_at(a: &Yaml, b: &Yaml, policy: &EqualityPolicy, path: &mut YamlPath) -> bool {
+    match (a, b) {
+        (Yaml::Hash(a_hash), Yaml::Hash(b_hash)) => {
+            (policy.empty_map_is_absent || a_hash.len() == b_hash.len())
+                && entries_equal_at(a_hash, b_hash, policy, path)
+                && entries_equal_at(b_hash, a_hash, policy, path)
+        }
+        (Yaml::Array(a_vec), Yaml::Array(b_vec)) => {
+            a_vec.len() == b_vec.len()
+                && a_vec.iter().zip(b_vec).enumerate().all(|(i, (a_item, b_item))| {
+                    path.push(PathSegment::Index(i));
+                    let equal = equal_at(a_item, b_item, policy, path);
+                    path.pop();
+                    equal
+                })
+        }
+        _ => deep_equal_with(a, b, policy) || (is_quantity_path(path) && quantity_equal(a, b)),
+    }
+}
+
+/// Checks whether every entry of `from` has an equal one in `to`, or counts as missing.
+fn entries_equal_at(from: &Hash, to: &Hash, policy: &EqualityPolicy, path: &mut YamlPath) -> bool {
+    from.iter().all(|(key, value)| match to.get(key) {
+        Some(other) => {
+            path.push(PathSegment::Key(key_to_string(key)));
+            let equal = equal_at(value, other, policy, path);
+            path.pop();
+            equal
+        }
+        None => policy.is_absent(value),
+    })
+}
+
+fn number(value: &Yaml) -> Option<f64> {
+    match value {
+        Yaml::Integer(i) => Some(*i as f64),
+        other => other.as_f64(),
+    }
 }
\ No newline at end of file
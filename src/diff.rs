@@ -1,22 +1,145 @@
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 
-use log::debug;
-use yaml_rust2::yaml::{Hash, Yaml};
-use crate::deep_equal::deep_equal;
+use log::{debug, warn};
+use rayon::prelude::*;
+use yaml_rust2::yaml::{Array, Hash, Yaml};
+use crate::deep_equal::{deep_equal_with, EqualityPolicy};
+use crate::merkle::MerkleIndex;
+use crate::merge::{item_key, merge_node, MergeOptions, MergeStrategy, NullMode};
+use crate::strategic::{element_order, DELETE_FROM_PRIMITIVE_LIST, PATCH, SET_ELEMENT_ORDER};
+use crate::path::{key_to_string, parse_pattern_list, PathPattern, PathSegment, YamlPath};
+use crate::quantity::{canonical_quantity, is_quantity_path};
+use crate::secrets::SecretPolicy;
+
+/// Settings shared by [`compute_diff_with`] and [`diff_and_common_multiple_with`].
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    /// Array merge strategies and null handling the diffs are computed for, so that
+    /// merging a diff over its base with the same options reproduces the original.
+    /// With [`NullMode::Delete`], keys of the base missing from the object become `null`.
+    pub merge: MergeOptions,
+    /// Which differences between values are not significant.
+    pub equality: EqualityPolicy,
+    /// Which values are kept out of the common base, and redacted in logs.
+    pub secrets: SecretPolicy,
+    /// Paths whose values are always kept in the files or always hoisted, whatever the quorum.
+    pub hoist: HoistRules,
+}
+
+/// Per-path overrides of the quorum deciding what goes into the common base.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HoistRules {
+    /// Paths never hoisted, e.g. `image.tag`: their values stay in each file even if all agree.
+    pub keep_local: Vec<PathPattern>,
+    /// Paths whose most common value is hoisted, at any quorum. A pattern applies to everything
+    /// below the path it matches.
+    pub force_shared: Vec<PathPattern>,
+}
+
+impl HoistRules {
+    /// Reads the `extract` section of the configuration file, e.g. `{keepLocal: [image.tag], forceShared: [global]}`.
+    pub fn from_yaml(yaml: &Yaml) -> Self {
+        HoistRules {
+            keep_local: parse_pattern_list(&yaml["keepLocal"], "keepLocal"),
+            force_shared: parse_pattern_list(&yaml["forceShared"], "forceShared"),
+        }
+    }
+
+    /// Checks whether the values at `path` stay in the files.
+    pub fn keeps_local(&self, path: &YamlPath) -> bool {
+        self.keep_local.iter().any(|pattern| pattern.matches(path))
+    }
+
+    /// Checks whether the most common value at `path` is hoisted whatever the quorum.
+    pub fn forces_shared(&self, path: &YamlPath) -> bool {
+        self.force_shared.iter().any(|pattern| pattern.matches_prefix_of(path))
+    }
+}
 
 /// Recursively computes the difference between an override YAML object and the helm values YAML object.
 pub fn compute_diff<'a>(obj: &'a Yaml, helm: &'a Yaml) -> Option<Cow<'a, Yaml>> {
-    if deep_equal(obj, helm) {
+    compute_diff_with(obj, helm, &DiffOptions::default())
+}
+
+/// Computes the difference like [`compute_diff`], with the given options.
+pub fn compute_diff_with<'a>(obj: &'a Yaml, helm: &'a Yaml, options: &DiffOptions) -> Option<Cow<'a, Yaml>> {
+    let index = MerkleIndex::new(&[obj, helm], &options.equality);
+    diff_node(obj, helm, options, &index, &mut YamlPath::root())
+}
+
+/// Computes the difference like [`compute_diff_with`] without hashing the inputs, comparing
+/// values in full. The benchmarks measure the hashes against it.
+#[doc(hidden)]
+pub fn compute_diff_unhashed<'a>(obj: &'a Yaml, helm: &'a Yaml, options: &DiffOptions) -> Option<Cow<'a, Yaml>> {
+    let index = MerkleIndex::unhashed(&options.equality);
+    diff_node(obj, helm, options, &index, &mut YamlPath::root())
+}
+
+/// Computes the difference of each of `objs` against the same `helm` document, like
+/// [`compute_diff_with`], hashing `helm` once for all of them.
+pub fn compute_diffs_with<'a>(objs: &[&'a Yaml], helm: &'a Yaml, options: &DiffOptions) -> Vec<Option<Cow<'a, Yaml>>> {
+    let helm_index = MerkleIndex::new(&[helm], &options.equality);
+    diffs_over(objs, helm, options, &helm_index)
+}
+
+/// Computes the differences like [`compute_diffs_with`] without hashing the inputs.
+#[doc(hidden)]
+pub fn compute_diffs_unhashed<'a>(objs: &[&'a Yaml], helm: &'a Yaml, options: &DiffOptions) -> Vec<Option<Cow<'a, Yaml>>> {
+    let helm_index = MerkleIndex::unhashed(&options.equality);
+    diffs_over(objs, helm, options, &helm_index)
+}
+
+fn diffs_over<'a>(
+    objs: &[&'a Yaml],
+    helm: &'a Yaml,
+    options: &DiffOptions,
+    helm_index: &MerkleIndex<'a>,
+) -> Vec<Option<Cow<'a, Yaml>>> {
+    objs.par_iter()
+        .map(|obj| {
+            let index = MerkleIndex::over(helm_index, &[obj]);
+            diff_node(obj, helm, options, &index, &mut YamlPath::root())
+        })
+        .collect()
+}
+
+fn diff_node<'a>(
+    obj: &'a Yaml,
+    helm: &'a Yaml,
+    options: &DiffOptions,
+    index: &MerkleIndex,
+    path: &mut YamlPath,
+) -> Option<Cow<'a, Yaml>> {
+    if index.equal(obj, helm, path) {
         None
     } else {
         match (obj, helm) {
             (Yaml::Hash(obj_hash), Yaml::Hash(helm_hash)) => {
                 let mut diff_hash = Hash::new();
                 for (key, obj_value) in obj_hash {
+                    if !helm_hash.contains_key(key) && options.equality.is_absent(obj_value) {
+                        continue;
+                    }
                     let helm_value = helm_hash.get(key).unwrap_or(&Yaml::Null);
-                    if let Some(diff_value) = compute_diff(obj_value, helm_value) {
-                        diff_hash.insert(key.clone(), diff_value.into_owned());
+                    path.push(PathSegment::Key(key_to_string(key)));
+                    let diff_value = diff_node(obj_value, helm_value, options, index, path).map(Cow::into_owned);
+                    if options.merge.is_strategic() {
+                        let directive = list_directive(key, obj_value, helm_value, diff_value.as_ref(), options, index, path);
+                        if let Some((directive_key, directive_value)) = directive {
+                            diff_hash.insert(directive_key, directive_value);
+                        }
+                    }
+                    if let Some(diff_value) = diff_value {
+                        diff_hash.insert(key.clone(), diff_value);
+                    }
+                    path.pop();
+                }
+                if options.merge.null_mode() == NullMode::Delete {
+                    for (key, helm_value) in helm_hash {
+                        if !helm_value.is_null() && !obj_hash.contains_key(key) && !options.equality.is_absent(helm_value) {
+                            diff_hash.insert(key.clone(), Yaml::Null);
+                        }
                     }
                 }
                 if diff_hash.is_empty() {
@@ -25,40 +148,252 @@ pub fn compute_diff<'a>(obj: &'a Yaml, helm: &'a Yaml) -> Option<Cow<'a, Yaml>>
                     Some(Cow::Owned(Yaml::Hash(diff_hash)))
                 }
             }
-            (Yaml::Array(obj_array), Yaml::Array(helm_array)) => {
-                if obj_array.len() != helm_array.len() {
-                    Some(Cow::Borrowed(obj))
-                } else {
-                    let mut has_diff = false;
-                    let diffs: Vec<_> = obj_array
-                        .iter()
-                        .zip(helm_array.iter())
-                        .map(|(obj_item, helm_item)| {
-                            if let Some(diff_item) = compute_diff(obj_item, helm_item) {
-                                has_diff = true;
-                                diff_item.into_owned()
-                            } else {
-                                Yaml::Null
-                            }
-                        })
-                        .collect();
-
-                    if has_diff {
-                        Some(Cow::Owned(Yaml::Array(diffs)))
+            (Yaml::Array(obj_array), Yaml::Array(helm_array)) => match options.merge.strategy(path) {
+                MergeStrategy::Replace => {
+                    if obj_array.len() != helm_array.len() || options.merge.is_strategic() {
+                        Some(Cow::Borrowed(obj))
                     } else {
-                        None
+                        let mut has_diff = false;
+                        let diffs: Vec<_> = obj_array
+                            .iter()
+                            .zip(helm_array.iter())
+                            .enumerate()
+                            .map(|(i, (obj_item, helm_item))| {
+                                path.push(PathSegment::Index(i));
+                                let diff_item = diff_node(obj_item, helm_item, options, index, path);
+                                path.pop();
+                                if let Some(diff_item) = diff_item {
+                                    has_diff = true;
+                                    diff_item.into_owned()
+                                } else {
+                                    Yaml::Null
+                                }
+                            })
+                            .collect();
+
+                        if has_diff {
+                            Some(Cow::Owned(Yaml::Array(diffs)))
+                        } else {
+                            None
+                        }
                     }
                 }
-            }
+                strategy => diff_array(obj, obj_array, helm_array, strategy, options, index, path),
+            },
             _ => Some(Cow::Borrowed(obj)),
         }
     }
 }
 
+/// Computes the items that, merged over `helm_array` with `strategy`, give back `obj_array`.
+/// Lists that cannot be reached that way (e.g. a base item was removed) are written whole.
+fn diff_array<'a>(
+    obj: &'a Yaml,
+    obj_array: &'a Array,
+    helm_array: &'a Array,
+    strategy: &MergeStrategy,
+    options: &DiffOptions,
+    index: &MerkleIndex,
+    path: &mut YamlPath,
+) -> Option<Cow<'a, Yaml>> {
+    let items: Option<Array> = match strategy {
+        MergeStrategy::Replace => None,
+        MergeStrategy::Append => starts_with(obj_array, helm_array, index, path)
+            .then(|| obj_array[helm_array.len()..].to_vec()),
+        MergeStrategy::Prepend => ends_with(obj_array, helm_array, index, path)
+            .then(|| obj_array[..obj_array.len() - helm_array.len()].to_vec()),
+        // Strategic merge patches remove set items with `$deleteFromPrimitiveList`, see `list_directive`
+        MergeStrategy::Union => {
+            let helm_items = ItemSet::new(helm_array, index, path);
+            let obj_items = ItemSet::new(obj_array, index, path);
+            (options.merge.is_strategic() || helm_array.iter().all(|item| obj_items.contains(item))).then(|| {
+                obj_array
+                    .iter()
+                    .filter(|item| !helm_items.contains(item))
+                    .cloned()
+                    .collect()
+            })
+        }
+        MergeStrategy::MergeByKey(key) => {
+            let is_matched = |helm_item: &Yaml| {
+                item_key(helm_item, key)
+                    .is_some_and(|value| obj_array.iter().any(|item| item_key(item, key) == Some(value)))
+            };
+            let strategic = options.merge.is_strategic();
+            (strategic || helm_array.iter().all(is_matched)).then(|| {
+                let mut items = Array::new();
+                for (i, item) in obj_array.iter().enumerate() {
+                    let helm_item = item_key(item, key).and_then(|value| {
+                        helm_array.iter().find(|helm_item| item_key(helm_item, key) == Some(value))
+                    });
+                    let Some(helm_item) = helm_item else {
+                        items.push(item.clone());
+                        continue;
+                    };
+                    path.push(PathSegment::Index(i));
+                    let item_diff = diff_node(item, helm_item, options, index, path);
+                    path.pop();
+                    if let Some(item_diff) = item_diff {
+                        items.push(with_item_key(item_diff.into_owned(), item, key));
+                    }
+                }
+                if strategic {
+                    // Base items the object no longer has are deleted with `$patch: delete`
+                    for helm_item in helm_array.iter().filter(|helm_item| !is_matched(helm_item)) {
+                        if let Some(value) = item_key(helm_item, key) {
+                            let mut delete = Hash::new();
+                            delete.insert(Yaml::String(key.clone()), value.clone());
+                            delete.insert(Yaml::String(PATCH.into()), Yaml::String("delete".into()));
+                            items.push(Yaml::Hash(delete));
+                        }
+                    }
+                }
+                items
+            })
+        }
+    };
+
+    match items {
+        Some(items) if items.is_empty() => None,
+        Some(items) => Some(Cow::Owned(Yaml::Array(items))),
+        None => {
+            warn!(
+                "The list at '{}' cannot be expressed as a diff with the {} strategy; writing it whole.",
+                path, strategy
+            );
+            Some(Cow::Borrowed(obj))
+        }
+    }
+}
+
+/// The `$setElementOrder` or `$deleteFromPrimitiveList` directive, if any, a strategic merge
+/// patch needs for the list under `key` to come out as in `obj_value`.
+fn list_directive(
+    key: &Yaml,
+    obj_value: &Yaml,
+    helm_value: &Yaml,
+    diff_value: Option<&Yaml>,
+    options: &DiffOptions,
+    index: &MerkleIndex,
+    path: &mut YamlPath,
+) -> Option<(Yaml, Yaml)> {
+    let (Some(obj_array), Some(helm_array), Some(field)) = (obj_value.as_vec(), helm_value.as_vec(), key.as_str()) else {
+        return None;
+    };
+    let strategy = options.merge.strategy(path);
+    match strategy {
+        MergeStrategy::MergeByKey(_) => {
+            let merged = match diff_value {
+                Some(diff_value) => merge_node(helm_value, diff_value, &options.merge, path).into_owned(),
+                None => helm_value.clone(),
+            };
+            let order = element_order(obj_array, strategy);
+            merged
+                .as_vec()
+                .is_some_and(|merged| element_order(merged, strategy) != order)
+                .then(|| (Yaml::String(format!("{}{}", SET_ELEMENT_ORDER, field)), Yaml::Array(order)))
+        }
+        MergeStrategy::Union => {
+            let obj_items = ItemSet::new(obj_array, index, path);
+            let removed: Array = helm_array
+                .iter()
+                .filter(|item| !obj_items.contains(item))
+                .cloned()
+                .collect();
+            (!removed.is_empty())
+                .then(|| (Yaml::String(format!("{}{}", DELETE_FROM_PRIMITIVE_LIST, field)), Yaml::Array(removed)))
+        }
+        _ => None,
+    }
+}
+
+/// The items of the list at `path`, looked up by hash; a matching hash is confirmed by comparing the items.
+struct ItemSet<'y, 'i> {
+    items: HashMap<u64, Vec<&'y Yaml>>,
+    index: &'i MerkleIndex<'i>,
+    path: &'i YamlPath,
+}
+
+impl<'y, 'i> ItemSet<'y, 'i> {
+    fn new(array: &'y [Yaml], index: &'i MerkleIndex<'i>, path: &'i YamlPath) -> Self {
+        let mut items: HashMap<u64, Vec<&Yaml>> = HashMap::new();
+        for item in array {
+            items.entry(index.hash(item)).or_default().push(item);
+        }
+        ItemSet { items, index, path }
+    }
+
+    fn contains(&self, item: &Yaml) -> bool {
+        self.items
+            .get(&self.index.hash(item))
+            .is_some_and(|candidates| candidates.iter().any(|candidate| self.index.equal(candidate, item, self.path)))
+    }
+}
+
+fn starts_with(array: &[Yaml], prefix: &[Yaml], index: &MerkleIndex, path: &YamlPath) -> bool {
+    array.len() >= prefix.len() && array.iter().zip(prefix).all(|(a, b)| index.equal(a, b, path))
+}
+
+fn ends_with(array: &[Yaml], suffix: &[Yaml], index: &MerkleIndex, path: &YamlPath) -> bool {
+    array.len() >= suffix.len() && array.iter().rev().zip(suffix.iter().rev()).all(|(a, b)| index.equal(a, b, path))
+}
+
+/// Puts the key field of `item` first in `diff`, so the diff item matches its base item when merged.
+fn with_item_key(diff: Yaml, item: &Yaml, field: &str) -> Yaml {
+    match diff {
+        Yaml::Hash(diff_hash) => {
+            let key = Yaml::String(field.to_string());
+            let mut keyed = Hash::new();
+            keyed.insert(key.clone(), item[field].clone());
+            keyed.extend(diff_hash.into_iter().filter(|(k, _)| *k != key));
+            Yaml::Hash(keyed)
+        }
+        _ => item.clone(),
+    }
+}
+
 /// Recursively computes the common base and differences among multiple Yaml objects.
 pub fn diff_and_common_multiple<'a>(
     objs: &'a [&'a Yaml],
     quorum: f64,
+) -> (Option<Cow<'a, Yaml>>, Vec<Option<Cow<'a, Yaml>>>) {
+    diff_and_common_multiple_with(objs, quorum, &DiffOptions::default())
+}
+
+/// Computes the common base and differences like [`diff_and_common_multiple`], with the given options.
+///
+/// Arrays with a merge strategy other than replace are split into the items at least the
+/// quorum of files share and per-file additions instead of being compared as a whole.
+///
+/// Every subtree is hashed once up front (see [`MerkleIndex`]), so values with different hashes
+/// are told apart in constant time and only equal values are compared in full.
+pub fn diff_and_common_multiple_with<'a>(
+    objs: &'a [&'a Yaml],
+    quorum: f64,
+    options: &DiffOptions,
+) -> (Option<Cow<'a, Yaml>>, Vec<Option<Cow<'a, Yaml>>>) {
+    let index = MerkleIndex::new(objs, &options.equality);
+    common_node(objs, quorum, options, &index, &mut YamlPath::root())
+}
+
+/// Computes the common base like [`diff_and_common_multiple_with`] without hashing the inputs,
+/// comparing every pair of values in full. The benchmarks measure the hashes against it.
+#[doc(hidden)]
+pub fn diff_and_common_multiple_unhashed<'a>(
+    objs: &'a [&'a Yaml],
+    quorum: f64,
+    options: &DiffOptions,
+) -> (Option<Cow<'a, Yaml>>, Vec<Option<Cow<'a, Yaml>>>) {
+    let index = MerkleIndex::unhashed(&options.equality);
+    common_node(objs, quorum, options, &index, &mut YamlPath::root())
+}
+
+fn common_node<'a>(
+    objs: &[&'a Yaml],
+    quorum: f64,
+    options: &DiffOptions,
+    index: &MerkleIndex,
+    path: &mut YamlPath,
 ) -> (Option<Cow<'a, Yaml>>, Vec<Option<Cow<'a, Yaml>>>) {
     debug!(
         "diff_and_common_multiple called with {} objects and quorum {}%.",
@@ -71,6 +406,15 @@ pub fn diff_and_common_multiple<'a>(
         return (None, vec![]);
     }
 
+    // Secrets and keepLocal paths stay in the files that set them, even if all of them agree
+    if options.secrets.is_secret(path, objs) || options.hoist.keeps_local(path) {
+        debug!("Keeping the values at '{}' out of the base.", path);
+        return (
+            None,
+            objs.iter().map(|obj| Some(Cow::Borrowed(*obj))).collect(),
+        );
+    }
+
     let total_files = objs.len();
     let quorum_count = (quorum * total_files as f64).ceil() as usize;
 
@@ -89,6 +433,13 @@ pub fn diff_and_common_multiple<'a>(
             Yaml::Hash(_) => "hash",
             _ => "unknown",
         };
+        // Values the equality policy can find equal are compared as one type
+        let obj_type_str = match obj_type_str {
+            "bool" | "int" | "real" | "string" if options.equality.coerce_strings => "scalar",
+            "int" | "real" | "string" if options.equality.quantities && is_quantity_path(path) => "quantity",
+            "int" | "real" if options.equality.numeric => "number",
+            other => other,
+        };
         type_set.insert(obj_type_str);
         if obj_type.is_empty() {
             obj_type = obj_type_str;
@@ -104,37 +455,66 @@ pub fn diff_and_common_multiple<'a>(
         );
     }
 
+    if obj_type == "array" {
+        let strategy = options.merge.strategy(path);
+        if *strategy != MergeStrategy::Replace {
+            debug!("Splitting arrays with the {} strategy.", strategy);
+            return common_array(objs, quorum, strategy, options, index, path);
+        }
+    }
+
     // Handle primitive types and arrays as atomic units
     if obj_type != "hash" {
         debug!("Handling primitive types or arrays as atomic units.");
 
-        // Collect occurrences of unique values using deep comparison
-        let mut occurrences: HashMap<&Yaml, usize> = HashMap::new();
+        // Collect occurrences of unique values in the order they are first seen; values are
+        // looked up by hash and a matching hash is confirmed by comparing the values
+        let mut positions: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut occurrences: Vec<(&Yaml, usize)> = Vec::new();
         for obj in objs {
-            *occurrences.entry(*obj).or_insert(0) += 1;
+            let candidates = positions.entry(index.hash(obj)).or_default();
+            match candidates.iter().find(|&&position| index.equal(occurrences[position].0, obj, path)) {
+                Some(&position) => occurrences[position].1 += 1,
+                None => {
+                    candidates.push(occurrences.len());
+                    occurrences.push((*obj, 1));
+                }
+            }
         }
 
-        // Find the value(s) that meet the quorum
-        let base_value = occurrences.iter().find_map(|(val, &count)| {
-            if count >= quorum_count {
-                Some(*val)
-            } else {
-                None
-            }
-        });
+        // Find the value(s) that meet the quorum; on a forceShared path the most common value,
+        // the first seen of those tied
+        let base_value = if options.hoist.forces_shared(path) {
+            occurrences.iter().rev().max_by_key(|&&(_, count)| count).map(|&(val, _)| val)
+        } else {
+            occurrences.iter().find_map(|&(val, count)| {
+                if count >= quorum_count {
+                    Some(val)
+                } else {
+                    None
+                }
+            })
+        };
 
         if let Some(base_val) = base_value {
-            debug!("Base value determined by quorum: {:?}", base_val);
-            let diffs = objs
+            debug!("Base value determined by quorum: {:?}", options.secrets.redact(path, base_val));
+            let diffs: Vec<_> = objs
                 .iter()
                 .map(|obj| {
-                    if deep_equal(obj, base_val) {
+                    if index.equal(obj, base_val, path) {
                         None
                     } else {
                         Some(Cow::Borrowed(*obj))
                     }
                 })
                 .collect();
+            // A quantity written in several ways is hoisted in its canonical form
+            let equal: Vec<&Yaml> = objs.iter().zip(&diffs).filter(|(_, diff)| diff.is_none()).map(|(obj, _)| *obj).collect();
+            if obj_type == "quantity" && equal.iter().any(|obj| !deep_equal_with(obj, base_val, &options.equality)) {
+                if let Some(canonical) = canonical_quantity(equal) {
+                    return (Some(Cow::Owned(canonical)), diffs);
+                }
+            }
             return (Some(Cow::Borrowed(base_val)), diffs);
         } else {
             // No value meets the quorum; include all values in diffs
@@ -149,11 +529,12 @@ pub fn diff_and_common_multiple<'a>(
     // Handle hashes (maps)
     if obj_type == "hash" {
         debug!("Handling hashes (maps).");
-        // Collect all unique keys
-        let mut all_keys = HashSet::new();
+        // Collect all unique keys, in the order they are first seen
+        let mut seen_keys = HashSet::new();
+        let mut all_keys = Vec::new();
         for obj in objs {
             if let Yaml::Hash(ref h) = obj {
-                all_keys.extend(h.keys());
+                all_keys.extend(h.keys().filter(|key| seen_keys.insert(*key)));
             }
         }
 
@@ -163,6 +544,13 @@ pub fn diff_and_common_multiple<'a>(
         let mut has_base = false;
         let mut has_diffs = vec![false; objs.len()];
 
+        // A missing key is a null, unless nulls delete keys: then it must not be mistaken for one
+        let missing = if options.merge.null_mode() == NullMode::Delete {
+            &Yaml::BadValue
+        } else {
+            &Yaml::Null
+        };
+
         // Iterate over all keys
         for key in &all_keys {
             debug!("Processing key: {:?}", key);
@@ -172,27 +560,37 @@ pub fn diff_and_common_multiple<'a>(
                 .iter()
                 .map(|obj| {
                     if let Yaml::Hash(ref h) = obj {
-                        h.get(*key).unwrap_or(&Yaml::Null)
+                        h.get(*key).unwrap_or(missing)
                     } else {
-                        &Yaml::Null
+                        missing
                     }
                 })
                 .collect();
+            if values_at_key.iter().all(|value| value.is_badvalue() || value.is_null() || options.equality.is_absent(value))
+                && values_at_key.iter().any(|value| options.equality.is_absent(value))
+            {
+                debug!("Only empty maps at this key; skipping it.");
+                continue;
+            }
 
             // Recursively process the values at this key
-            let (sub_base, sub_diffs) = diff_and_common_multiple(&values_at_key, quorum);
+            path.push(PathSegment::Key(key_to_string(key)));
+            let (sub_base, sub_diffs) = common_node(&values_at_key, quorum, options, index, path);
+            path.pop();
 
-            if let Some(ref sub_base_val) = sub_base {
+            let base_includes_key = sub_base.is_some();
+            if let Some(sub_base_val) = sub_base {
                 // Base value meets quorum
-                base_hash.insert((*key).clone(), sub_base_val.clone().into_owned());
+                base_hash.insert((*key).clone(), sub_base_val.into_owned());
                 has_base = true;
             }
 
-            let base_includes_key = sub_base.is_some();
-
             for (i, sub_diff) in sub_diffs.into_iter().enumerate() {
                 if let Some(sub_diff_val) = sub_diff {
-                    if !sub_diff_val.is_null() || base_includes_key {
+                    if sub_diff_val.is_badvalue() {
+                        continue;
+                    }
+                    if !sub_diff_val.is_null() || base_includes_key || missing.is_badvalue() {
                         diffs[i].insert((*key).clone(), sub_diff_val.into_owned());
                         has_diffs[i] = true;
                     }
@@ -227,4 +625,197 @@ pub fn diff_and_common_multiple<'a>(
         None,
         objs.iter().map(|obj| Some(Cow::Borrowed(*obj))).collect(),
     )
-}
\ No newline at end of file
+}
+
+/// Splits arrays merged with `strategy` into the items shared by at least the quorum of them and
+/// per-file additions. A file whose list cannot be rebuilt by merging its additions over the
+/// shared items (it lacks some of them, or orders them differently) gets its whole list, with a
+/// warning; in a strategic merge patch the keyed items it lacks are deleted with `$patch: delete`.
+fn common_array<'a>(
+    objs: &[&'a Yaml],
+    quorum: f64,
+    strategy: &MergeStrategy,
+    options: &DiffOptions,
+    index: &MerkleIndex,
+    path: &mut YamlPath,
+) -> (Option<Cow<'a, Yaml>>, Vec<Option<Cow<'a, Yaml>>>) {
+    let arrays: Vec<&'a Array> = objs.iter().filter_map(|obj| obj.as_vec()).collect();
+    let quorum_count = ((quorum * arrays.len() as f64).ceil() as usize).max(1);
+    let equal = |a: &Yaml, b: &Yaml| index.equal(a, b, path);
+
+    let (common, additions): (Array, Vec<Array>) = match strategy {
+        MergeStrategy::Replace | MergeStrategy::Append => {
+            let prefix = quorum_part(&arrays, quorum_count, |a, n| &a[..n], |a, part| starts_with(a, part, index, path));
+            let additions = arrays
+                .iter()
+                .map(|a| if starts_with(a, prefix, index, path) { a[prefix.len()..].to_vec() } else { a.to_vec() })
+                .collect();
+            (prefix.to_vec(), additions)
+        }
+        MergeStrategy::Prepend => {
+            let suffix = quorum_part(&arrays, quorum_count, |a, n| &a[a.len() - n..], |a, part| ends_with(a, part, index, path));
+            let additions = arrays
+                .iter()
+                .map(|a| if ends_with(a, suffix, index, path) { a[..a.len() - suffix.len()].to_vec() } else { a.to_vec() })
+                .collect();
+            (suffix.to_vec(), additions)
+        }
+        MergeStrategy::Union => {
+            // Distinct items in the order they are first seen, with the number of lists holding them
+            let mut distinct: Vec<(&Yaml, usize)> = Vec::new();
+            for array in &arrays {
+                let mut held: Vec<usize> = Vec::new();
+                for item in array.iter() {
+                    let position = match distinct.iter().position(|(seen, _)| equal(seen, item)) {
+                        Some(position) => position,
+                        None => {
+                            distinct.push((item, 0));
+                            distinct.len() - 1
+                        }
+                    };
+                    if !held.contains(&position) {
+                        held.push(position);
+                        distinct[position].1 += 1;
+                    }
+                }
+            }
+            let common: Array = distinct
+                .into_iter()
+                .filter(|&(_, count)| count >= quorum_count)
+                .map(|(item, _)| item.clone())
+                .collect();
+            let additions = arrays
+                .iter()
+                .map(|a| a.iter().filter(|item| !common.iter().any(|c| equal(c, item))).cloned().collect())
+                .collect();
+            (common, additions)
+        }
+        MergeStrategy::MergeByKey(key) => {
+            // Key values in the order they are first seen
+            let mut seen_keys = HashSet::new();
+            let keys: Vec<&Yaml> = arrays
+                .iter()
+                .flat_map(|a| a.iter().filter_map(|item| item_key(item, key)))
+                .filter(|value| seen_keys.insert(*value))
+                .collect();
+
+            let mut common = Array::new();
+            let mut common_keys: Vec<&Yaml> = Vec::new();
+            // The diff of the first item of each list with a common key, by list and position
+            let mut item_diffs: Vec<HashMap<usize, Option<Yaml>>> = vec![HashMap::new(); arrays.len()];
+            for value in keys {
+                let holders: Vec<(usize, usize)> = arrays
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, a)| Some((i, a.iter().position(|item| item_key(item, key) == Some(value))?)))
+                    .collect();
+                if holders.len() < quorum_count {
+                    continue;
+                }
+                let items: Vec<&'a Yaml> = holders.iter().map(|&(i, position)| &arrays[i][position]).collect();
+                path.push(PathSegment::Index(holders[0].1));
+                let (item_base, diffs) = common_node(&items, quorum, options, index, path);
+                path.pop();
+                let Some(item_base) = item_base else { continue };
+                common.push(with_item_key(item_base.into_owned(), items[0], key));
+                common_keys.push(value);
+                for (&(i, position), diff) in holders.iter().zip(diffs) {
+                    let diff = diff.map(|diff| with_item_key(diff.into_owned(), items[0], key));
+                    item_diffs[i].insert(position, diff);
+                }
+            }
+
+            let additions = arrays
+                .iter()
+                .zip(&item_diffs)
+                .map(|(a, diffs)| {
+                    let mut items = Array::new();
+                    for (position, item) in a.iter().enumerate() {
+                        match diffs.get(&position) {
+                            Some(Some(diff)) => items.push(diff.clone()),
+                            Some(None) => {}
+                            // Items with no common key, and later items with the same key, are kept
+                            None => items.push(item.clone()),
+                        }
+                    }
+                    if options.merge.is_strategic() {
+                        for value in common_keys.iter().filter(|value| !a.iter().any(|item| item_key(item, key) == Some(**value))) {
+                            let mut delete = Hash::new();
+                            delete.insert(Yaml::String(key.clone()), (*value).clone());
+                            delete.insert(Yaml::String(PATCH.into()), Yaml::String("delete".into()));
+                            items.push(Yaml::Hash(delete));
+                        }
+                    }
+                    items
+                })
+                .collect();
+            (common, additions)
+        }
+    };
+
+    if common.is_empty() {
+        return (None, objs.iter().map(|obj| Some(Cow::Borrowed(*obj))).collect());
+    }
+    let common = Yaml::Array(common);
+    let diffs = objs
+        .iter()
+        .zip(additions)
+        .enumerate()
+        .map(|(i, (obj, items))| {
+            let items = Yaml::Array(items);
+            let merged = merge_node(&common, &items, &options.merge, path);
+            if !rebuilds(&merged, obj, strategy, options) {
+                warn!(
+                    "The list at '{}' of input {} cannot be rebuilt from the common items with the {} strategy; writing it whole.",
+                    path,
+                    i + 1,
+                    strategy
+                );
+                return Some(Cow::Borrowed(*obj));
+            }
+            (!items.as_vec().is_some_and(Array::is_empty)).then(|| Cow::Owned(items))
+        })
+        .collect();
+    (Some(Cow::Owned(common)), diffs)
+}
+
+/// Checks whether merging a file's additions over the common items gave back its list `obj`;
+/// union lists are compared as sets, as a union does not keep the order of the items.
+fn rebuilds(merged: &Yaml, obj: &Yaml, strategy: &MergeStrategy, options: &DiffOptions) -> bool {
+    let equal = |a: &Yaml, b: &Yaml| deep_equal_with(a, b, &options.equality);
+    match (strategy, merged.as_vec(), obj.as_vec()) {
+        (MergeStrategy::Union, Some(merged), Some(obj)) => {
+            merged.iter().all(|item| obj.iter().any(|o| equal(o, item)))
+                && obj.iter().all(|item| merged.iter().any(|m| equal(m, item)))
+        }
+        _ => equal(merged, obj),
+    }
+}
+
+/// The longest part of one of the lists, as cut by `part`, that at least `quorum_count` of them
+/// share; the first such list wins ties.
+fn quorum_part<'a>(
+    arrays: &[&'a Array],
+    quorum_count: usize,
+    part: impl Fn(&'a [Yaml], usize) -> &'a [Yaml],
+    shares: impl Fn(&[Yaml], &[Yaml]) -> bool,
+) -> &'a [Yaml] {
+    let mut best: &'a [Yaml] = &[];
+    for array in arrays {
+        // Shorter parts are shared by at least as many lists, so the longest is found by bisection
+        let is_shared = |n: usize| arrays.iter().filter(|a| shares(a, part(array, n))).count() >= quorum_count;
+        let (mut low, mut high) = (0, array.len());
+        while low < high {
+            let mid = (low + high).div_ceil(2);
+            if is_shared(mid) {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        if low > best.len() {
+            best = part(array, low);
+        }
+    }
+    best
+}
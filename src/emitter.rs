@@ -0,0 +1,510 @@
+use std::str::FromStr;
+
+use log::warn;
+use yaml_rust2::yaml::Hash;
+use yaml_rust2::{EmitError, Yaml};
+
+use crate::loader::{ScalarStyle, SourceMap};
+use crate::path::{key_to_string, PathSegment, YamlPath};
+
+/// Quotes used for strings that cannot be written plain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuoteStyle {
+    Double,
+    /// Single quotes, falling back to double quotes for strings with control characters.
+    Single,
+}
+
+impl FromStr for QuoteStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "double" => Ok(QuoteStyle::Double),
+            "single" => Ok(QuoteStyle::Single),
+            other => Err(format!("unknown quote style '{}', expected 'double' or 'single'", other)),
+        }
+    }
+}
+
+/// Output formatting settings. The defaults reproduce `yaml_rust2::YamlEmitter`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmitterConfig {
+    /// Spaces per indentation level of hashes, and of sequences nested in hashes with
+    /// `indent_sequences`. The content of a sequence item always follows its `- `, two columns in.
+    pub indent: usize,
+    /// Indent sequences nested in hashes (`key:\n  - a`) instead of writing them compact (`key:\n- a`).
+    pub indent_sequences: bool,
+    pub quote_style: QuoteStyle,
+    /// Fold long strings into `>-` blocks to keep lines within this width.
+    pub line_width: Option<usize>,
+    /// Write sequences of scalars in flow style (`[a, b]`) when they fit within this width.
+    pub flow_sequence_width: Option<usize>,
+    /// Start every document with `---`.
+    pub explicit_start: bool,
+}
+
+impl Default for EmitterConfig {
+    fn default() -> Self {
+        EmitterConfig {
+            indent: 2,
+            indent_sequences: true,
+            quote_style: QuoteStyle::Double,
+            line_width: None,
+            flow_sequence_width: None,
+            explicit_start: false,
+        }
+    }
+}
+
+impl EmitterConfig {
+    /// Reads the `emitter` section of the configuration file.
+    /// Missing settings keep their defaults; invalid ones are reported and ignored.
+    pub fn from_yaml(yaml: &Yaml) -> Self {
+        let mut config = EmitterConfig::default();
+        if yaml.as_hash().is_none() {
+            return config;
+        }
+
+        match &yaml["indent"] {
+            Yaml::Integer(i) if *i > 0 => config.indent = *i as usize,
+            Yaml::BadValue => {}
+            other => warn!("Ignoring invalid emitter indent: {:?}", other),
+        }
+        match &yaml["indentSequences"] {
+            Yaml::Boolean(b) => config.indent_sequences = *b,
+            Yaml::BadValue => {}
+            other => warn!("Ignoring invalid emitter indentSequences: {:?}", other),
+        }
+        match yaml["quoteStyle"].as_str().map(QuoteStyle::from_str) {
+            Some(Ok(style)) => config.quote_style = style,
+            Some(Err(e)) => warn!("Ignoring emitter quoteStyle: {}", e),
+            None => {}
+        }
+        match &yaml["lineWidth"] {
+            Yaml::Integer(i) if *i > 0 => config.line_width = Some(*i as usize),
+            Yaml::BadValue | Yaml::Null => {}
+            other => warn!("Ignoring invalid emitter lineWidth: {:?}", other),
+        }
+        match &yaml["flowSequenceWidth"] {
+            Yaml::Integer(i) if *i > 0 => config.flow_sequence_width = Some(*i as usize),
+            Yaml::BadValue | Yaml::Null => {}
+            other => warn!("Ignoring invalid emitter flowSequenceWidth: {:?}", other),
+        }
+        match &yaml["explicitStart"] {
+            Yaml::Boolean(b) => config.explicit_start = *b,
+            Yaml::BadValue => {}
+            other => warn!("Ignoring invalid emitter explicitStart: {:?}", other),
+        }
+        config
+    }
+}
+
+/// Serializes a document the way yabe writes its files: without the leading `---`
+/// and with a trailing newline.
+pub fn emit_yaml(doc: &Yaml) -> Result<String, EmitError> {
+    emit_yaml_with(doc, &EmitterConfig::default())
+}
+
+/// Serializes a document with the given formatting settings.
+pub fn emit_yaml_with(doc: &Yaml, config: &EmitterConfig) -> Result<String, EmitError> {
+    emit(doc, config, None)
+}
+
+/// Serializes a document like [`emit_yaml_with`], but writes scalars that are unchanged
+/// from `source` the way they were written there: with the same quotes, number literal
+/// (such as `0755` or `0x1F`) or block style.
+pub fn emit_yaml_preserving(doc: &Yaml, config: &EmitterConfig, source: &SourceMap) -> Result<String, EmitError> {
+    emit(doc, config, Some(source))
+}
+
+fn emit(doc: &Yaml, config: &EmitterConfig, source: Option<&SourceMap>) -> Result<String, EmitError> {
+    let mut emitter = Emitter {
+        out: String::new(),
+        config,
+        column: 0,
+        source,
+        path: YamlPath::root(),
+    };
+    if config.explicit_start {
+        emitter.write("---\n");
+    }
+    emitter.emit_node(doc, 0);
+    emitter.out.push('\n');
+    Ok(emitter.out)
+}
+
+/// Serializes a stream of documents, separating them with `---`.
+/// An empty stream yields an empty string.
+pub fn emit_documents(docs: &[Yaml], config: &EmitterConfig) -> Result<String, EmitError> {
+    emit_documents_preserving(docs, &[], config)
+}
+
+/// Serializes a stream of documents like [`emit_documents`], preserving the presentation
+/// of each document's scalars from the source map at the same position in `sources`.
+pub fn emit_documents_preserving(
+    docs: &[Yaml],
+    sources: &[SourceMap],
+    config: &EmitterConfig,
+) -> Result<String, EmitError> {
+    let mut out_str = String::new();
+    for (i, doc) in docs.iter().enumerate() {
+        if i > 0 && !config.explicit_start {
+            out_str.push_str("---\n");
+        }
+        out_str.push_str(&emit(doc, config, sources.get(i))?);
+    }
+    Ok(out_str)
+}
+
+struct Emitter<'c> {
+    out: String,
+    config: &'c EmitterConfig,
+    /// Column of the cursor on the current line.
+    column: usize,
+    source: Option<&'c SourceMap>,
+    /// Path of the node being emitted.
+    path: YamlPath,
+}
+
+impl Emitter<'_> {
+    fn write(&mut self, s: &str) {
+        self.out.push_str(s);
+        match s.rfind('\n') {
+            Some(pos) => self.column = s[pos + 1..].chars().count(),
+            None => self.column += s.chars().count(),
+        }
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(' ', indent));
+        self.column = indent;
+    }
+
+    /// Emits `node` at the cursor; continuation lines of the node start at column `indent`.
+    fn emit_node(&mut self, node: &Yaml, indent: usize) {
+        match node {
+            Yaml::Array(v) if v.is_empty() => self.write("[]"),
+            Yaml::Array(v) => match self.flow_sequence(v) {
+                Some(flow) => self.write(&flow),
+                None => self.emit_array(v, indent),
+            },
+            Yaml::Hash(h) if h.is_empty() => self.write("{}"),
+            Yaml::Hash(h) => self.emit_hash(h, indent),
+            scalar if self.emit_preserved(scalar, indent) => {}
+            Yaml::String(s) if indent > 0 && self.should_fold(s) => self.emit_folded(s, indent),
+            scalar => {
+                let text = self.scalar_text(scalar);
+                self.write(&text);
+            }
+        }
+    }
+
+    fn emit_array(&mut self, v: &[Yaml], indent: usize) {
+        for (i, item) in v.iter().enumerate() {
+            if i > 0 {
+                self.newline(indent);
+            }
+            self.write("- ");
+            self.path.push(PathSegment::Index(i));
+            self.emit_node(item, indent + 2);
+            self.path.pop();
+        }
+    }
+
+    fn emit_hash(&mut self, h: &Hash, indent: usize) {
+        for (i, (k, v)) in h.iter().enumerate() {
+            if i > 0 {
+                self.newline(indent);
+            }
+            if matches!(k, Yaml::Hash(_) | Yaml::Array(_)) {
+                self.write("? ");
+                self.emit_node(k, indent + 2);
+                self.newline(indent);
+                self.write(": ");
+                self.emit_node(v, indent + 2);
+                continue;
+            }
+            let key = self.scalar_text(k);
+            self.write(&key);
+            self.write(":");
+            self.path.push(PathSegment::Key(key_to_string(k)));
+            self.emit_hash_value(v, indent);
+            self.path.pop();
+        }
+    }
+
+    fn emit_hash_value(&mut self, v: &Yaml, indent: usize) {
+        let child_indent = indent + self.config.indent;
+        match v {
+            Yaml::Array(a) if !a.is_empty() => match self.flow_sequence(a) {
+                Some(flow) => {
+                    self.write(" ");
+                    self.write(&flow);
+                }
+                None => {
+                    let seq_indent = if self.config.indent_sequences { child_indent } else { indent };
+                    self.newline(seq_indent);
+                    self.emit_array(a, seq_indent);
+                }
+            },
+            Yaml::Hash(h) if !h.is_empty() => {
+                self.newline(child_indent);
+                self.emit_hash(h, child_indent);
+            }
+            _ => {
+                self.write(" ");
+                self.emit_node(v, child_indent);
+            }
+        }
+    }
+
+    /// Renders a sequence of scalars in flow style if the configuration allows it and it fits.
+    fn flow_sequence(&self, v: &[Yaml]) -> Option<String> {
+        let width = self.config.flow_sequence_width?;
+        if v.iter().any(|item| matches!(item, Yaml::Array(_) | Yaml::Hash(_))) {
+            return None;
+        }
+        let items: Vec<String> = v
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                self.preserved_inline(item, &self.path.child_index(i))
+                    .unwrap_or_else(|| self.scalar_text(item))
+            })
+            .collect();
+        let flow = format!("[{}]", items.join(", "));
+        let length = flow.chars().count();
+        let fits_line = self.config.line_width.is_none_or(|line| self.column + 1 + length <= line);
+        (length <= width && fits_line).then_some(flow)
+    }
+
+    fn scalar_text(&self, node: &Yaml) -> String {
+        match node {
+            Yaml::String(s) if need_quotes(s) => quote(s, self.config.quote_style),
+            Yaml::String(s) | Yaml::Real(s) => s.clone(),
+            Yaml::Integer(i) => i.to_string(),
+            Yaml::Boolean(b) => b.to_string(),
+            Yaml::Null | Yaml::BadValue => "~".to_string(),
+            Yaml::Alias(_) | Yaml::Array(_) | Yaml::Hash(_) => String::new(),
+        }
+    }
+
+    /// Writes `node` the way it was written in the source, if it is unchanged there
+    /// and that presentation is still valid here.
+    fn emit_preserved(&mut self, node: &Yaml, indent: usize) -> bool {
+        if let Some(text) = self.preserved_inline(node, &self.path) {
+            self.write(&text);
+            return true;
+        }
+        let Some(source) = self.source.and_then(|source| source.lookup(&self.path, node)) else {
+            return false;
+        };
+        match (source.style, node) {
+            (ScalarStyle::Literal | ScalarStyle::Folded, Yaml::String(s))
+                if indent > 0 && block_compatible(s, source.style) =>
+            {
+                self.emit_block(s, source.style, indent);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The original single-line text of `node` at `path`: its quotes, or its number literal.
+    fn preserved_inline(&self, node: &Yaml, path: &YamlPath) -> Option<String> {
+        let source = self.source?.lookup(path, node)?;
+        match (source.style, node) {
+            (ScalarStyle::Plain, Yaml::String(_)) => None,
+            (ScalarStyle::Plain, _) => (Yaml::from_str(&source.raw) == *node).then(|| source.raw.clone()),
+            (ScalarStyle::SingleQuoted, Yaml::String(s)) if !s.chars().any(char::is_control) => {
+                Some(quote(s, QuoteStyle::Single))
+            }
+            (ScalarStyle::DoubleQuoted, Yaml::String(s)) => Some(quote(s, QuoteStyle::Double)),
+            _ => None,
+        }
+    }
+
+    /// Writes `s` as a literal (`|`) or folded (`>`) block, with the chomping indicator
+    /// that reproduces its trailing newlines.
+    fn emit_block(&mut self, s: &str, style: ScalarStyle, indent: usize) {
+        let body = s.trim_end_matches('\n');
+        let trailing = s.len() - body.len();
+        self.write(if style == ScalarStyle::Literal { "|" } else { ">" });
+        self.write(match trailing {
+            0 => "-",
+            1 => "",
+            _ => "+",
+        });
+
+        let width = self.config.line_width.map(|width| width.saturating_sub(indent).max(1));
+        for (i, line) in body.split('\n').enumerate() {
+            // In a folded block a single line break reads as a space, so every
+            // line break of the value takes an empty line.
+            if style == ScalarStyle::Folded && i > 0 {
+                self.out.push('\n');
+            }
+            if line.is_empty() {
+                if style == ScalarStyle::Literal {
+                    self.out.push('\n');
+                }
+                continue;
+            }
+            match width {
+                Some(width) if style == ScalarStyle::Folded => {
+                    let mut folded = String::new();
+                    for word in fold_words(line) {
+                        if !folded.is_empty() && folded.chars().count() + 1 + word.chars().count() > width {
+                            self.newline(indent);
+                            self.write(&folded);
+                            folded.clear();
+                        }
+                        if !folded.is_empty() {
+                            folded.push(' ');
+                        }
+                        folded.push_str(word);
+                    }
+                    self.newline(indent);
+                    self.write(&folded);
+                }
+                _ => {
+                    self.newline(indent);
+                    self.write(line);
+                }
+            }
+        }
+        if trailing > 1 {
+            self.out.extend(std::iter::repeat_n('\n', trailing - 1));
+            self.column = 0;
+        }
+    }
+
+    fn should_fold(&self, s: &str) -> bool {
+        match self.config.line_width {
+            Some(width) => {
+                self.column + 1 + s.chars().count() > width
+                    && !s.starts_with(' ')
+                    && !s.ends_with(' ')
+                    && s.chars().all(|c| matches!(c, '\x20'..='\x7e' | '\u{00a0}'..='\u{d7ff}' | '\u{e000}'..))
+                    && s.contains(' ')
+            }
+            None => false,
+        }
+    }
+
+    /// Writes `s` as a `>-` block, breaking lines at single spaces so they fit the line width.
+    fn emit_folded(&mut self, s: &str, indent: usize) {
+        let width = self.config.line_width.unwrap_or(usize::MAX).saturating_sub(indent).max(1);
+        self.write(">-");
+
+        let mut line = String::new();
+        for word in fold_words(s) {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                self.newline(indent);
+                self.write(&line);
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        self.newline(indent);
+        self.write(&line);
+    }
+}
+
+/// Checks whether `s` reads back unchanged from a block of the given style without
+/// an indentation indicator.
+fn block_compatible(s: &str, style: ScalarStyle) -> bool {
+    let body = s.trim_end_matches('\n');
+    let printable = |c: char| matches!(c, '\t' | '\n' | '\x20'..='\x7e' | '\u{85}' | '\u{a0}'..='\u{d7ff}' | '\u{e000}'..='\u{fefe}' | '\u{ff00}'..);
+    let indented = |line: &str| line.starts_with([' ', '\t']);
+    !body.is_empty()
+        && s.chars().all(printable)
+        && match style {
+            ScalarStyle::Folded => !body.split('\n').any(indented),
+            _ => !body.split('\n').find(|line| !line.is_empty()).is_some_and(indented),
+        }
+}
+
+/// Splits `s` at the spaces that may become line breaks in a folded block: single spaces
+/// between two non-space characters.
+fn fold_words(s: &str) -> Vec<&str> {
+    let bytes = s.as_bytes();
+    let mut words = Vec::new();
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b' ' && i > 0 && i + 1 < bytes.len() && bytes[i - 1] != b' ' && bytes[i + 1] != b' ' {
+            words.push(&s[start..i]);
+            start = i + 1;
+        }
+    }
+    words.push(&s[start..]);
+    words
+}
+
+fn quote(s: &str, style: QuoteStyle) -> String {
+    let has_control = s.chars().any(|c| c.is_control());
+    if style == QuoteStyle::Single && !has_control {
+        return format!("'{}'", s.replace('\'', "''"));
+    }
+
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\x08' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\x0c' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            '\x00'..='\x1f' | '\x7f' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Checks whether a string must be quoted to be read back as the same string.
+/// Mirrors `yaml_rust2`'s emitter, and additionally quotes anything the loader
+/// would not read back as a string (such as `0o17`).
+fn need_quotes(string: &str) -> bool {
+    string.is_empty()
+        || string.starts_with(' ')
+        || string.ends_with(' ')
+        || string.starts_with(['&', '*', '?', '|', '-', '<', '>', '=', '!', '%', '@'])
+        || string.contains(|character: char| {
+            matches!(character, ':'
+            | '{'
+            | '}'
+            | '['
+            | ']'
+            | ','
+            | '#'
+            | '`'
+            | '\"'
+            | '\''
+            | '\\'
+            | '\0'..='\x06'
+            | '\t'
+            | '\n'
+            | '\r'
+            | '\x0e'..='\x1a'
+            | '\x1c'..='\x1f')
+        })
+        || [
+            "yes", "Yes", "YES", "no", "No", "NO", "True", "TRUE", "true", "False", "FALSE",
+            "false", "on", "On", "ON", "off", "Off", "OFF", "null", "Null", "NULL", "~",
+        ]
+        .contains(&string)
+        || string.starts_with('.')
+        || string.starts_with("0x")
+        || string.parse::<i64>().is_ok()
+        || string.parse::<f64>().is_ok()
+        || !matches!(Yaml::from_str(string), Yaml::String(_))
+}
@@ -0,0 +1,339 @@
+use std::collections::{BTreeMap, HashMap};
+
+use yaml_rust2::yaml::{Array, Hash, Yaml};
+
+use crate::path::{key_to_string, PathPattern, PatternSegment};
+use crate::sorter::{array_sorter, hash_sorter};
+
+/// The consensus key order at one (index-generalized) hash location, with the orders seen there.
+struct HashStats {
+    pattern: PathPattern,
+    observations: Vec<Vec<Yaml>>,
+    prefix: Vec<String>,
+    consistent: bool,
+}
+
+/// Arrays of hashes seen at one (index-generalized) location.
+struct ArrayStats<'a> {
+    pattern: PathPattern,
+    observations: Vec<&'a Array>,
+}
+
+/// Key orders collected at one (index-generalized) hash location.
+struct HashObservations {
+    pattern: PathPattern,
+    orders: Vec<Vec<Yaml>>,
+}
+
+#[derive(Default)]
+struct Observations<'a> {
+    hashes: BTreeMap<String, HashObservations>,
+    arrays: BTreeMap<String, ArrayStats<'a>>,
+}
+
+impl<'a> Observations<'a> {
+    fn collect(&mut self, node: &'a Yaml, segments: &mut Vec<PatternSegment>) {
+        match node {
+            Yaml::Hash(h) => {
+                let pattern = PathPattern::new(segments.clone());
+                self.hashes
+                    .entry(pattern.to_string())
+                    .or_insert_with(|| HashObservations { pattern, orders: Vec::new() })
+                    .orders
+                    .push(h.keys().cloned().collect());
+                for (k, v) in h {
+                    segments.push(PatternSegment::Key(key_to_string(k)));
+                    self.collect(v, segments);
+                    segments.pop();
+                }
+            }
+            Yaml::Array(v) => {
+                if v.iter().any(|item| item.as_hash().is_some()) {
+                    let pattern = PathPattern::new(segments.clone());
+                    self.arrays
+                        .entry(pattern.to_string())
+                        .or_insert_with(|| ArrayStats { pattern, observations: Vec::new() })
+                        .observations
+                        .push(v);
+                }
+                segments.push(PatternSegment::AnyIndex);
+                for item in v {
+                    self.collect(item, segments);
+                }
+                segments.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Infers a sort configuration, in the format consumed by [`crate::sorter::sort_yaml`],
+/// that reproduces the dominant key and array order of `docs` with as few changes as possible.
+///
+/// The result holds a global `preOrder` and `sortKey` plus path-scoped `rules` for the
+/// locations where the documents agree on an order the global settings would not produce.
+pub fn infer_sort_config(docs: &[&Yaml]) -> Yaml {
+    let mut observations = Observations::default();
+    for doc in docs {
+        observations.collect(doc, &mut Vec::new());
+    }
+
+    let hash_stats: Vec<HashStats> = observations
+        .hashes
+        .into_values()
+        .map(|HashObservations { pattern, orders: observations }| {
+            let string_orders: Vec<(Vec<String>, usize)> = observations
+                .iter()
+                .map(|keys| (keys.iter().filter_map(|k| k.as_str().map(String::from)).collect(), 1))
+                .collect();
+            let (dominant, consistent) = consensus(&string_orders);
+            HashStats {
+                pattern,
+                prefix: required_prefix(&dominant).to_vec(),
+                observations,
+                consistent,
+            }
+        })
+        .collect();
+    let array_stats: Vec<ArrayStats> = observations.arrays.into_values().collect();
+
+    let (pre_order, hash_rules) = infer_pre_order(&hash_stats);
+    let (sort_key, array_rules) = infer_sort_key(&array_stats);
+
+    let mut rules: Vec<(String, Yaml)> = hash_rules
+        .into_iter()
+        .map(|(pattern, pre_order)| (pattern.to_string(), rule("preOrder", string_array(&pre_order), &pattern)))
+        .chain(array_rules.into_iter().map(|(pattern, key)| {
+            let rule = match key {
+                Some(key) => rule("sortKey", Yaml::String(key), &pattern),
+                None => rule("keepOrder", Yaml::Boolean(true), &pattern),
+            };
+            (pattern.to_string(), rule)
+        }))
+        .collect();
+    rules.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut config = Hash::new();
+    if let Some(sort_key) = sort_key {
+        config.insert(Yaml::String("sortKey".into()), Yaml::String(sort_key));
+    }
+    config.insert(Yaml::String("preOrder".into()), string_array(&pre_order));
+    if !rules.is_empty() {
+        config.insert(
+            Yaml::String("rules".into()),
+            Yaml::Array(rules.into_iter().map(|(_, rule)| rule).collect()),
+        );
+    }
+    Yaml::Hash(config)
+}
+
+fn rule(name: &str, value: Yaml, pattern: &PathPattern) -> Yaml {
+    let mut rule = Hash::new();
+    rule.insert(Yaml::String("path".into()), Yaml::String(pattern.to_string()));
+    rule.insert(Yaml::String(name.into()), value);
+    Yaml::Hash(rule)
+}
+
+fn string_array(items: &[String]) -> Yaml {
+    Yaml::Array(items.iter().cloned().map(Yaml::String).collect())
+}
+
+/// Orders keys by how many other keys they precede in the majority of `orders`
+/// (ties keep first-seen order), and reports whether no two orders contradict each other.
+fn consensus(orders: &[(Vec<String>, usize)]) -> (Vec<String>, bool) {
+    let mut keys: Vec<&String> = Vec::new();
+    let mut votes: HashMap<(&String, &String), usize> = HashMap::new();
+    for (order, weight) in orders {
+        for (i, a) in order.iter().enumerate() {
+            if !keys.contains(&a) {
+                keys.push(a);
+            }
+            for b in &order[i + 1..] {
+                *votes.entry((a, b)).or_insert(0) += weight;
+            }
+        }
+    }
+
+    let consistent = votes.keys().all(|(a, b)| !votes.contains_key(&(*b, *a)));
+    let vote = |a: &String, b: &String| votes.get(&(a, b)).copied().unwrap_or(0);
+    let mut scored: Vec<(usize, &String)> = keys
+        .iter()
+        .map(|a| (keys.iter().filter(|b| vote(a, b) > vote(b, a)).count(), *a))
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    (scored.into_iter().map(|(_, k)| k.clone()).collect(), consistent)
+}
+
+/// The shortest prefix of `order` after which the remaining keys are already sorted.
+fn required_prefix(order: &[String]) -> &[String] {
+    let mut start = order.len().saturating_sub(1);
+    while start > 0 && order[start - 1] <= order[start] {
+        start -= 1;
+    }
+    &order[..start]
+}
+
+fn hash_order_changes(keys: &[Yaml], pre_order: &[&str]) -> bool {
+    let mut hash: Hash = keys.iter().map(|k| (k.clone(), Yaml::Null)).collect();
+    hash_sorter(&mut hash, pre_order);
+    !hash.keys().eq(keys.iter())
+}
+
+fn array_order_changes(array: &Array, sort_key: &str) -> bool {
+    let mut sorted = array.clone();
+    array_sorter(&mut sorted, sort_key);
+    sorted != *array
+}
+
+/// Number of observed hashes that would change, plus the scoped rules needed, for `pre_order`.
+fn evaluate_pre_order<'s>(
+    stats: &'s [HashStats],
+    pre_order: &[String],
+) -> (usize, Vec<(&'s PathPattern, &'s [String])>) {
+    let global: Vec<&str> = pre_order.iter().map(String::as_str).collect();
+    let mut changes = 0;
+    let mut rules = Vec::new();
+    for stat in stats {
+        let count = |pre_order: &[&str]| {
+            stat.observations
+                .iter()
+                .filter(|keys| hash_order_changes(keys, pre_order))
+                .count()
+        };
+        let global_changes = count(&global);
+        if global_changes == 0 {
+            continue;
+        }
+        if stat.consistent {
+            let scoped: Vec<&str> = stat.prefix.iter().map(String::as_str).collect();
+            let scoped_changes = count(&scoped);
+            if scoped_changes < global_changes {
+                changes += scoped_changes;
+                rules.push((&stat.pattern, stat.prefix.as_slice()));
+                continue;
+            }
+        }
+        changes += global_changes;
+    }
+    (changes, rules)
+}
+
+fn infer_pre_order(stats: &[HashStats]) -> (Vec<String>, Vec<(PathPattern, Vec<String>)>) {
+    let prefixes: Vec<(Vec<String>, usize)> = stats
+        .iter()
+        .filter(|stat| !stat.prefix.is_empty())
+        .map(|stat| (stat.prefix.clone(), stat.observations.len()))
+        .collect();
+    let (mut pre_order, _) = consensus(&prefixes);
+
+    // Drop global keys that do not pay for themselves; on ties prefer global keys over scoped ones.
+    let cost = |pre_order: &[String]| {
+        let (changes, rules) = evaluate_pre_order(stats, pre_order);
+        let scoped_keys: usize = rules.iter().map(|(_, prefix)| prefix.len()).sum();
+        (changes, rules.len(), scoped_keys, pre_order.len())
+    };
+    let mut best = cost(&pre_order);
+    for i in (0..pre_order.len()).rev() {
+        let mut candidate = pre_order.clone();
+        candidate.remove(i);
+        let candidate_cost = cost(&candidate);
+        if candidate_cost < best {
+            best = candidate_cost;
+            pre_order = candidate;
+        }
+    }
+
+    let rules = evaluate_pre_order(stats, &pre_order)
+        .1
+        .into_iter()
+        .map(|(pattern, prefix)| (pattern.clone(), prefix.to_vec()))
+        .collect();
+    (pre_order, rules)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum KeyFit {
+    /// Sorting by the key changes nothing and orders at least one array.
+    Good(usize),
+    /// Sorting by the key changes nothing but has nothing to order.
+    Neutral,
+    /// Sorting by the key reorders at least one array.
+    Bad,
+}
+
+fn key_fit(stat: &ArrayStats, key: &str) -> KeyFit {
+    if stat.observations.iter().any(|array| array_order_changes(array, key)) {
+        return KeyFit::Bad;
+    }
+    let support = stat
+        .observations
+        .iter()
+        .filter(|array| array.iter().filter(|item| item[key].as_str().is_some()).count() > 1)
+        .count();
+    if support > 0 {
+        KeyFit::Good(support)
+    } else {
+        KeyFit::Neutral
+    }
+}
+
+/// The global `sortKey`, and per-path rules with the key to sort by there, or none where the
+/// arrays must keep their order.
+fn infer_sort_key(stats: &[ArrayStats]) -> (Option<String>, Vec<(PathPattern, Option<String>)>) {
+    let mut candidates: Vec<String> = Vec::new();
+    for stat in stats {
+        for array in &stat.observations {
+            for item in array.iter() {
+                for (k, v) in item.as_hash().into_iter().flatten() {
+                    if let (Some(k), Some(_)) = (k.as_str(), v.as_str()) {
+                        if !candidates.iter().any(|c| c == k) {
+                            candidates.push(k.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let fits: Vec<Vec<KeyFit>> = stats
+        .iter()
+        .map(|stat| candidates.iter().map(|key| key_fit(stat, key)).collect())
+        .collect();
+    let score = |c: usize| -> isize {
+        fits.iter()
+            .map(|fit| match fit[c] {
+                KeyFit::Good(_) => 1,
+                KeyFit::Neutral => 0,
+                KeyFit::Bad => -1,
+            })
+            .sum()
+    };
+    let global = (0..candidates.len())
+        .map(|c| (score(c), c))
+        .filter(|(score, _)| *score > 0)
+        .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+        .map(|(_, c)| c);
+
+    let mut rules = Vec::new();
+    for (stat, fit) in stats.iter().zip(&fits) {
+        if let Some(g) = global {
+            if matches!(fit[g], KeyFit::Good(_)) {
+                continue;
+            }
+        }
+        let best_local = (0..candidates.len())
+            .filter_map(|c| match fit[c] {
+                KeyFit::Good(support) => Some((support, c)),
+                _ => None,
+            })
+            .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+            .map(|(_, c)| candidates[c].clone());
+        match (best_local, global.map(|g| fit[g])) {
+            (Some(key), _) => rules.push((stat.pattern.clone(), Some(key))),
+            (None, Some(KeyFit::Bad)) => rules.push((stat.pattern.clone(), None)),
+            _ => {}
+        }
+    }
+
+    (global.map(|c| candidates[c].clone()), rules)
+}
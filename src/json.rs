@@ -0,0 +1,81 @@
+use std::fmt::Write;
+
+use yaml_rust2::Yaml;
+
+use crate::path::key_to_string;
+
+/// Serializes a YAML value as pretty-printed JSON with two-space indentation.
+/// Keys that are not strings are written as their YAML text; reals JSON cannot represent
+/// (`.inf`, `.nan`) and values without a JSON equivalent are written as `null`.
+pub fn to_json(doc: &Yaml) -> String {
+    let mut out = String::new();
+    write_value(&mut out, doc, 0);
+    out.push('\n');
+    out
+}
+
+fn write_value(out: &mut String, value: &Yaml, level: usize) {
+    match value {
+        Yaml::Hash(hash) if !hash.is_empty() => {
+            out.push('{');
+            for (i, (key, value)) in hash.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                write_string(out, &key_to_string(key));
+                out.push_str(": ");
+                write_value(out, value, level + 1);
+            }
+            newline(out, level);
+            out.push('}');
+        }
+        Yaml::Hash(_) => out.push_str("{}"),
+        Yaml::Array(array) if !array.is_empty() => {
+            out.push('[');
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                write_value(out, item, level + 1);
+            }
+            newline(out, level);
+            out.push(']');
+        }
+        Yaml::Array(_) => out.push_str("[]"),
+        Yaml::String(s) => write_string(out, s),
+        Yaml::Integer(i) => out.push_str(&i.to_string()),
+        Yaml::Real(_) => match value.as_f64() {
+            Some(f) if f.is_finite() => out.push_str(&f.to_string()),
+            _ => out.push_str("null"),
+        },
+        Yaml::Boolean(b) => out.push_str(&b.to_string()),
+        Yaml::Null | Yaml::Alias(_) | Yaml::BadValue => out.push_str("null"),
+    }
+}
+
+fn newline(out: &mut String, level: usize) {
+    out.push('\n');
+    out.push_str(&"  ".repeat(level));
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
\ No newline at end of file
@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use yaml_rust2::yaml::{Array, Hash, Yaml};
+
+use crate::manifest::ResourceId;
+use crate::merge::{merge_yaml_with, MergeOptions};
+use crate::patch::{json_patch, operations_to_yaml};
+use crate::template::{input_names, TemplateError};
+
+const KUSTOMIZATION: &str = "kustomization.yaml";
+
+/// How overlays patch the resources of the base.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatchFormat {
+    /// Strategic merge patches: the part of each resource that differs, with its identity.
+    #[default]
+    Strategic,
+    /// RFC 6902 JSON patches with a target selecting the resource.
+    Json6902,
+}
+
+impl FromStr for PatchFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strategic" => Ok(PatchFormat::Strategic),
+            "json6902" => Ok(PatchFormat::Json6902),
+            other => Err(format!("unknown kustomize patch format '{}'", other)),
+        }
+    }
+}
+
+/// A file of the kustomize tree: its path relative to the output directory and its document.
+/// Resources and patches are sorted before they are written, kustomizations are not.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KustomizeFile {
+    pub path: String,
+    pub doc: Yaml,
+    pub is_kustomization: bool,
+}
+
+/// The `base/` directory: a file per resource of the `base` bundle and a kustomization listing them.
+pub fn base_files(base: Option<&Yaml>) -> Vec<KustomizeFile> {
+    let mut files = Vec::new();
+    let mut names = HashSet::new();
+    for resource in resources(base) {
+        let name = file_name(resource, &mut names);
+        files.push(KustomizeFile {
+            path: format!("base/{}", name),
+            doc: resource.clone(),
+            is_kustomization: false,
+        });
+    }
+    let listed = files.iter().map(|file| file.path["base/".len()..].to_string()).collect();
+    files.push(kustomization("base", listed, Vec::new()));
+    files
+}
+
+/// The overlays of the input files `inputs` with their `diffs`, each named after its input file.
+/// Input files with the same name in different directories would share an overlay, so they are
+/// an error.
+pub fn overlays(
+    inputs: &[&str],
+    diffs: &[Option<Yaml>],
+    base: Option<&Yaml>,
+    format: PatchFormat,
+    options: &MergeOptions,
+) -> Result<Vec<KustomizeFile>, TemplateError> {
+    let names = input_names(inputs)?;
+    Ok(names
+        .iter()
+        .zip(diffs)
+        .flat_map(|(name, diff)| overlay_files(name, diff.as_ref(), base, format, options))
+        .collect())
+}
+
+/// The `overlays/<name>/` directory of an input: resources of `diff` that `base` has become
+/// patches, the others are added as resources over `../../base`.
+pub fn overlay_files(
+    name: &str,
+    diff: Option<&Yaml>,
+    base: Option<&Yaml>,
+    format: PatchFormat,
+    options: &MergeOptions,
+) -> Vec<KustomizeFile> {
+    let dir = format!("overlays/{}", name);
+    let mut files = Vec::new();
+    let mut names = HashSet::new();
+    let mut listed = vec!["../../base".to_string()];
+    let mut patches = Vec::new();
+    let base_resources = base.and_then(Yaml::as_hash);
+    for (id, resource) in diff.and_then(Yaml::as_hash).into_iter().flatten() {
+        let file = file_name(resource, &mut names);
+        let Some(base_resource) = base_resources.and_then(|base| base.get(id)) else {
+            listed.push(file.clone());
+            files.push(KustomizeFile {
+                path: format!("{}/{}", dir, file),
+                doc: resource.clone(),
+                is_kustomization: false,
+            });
+            continue;
+        };
+        let mut patch = Hash::new();
+        patch.insert(Yaml::String("path".into()), Yaml::String(file.clone()));
+        let doc = match format {
+            PatchFormat::Strategic => resource.clone(),
+            PatchFormat::Json6902 => {
+                patch.insert(Yaml::String("target".into()), target(base_resource));
+                let values = merge_yaml_with(base_resource, resource, options);
+                operations_to_yaml(&json_patch(&values, base_resource))
+            }
+        };
+        patches.push(Yaml::Hash(patch));
+        files.push(KustomizeFile {
+            path: format!("{}/{}", dir, file),
+            doc,
+            is_kustomization: false,
+        });
+    }
+    files.push(kustomization(&dir, listed, patches));
+    files
+}
+
+fn resources(bundle: Option<&Yaml>) -> impl Iterator<Item = &Yaml> {
+    bundle.and_then(Yaml::as_hash).into_iter().flatten().map(|(_, resource)| resource)
+}
+
+fn kustomization(dir: &str, resources: Vec<String>, patches: Array) -> KustomizeFile {
+    let mut doc = Hash::new();
+    doc.insert(Yaml::String("apiVersion".into()), Yaml::String("kustomize.config.k8s.io/v1beta1".into()));
+    doc.insert(Yaml::String("kind".into()), Yaml::String("Kustomization".into()));
+    doc.insert(
+        Yaml::String("resources".into()),
+        Yaml::Array(resources.into_iter().map(Yaml::String).collect()),
+    );
+    if !patches.is_empty() {
+        doc.insert(Yaml::String("patches".into()), Yaml::Array(patches));
+    }
+    KustomizeFile {
+        path: format!("{}/{}", dir, KUSTOMIZATION),
+        doc: Yaml::Hash(doc),
+        is_kustomization: true,
+    }
+}
+
+/// The target of a JSON patch: the group, version, kind, name and namespace of the resource.
+fn target(resource: &Yaml) -> Yaml {
+    let mut target = Hash::new();
+    let api_version = resource["apiVersion"].as_str().unwrap_or("");
+    let (group, version) = api_version.rsplit_once('/').unwrap_or(("", api_version));
+    let id = ResourceId::of(resource);
+    let fields = [
+        ("group", Some(group)),
+        ("version", Some(version)),
+        ("kind", id.as_ref().map(|id| id.kind.as_str())),
+        ("name", id.as_ref().map(|id| id.name.as_str())),
+        ("namespace", id.as_ref().and_then(|id| id.namespace.as_deref())),
+    ];
+    for (field, value) in fields {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            target.insert(Yaml::String(field.into()), Yaml::String(value.into()));
+        }
+    }
+    Yaml::Hash(target)
+}
+
+/// A file name for a resource, `[namespace-]kind-name.yaml` in lowercase, unique among `taken`.
+fn file_name(resource: &Yaml, taken: &mut HashSet<String>) -> String {
+    let stem = match ResourceId::of(resource) {
+        Some(id) => {
+            let mut parts: Vec<&str> = id.namespace.as_deref().into_iter().collect();
+            parts.extend([id.kind.as_str(), id.name.as_str()]);
+            parts.join("-").to_lowercase().replace(['/', ':'], "-")
+        }
+        None => "resource".to_string(),
+    };
+    let mut name = format!("{}.yaml", stem);
+    let mut n = 1;
+    while !taken.insert(name.clone()) {
+        n += 1;
+        name = format!("{}-{}.yaml", stem, n);
+    }
+    name
+}
\ No newline at end of file
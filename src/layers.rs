@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+
+use rayon::prelude::*;
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::diff::{compute_diffs_with, diff_and_common_multiple_with, DiffOptions};
+use crate::merge::{merge_yaml_with, MergeOptions, MergeStrategy, NullMode};
+use crate::path::{key_to_string, PathSegment, YamlPath};
+
+/// Merges a stack of values layers in order, lowest first.
+/// Returns `None` for an empty stack.
+pub fn merge_layers(layers: &[&Yaml], options: &MergeOptions) -> Option<Yaml> {
+    let (first, rest) = layers.split_first()?;
+    let mut merged = (*first).clone();
+    for layer in rest {
+        merged = merge_yaml_with(&merged, layer, options).into_owned();
+    }
+    Some(merged)
+}
+
+/// Extracts the values common to `inputs` into `layers[target]`.
+///
+/// Each input is the top of its own stack `layers ++ [input]`. The layers below the target
+/// are left as they are, the target is rewritten with the values the effective inputs share
+/// (per `quorum`), and the layers above it stay in place; values they would shadow are
+/// not moved into the target. Returns the new target layer and, per input, the diff that
+/// reproduces its effective values on top of the new stack.
+pub fn extract_into_layer(
+    layers: &[&Yaml],
+    target: usize,
+    inputs: &[&Yaml],
+    quorum: f64,
+    options: &DiffOptions,
+) -> (Option<Yaml>, Vec<Option<Yaml>>) {
+    let below = merge_layers(&layers[..target], &options.merge);
+    let above = &layers[target + 1..];
+    let stack = merge_layers(layers, &options.merge);
+
+    let effective: Vec<Yaml> = inputs
+        .par_iter()
+        .map(|input| match stack {
+            Some(ref stack) => merge_yaml_with(stack, input, &options.merge).into_owned(),
+            None => (*input).clone(),
+        })
+        .collect();
+
+    // What the target, the layers above it and the input contribute over the layers below
+    let effective_refs: Vec<&Yaml> = effective.iter().collect();
+    let contributions: Vec<Yaml> = match below {
+        Some(ref below) => compute_diffs_with(&effective_refs, below, options)
+            .into_iter()
+            .map(|diff| diff.map_or(Yaml::Null, Cow::into_owned))
+            .collect(),
+        None => effective.clone(),
+    };
+    let contribution_refs: Vec<&Yaml> = contributions.iter().collect();
+    let (common, _) = diff_and_common_multiple_with(&contribution_refs, quorum, options);
+
+    let mut layer = common.map(Cow::into_owned);
+    for above_layer in above {
+        layer = layer.and_then(|layer| remove_shadowed(layer, above_layer, &options.merge, &mut YamlPath::root()));
+    }
+
+    let new_stack: Vec<&Yaml> = layers[..target]
+        .iter()
+        .copied()
+        .chain(layer.as_ref())
+        .chain(above.iter().copied())
+        .collect();
+    let new_stack = merge_layers(&new_stack, &options.merge);
+
+    // Keys the new stack sets but an input does not have are written as null
+    let diff_options = DiffOptions {
+        merge: options.merge.clone().with_null_mode(NullMode::Delete),
+        ..options.clone()
+    };
+    let diffs = match new_stack {
+        Some(ref new_stack) => compute_diffs_with(&effective_refs, new_stack, &diff_options)
+            .into_iter()
+            .map(|diff| diff.map(Cow::into_owned))
+            .collect(),
+        None => effective.iter().cloned().map(Some).collect(),
+    };
+
+    (layer, diffs)
+}
+
+/// Drops the values of `layer` that `above` replaces when it is merged on top.
+fn remove_shadowed(layer: Yaml, above: &Yaml, options: &MergeOptions, path: &mut YamlPath) -> Option<Yaml> {
+    match (layer, above) {
+        (Yaml::Hash(hash), Yaml::Hash(above_hash)) => {
+            if hash.is_empty() {
+                return Some(Yaml::Hash(hash));
+            }
+            let mut kept = Hash::new();
+            for (key, value) in hash {
+                match above_hash.get(&key) {
+                    None => {
+                        kept.insert(key, value);
+                    }
+                    Some(above_value) => {
+                        path.push(PathSegment::Key(key_to_string(&key)));
+                        if let Some(value) = remove_shadowed(value, above_value, options, path) {
+                            kept.insert(key, value);
+                        }
+                        path.pop();
+                    }
+                }
+            }
+            (!kept.is_empty()).then_some(Yaml::Hash(kept))
+        }
+        (Yaml::Array(array), Yaml::Array(_)) if *options.strategy(path) != MergeStrategy::Replace => {
+            Some(Yaml::Array(array))
+        }
+        _ => None,
+    }
+}
\ No newline at end of file
@@ -1,6 +1,26 @@
+pub mod argocd;
+pub mod chart;
+pub mod conflicts;
 pub mod deep_equal;
 pub mod diff;
+pub mod emitter;
+pub mod infer;
+pub mod json;
+pub mod kustomize;
+pub mod layers;
+pub mod loader;
+pub mod manifest;
 pub mod merge;
+pub mod merkle;
+pub mod patch;
+pub mod path;
+pub mod provenance;
+pub mod quantity;
+pub mod revision;
+pub mod schema;
+pub mod secrets;
 pub mod sorter;
+pub mod strategic;
+pub mod template;
 
 pub use diff::{compute_diff, diff_and_common_multiple};
\ No newline at end of file
@@ -0,0 +1,374 @@
+use std::collections::{BTreeMap, HashMap};
+
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser, Tag};
+use yaml_rust2::scanner::{Marker, ScanError, Scanner, TScalarStyle, Token, TokenType};
+use yaml_rust2::yaml::Hash;
+use yaml_rust2::Yaml;
+
+use crate::path::{key_to_string, PathSegment, YamlPath};
+
+/// How a scalar was written in its source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarStyle {
+    Plain,
+    SingleQuoted,
+    DoubleQuoted,
+    Literal,
+    Folded,
+}
+
+impl From<TScalarStyle> for ScalarStyle {
+    fn from(style: TScalarStyle) -> Self {
+        match style {
+            TScalarStyle::Plain => ScalarStyle::Plain,
+            TScalarStyle::SingleQuoted => ScalarStyle::SingleQuoted,
+            TScalarStyle::DoubleQuoted => ScalarStyle::DoubleQuoted,
+            TScalarStyle::Literal => ScalarStyle::Literal,
+            TScalarStyle::Folded => ScalarStyle::Folded,
+        }
+    }
+}
+
+/// The original presentation of a scalar.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScalarSource {
+    /// The value the scalar was loaded as.
+    pub value: Yaml,
+    pub style: ScalarStyle,
+    /// The scalar text as written, before it was converted to a number, boolean or null.
+    pub raw: String,
+    /// 1-indexed line of the scalar in its file.
+    pub line: usize,
+}
+
+/// Presentation of the scalars of one or more documents, by path.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    scalars: HashMap<YamlPath, Vec<ScalarSource>>,
+}
+
+impl SourceMap {
+    pub fn is_empty(&self) -> bool {
+        self.scalars.is_empty()
+    }
+
+    /// Returns the first recorded presentation of `value` at `path`, if the value there is unchanged.
+    pub fn lookup(&self, path: &YamlPath, value: &Yaml) -> Option<&ScalarSource> {
+        self.scalars.get(path)?.iter().find(|source| source.value == *value)
+    }
+
+    /// Returns all recorded scalars at `path`.
+    pub fn get(&self, path: &YamlPath) -> &[ScalarSource] {
+        self.scalars.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn insert(&mut self, path: YamlPath, source: ScalarSource) {
+        self.scalars.entry(path).or_default().push(source);
+    }
+
+    /// Returns the scalars under `segment`, with their paths relative to it.
+    pub fn subtree(&self, segment: &PathSegment) -> SourceMap {
+        let mut subtree = SourceMap::default();
+        for (path, sources) in &self.scalars {
+            if let Some((first, rest)) = path.segments().split_first() {
+                if first == segment {
+                    let mut relative = YamlPath::root();
+                    rest.iter().cloned().for_each(|s| relative.push(s));
+                    subtree.scalars.insert(relative, sources.clone());
+                }
+            }
+        }
+        subtree
+    }
+
+    /// Returns the scalars moved under `segment`.
+    pub fn nested(&self, segment: &PathSegment) -> SourceMap {
+        let mut nested = SourceMap::default();
+        for (path, sources) in &self.scalars {
+            let mut moved = YamlPath::root();
+            moved.push(segment.clone());
+            path.segments().iter().cloned().for_each(|s| moved.push(s));
+            nested.scalars.insert(moved, sources.clone());
+        }
+        nested
+    }
+
+    /// Adds the scalars of `other`, after the ones already recorded.
+    pub fn extend(&mut self, other: &SourceMap) {
+        for (path, sources) in &other.scalars {
+            self.scalars.entry(path.clone()).or_default().extend(sources.iter().cloned());
+        }
+    }
+}
+
+/// A document together with the presentation of its scalars.
+#[derive(Clone, Debug)]
+pub struct LoadedDocument {
+    pub yaml: Yaml,
+    pub source: SourceMap,
+}
+
+/// Loads all documents of `content`, like `YamlLoader::load_from_str`, and records
+/// the original presentation of every scalar.
+pub fn load_documents(content: &str) -> Result<Vec<LoadedDocument>, ScanError> {
+    let mut loader = SourceLoader::default();
+    let mut parser = Parser::new_from_str(content);
+    parser.load(&mut loader, true)?;
+    match loader.error {
+        Some(e) => Err(e),
+        None => Ok(loader.docs),
+    }
+}
+
+/// Loads the first document of `content`, if any.
+pub fn load_document(content: &str) -> Result<Option<LoadedDocument>, ScanError> {
+    Ok(load_documents(content)?.into_iter().next())
+}
+
+/// Checks whether `content` has comments, which the emitter cannot reproduce.
+///
+/// A `#` at the start of a line or after whitespace starts a comment unless it is part of a
+/// quoted or block scalar; plain scalars end before such a `#`. The scanner finds the quoted and
+/// block scalars in one pass, and the text is scanned once for a `#` outside of them. Content
+/// that does not scan has no comments to keep.
+pub fn has_comments(content: &str) -> bool {
+    let chars: Vec<char> = content.chars().collect();
+    let mut scalars = Vec::new();
+    let mut scanner = Scanner::new(content.chars());
+    for Token(mark, token) in scanner.by_ref() {
+        match token {
+            TokenType::Scalar(TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted, _) => {
+                scalars.push((mark.index(), quoted_end(&chars, mark.index())));
+            }
+            TokenType::Scalar(TScalarStyle::Literal | TScalarStyle::Folded, _) => {
+                scalars.push((mark.index(), block_end(&chars, mark.index())));
+            }
+            _ => {}
+        }
+    }
+    if scanner.get_error().is_some() {
+        return false;
+    }
+
+    let mut scalars = scalars.into_iter().peekable();
+    (0..chars.len()).any(|i| {
+        while scalars.next_if(|&(_, end)| end <= i).is_some() {}
+        let in_scalar = scalars.peek().is_some_and(|&(start, _)| start <= i);
+        chars[i] == '#' && (i == 0 || chars[i - 1].is_whitespace()) && !in_scalar
+    })
+}
+
+/// The index after the closing quote of the quoted scalar at `start`.
+fn quoted_end(chars: &[char], start: usize) -> usize {
+    let quote = chars[start];
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if quote == '"' => i += 1,
+            '\'' if quote == '\'' && chars.get(i + 1) == Some(&'\'') => i += 1,
+            c if c == quote => return i + 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// The index after the content of the block scalar whose `|` or `>` indicator is at `start`: its
+/// lines are indented deeper than the node it belongs to, and blank lines between them belong to
+/// it. The rest of the indicator line is not content, so a comment there is found.
+fn block_end(chars: &[char], start: usize) -> usize {
+    let line_start = chars[..start].iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1);
+    let indent = |from: usize| chars[from..].iter().take_while(|&&c| c == ' ').count();
+    // The node starts after the `- ` of the sequence entries on its line; a scalar that is an entry
+    // itself belongs to the last of them
+    let mut parent = line_start + indent(line_start);
+    let mut entry = parent;
+    while parent < start && chars[parent] == '-' && chars.get(parent + 1).is_some_and(|c| c.is_whitespace()) {
+        entry = parent;
+        parent += 1 + indent(parent + 1);
+    }
+    let parent_indent = if parent >= start { entry - line_start } else { parent - line_start };
+    let Some(mut end) = chars[start..].iter().position(|&c| c == '\n').map(|i| start + i + 1) else {
+        return chars.len();
+    };
+    let mut content_indent = None;
+    let mut line = end;
+    while line < chars.len() {
+        let next = chars[line..].iter().position(|&c| c == '\n').map_or(chars.len(), |i| line + i + 1);
+        let blank = chars[line..next].iter().all(|c| c.is_whitespace());
+        if !blank {
+            let width = indent(line);
+            if width <= parent_indent || content_indent.is_some_and(|content| width < content) {
+                break;
+            }
+            content_indent.get_or_insert(width);
+            end = next;
+        }
+        line = next;
+    }
+    end
+}
+
+/// A node under construction, with the path segment that leads to it from its parent
+/// (`None` for hash keys and the document root).
+struct Frame {
+    node: Yaml,
+    anchor: usize,
+    segment: Option<PathSegment>,
+}
+
+#[derive(Default)]
+struct SourceLoader {
+    docs: Vec<LoadedDocument>,
+    stack: Vec<Frame>,
+    key_stack: Vec<Yaml>,
+    anchors: BTreeMap<usize, Yaml>,
+    source: SourceMap,
+    error: Option<ScanError>,
+}
+
+impl MarkedEventReceiver for SourceLoader {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.on_event_impl(ev, mark) {
+            self.error = Some(e);
+        }
+    }
+}
+
+impl SourceLoader {
+    fn on_event_impl(&mut self, ev: Event, mark: Marker) -> Result<(), ScanError> {
+        match ev {
+            Event::DocumentStart | Event::Nothing | Event::StreamStart | Event::StreamEnd => {}
+            Event::DocumentEnd => {
+                let yaml = match self.stack.pop() {
+                    Some(frame) => frame.node,
+                    None => Yaml::BadValue,
+                };
+                self.docs.push(LoadedDocument {
+                    yaml,
+                    source: std::mem::take(&mut self.source),
+                });
+            }
+            Event::SequenceStart(anchor, _) => {
+                let segment = self.child_segment();
+                self.stack.push(Frame { node: Yaml::Array(Vec::new()), anchor, segment });
+            }
+            Event::MappingStart(anchor, _) => {
+                let segment = self.child_segment();
+                self.stack.push(Frame { node: Yaml::Hash(Hash::new()), anchor, segment });
+                self.key_stack.push(Yaml::BadValue);
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                if matches!(ev, Event::MappingEnd) {
+                    self.key_stack.pop();
+                }
+                if let Some(frame) = self.stack.pop() {
+                    self.insert_node(frame.node, frame.anchor, mark)?;
+                }
+            }
+            Event::Scalar(raw, style, anchor, tag) => {
+                let value = scalar_value(&raw, style, tag.as_ref());
+                if let Some(path) = self.child_path() {
+                    self.source.insert(
+                        path,
+                        ScalarSource {
+                            value: value.clone(),
+                            style: style.into(),
+                            raw,
+                            line: mark.line(),
+                        },
+                    );
+                }
+                self.insert_node(value, anchor, mark)?;
+            }
+            Event::Alias(id) => {
+                let node = self.anchors.get(&id).cloned().unwrap_or(Yaml::BadValue);
+                self.insert_node(node, 0, mark)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The segment leading from the innermost open collection to its next child,
+    /// or `None` if the next node is a hash key.
+    fn child_segment(&self) -> Option<PathSegment> {
+        match self.stack.last().map(|frame| &frame.node) {
+            Some(Yaml::Array(v)) => Some(PathSegment::Index(v.len())),
+            Some(Yaml::Hash(_)) => match self.key_stack.last() {
+                Some(Yaml::BadValue) | None => None,
+                Some(key) => Some(PathSegment::Key(key_to_string(key))),
+            },
+            _ => None,
+        }
+    }
+
+    /// The path of the next node, unless it is (part of) a hash key.
+    fn child_path(&self) -> Option<YamlPath> {
+        let mut path = YamlPath::root();
+        for frame in self.stack.iter().skip(1) {
+            path.push(frame.segment.clone()?);
+        }
+        if !self.stack.is_empty() {
+            path.push(self.child_segment()?);
+        }
+        Some(path)
+    }
+
+    fn insert_node(&mut self, node: Yaml, anchor: usize, mark: Marker) -> Result<(), ScanError> {
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        let Some(parent) = self.stack.last_mut() else {
+            self.stack.push(Frame { node, anchor: 0, segment: None });
+            return Ok(());
+        };
+        match parent.node {
+            Yaml::Array(ref mut v) => v.push(node),
+            Yaml::Hash(ref mut h) => {
+                let key = self.key_stack.last_mut().expect("hash without a key slot");
+                if key.is_badvalue() {
+                    *key = node;
+                } else {
+                    let key = std::mem::replace(key, Yaml::BadValue);
+                    if h.contains_key(&key) {
+                        return Err(ScanError::new_string(
+                            mark,
+                            format!("{:?}: duplicated key in mapping", key),
+                        ));
+                    }
+                    h.insert(key, node);
+                }
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+/// Converts a scalar event to a value exactly like `YamlLoader` does.
+fn scalar_value(v: &str, style: TScalarStyle, tag: Option<&Tag>) -> Yaml {
+    if style != TScalarStyle::Plain {
+        return Yaml::String(v.to_string());
+    }
+    match tag {
+        Some(Tag { handle, suffix }) if handle == "tag:yaml.org,2002:" => match suffix.as_str() {
+            "bool" => v.parse::<bool>().map_or(Yaml::BadValue, Yaml::Boolean),
+            "int" => v.parse::<i64>().map_or(Yaml::BadValue, Yaml::Integer),
+            "float" => match Yaml::from_str(v) {
+                Yaml::Real(_) | Yaml::Integer(_) => Yaml::Real(v.to_string()),
+                _ => Yaml::BadValue,
+            },
+            "null" => match v {
+                "~" | "null" => Yaml::Null,
+                _ => Yaml::BadValue,
+            },
+            _ => Yaml::String(v.to_string()),
+        },
+        Some(_) => Yaml::String(v.to_string()),
+        None => Yaml::from_str(v),
+    }
+}
\ No newline at end of file
@@ -3,26 +3,88 @@ use std::error::Error;
 use std::fs;
 use std::path::Path;
 
-use clap::Parser;
+use clap::builder::RangedU64ValueParser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use log::{info, warn};
-use yaml_rust2::{Yaml, YamlEmitter, YamlLoader};
-use yabe::diff::{compute_diff, diff_and_common_multiple};
-use yabe::merge::merge_yaml;
-use yabe::sorter::sort_yaml;
+use rayon::prelude::*;
+use yaml_rust2::yaml::Hash;
+use yaml_rust2::{Yaml, YamlLoader};
+use yabe::argocd::{application_set, applications, update_manifests, ArgoConfig, Environment, ManifestKind};
+use yabe::chart::{is_chart, load_chart_schema, load_chart_values};
+use yabe::conflicts::{find_type_conflicts_with, ConflictPolicy};
+use yabe::deep_equal::EqualityPolicy;
+use yabe::diff::{compute_diffs_with, diff_and_common_multiple_with, DiffOptions, HoistRules};
+use yabe::emitter::{
+    emit_documents, emit_documents_preserving, emit_yaml, emit_yaml_preserving, emit_yaml_with, EmitterConfig, QuoteStyle,
+};
+use yabe::infer::infer_sort_config;
+use yabe::json::to_json;
+use yabe::kustomize::{base_files, overlays, KustomizeFile, PatchFormat};
+use yabe::layers::{extract_into_layer, merge_layers};
+use yabe::loader::{has_comments, load_document, load_documents, LoadedDocument, SourceMap};
+use yabe::manifest::{bundle, restore_identity, unbundle};
+use yabe::merge::{merge_yaml_with, MergeOptions, NullMode};
+use yabe::patch::{json_patch, merge_patch, operations_to_yaml};
+use yabe::path::YamlPath;
+use yabe::provenance::{merge_layers_traced, same_and_overridden, Origin, Provenance};
+use yabe::revision::{read_input, revision_path};
+use yabe::schema::{infer_schema, validate_with};
+use yabe::secrets::{is_sops_encrypted, SecretPolicy};
+use yabe::sorter::{sort_yaml, sort_yaml_with_reference};
+use yabe::template::{input_names, output_paths, shared_path, PathTemplate, TemplateError};
 
-/// Command-line arguments
+/// Command-line interface
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    args: Args,
+
+    /// Enable debug logging
+    #[arg(long = "debug", global = true)]
+    debug: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Infer a sort configuration from existing YAML files
+    InferConfig(InferConfigArgs),
+    /// Format YAML files in place with the sort configuration
+    Fmt(FmtArgs),
+    /// Show which layer set each effective value of a stack of values files
+    Blame(BlameArgs),
+    /// Infer a JSON Schema for the values from the input files and the read-base
+    InferSchema(InferSchemaArgs),
+}
+
+/// Extraction arguments
+#[derive(clap::Args)]
 struct Args {
-    /// Helm chart values file
+    /// Helm chart values file, or a chart directory or .tgz archive to read its values.yaml from
     #[arg(short = 'r', long = "read-base", value_name = "READ_BASE")]
     read_only_base: Option<String>,
 
+    /// Fold the default values of the subcharts of a --read-base chart in, under their alias keys
+    #[arg(long = "subcharts", requires = "read_only_base")]
+    subcharts: bool,
+
     /// Base YAML file to merge with input files
     #[arg(short = 'b', long = "base", value_name = "WRITE_BASE")]
     base: Option<String>,
 
-    /// Input YAML files
+    /// Values layer between the read-base and the input files, lowest first (repeatable)
+    #[arg(short = 'l', long = "layer", value_name = "LAYER", conflicts_with = "base")]
+    layers: Vec<String>,
+
+    /// Extract the common values into this layer instead of a new base file
+    #[arg(long = "extract-into", value_name = "LAYER", requires = "layers")]
+    extract_into: Option<String>,
+
+    /// Input YAML files, or `REV:path` to read a file at a git revision
     #[arg(required = true)]
     input_files: Vec<String>,
 
@@ -34,212 +96,1201 @@ struct Args {
     #[arg(short = 'o', long = "out", default_value = "./out")]
     out_folder: String,
 
-    /// Enable debug logging
-    #[arg(long = "debug")]
-    debug: bool,
-
     /// Quorum percentage (0-100)
     #[arg(short = 'q', long = "quorum", default_value_t = 51)]
     quorum: u8,
 
-    /// Base file output path
+    /// Base file output path; may refer to {out}, {dir}, {group}, {ext} and {format} when all input files agree on them
     #[arg(long = "base-out-path", default_value = "./base.yaml")]
     base_out_path: String,
 
+    /// Diff file output path: {out} is the output folder, {dir}, {stem} and {group} the directory, file name
+    /// without extension and directory name of the input file, {ext} its extension, {format} yaml or json
+    /// and {index} its position
+    #[arg(long = "diff-out-path", value_name = "TEMPLATE", default_value = "{out}/{stem}_diff.{format}", conflicts_with = "inplace")]
+    diff_out_path: String,
+
     /// Sort configuration file path
-    #[arg(long = "sort-config-path", default_value = "./sort-config.yaml")]
+    #[arg(long = "sort-config-path", visible_alias = "config", default_value = "./sort-config.yaml")]
+    sort_config_path: String,
+
+    /// Order keys as they appear in the read-base, before applying the sort configuration
+    #[arg(long = "read-base-order", requires = "read_only_base")]
+    read_base_order: bool,
+
+    #[command(flatten)]
+    target: TargetArgs,
+
+    /// Format of the diff files: yaml (override files), merge-patch (RFC 7386 JSON Merge Patch)
+    /// or json-patch (RFC 6902 JSON Patch)
+    #[arg(
+        long = "diff-format",
+        value_name = "FORMAT",
+        default_value = "yaml",
+        value_parser = ["yaml", "merge-patch", "json-patch"],
+        conflicts_with = "inplace"
+    )]
+    diff_format: String,
+
+    /// What to do when files disagree on the kind of a value (e.g. a map and a string):
+    /// ignore, warn or error [default: typeConflicts of the configuration file, or warn]
+    #[arg(long = "type-conflicts", value_name = "POLICY", value_parser = ["ignore", "warn", "error"])]
+    type_conflicts: Option<String>,
+
+    /// Write ArgoCD manifests that wire the value files of each input file together to this file,
+    /// updating the manifests it already has
+    #[arg(long = "argocd", value_name = "PATH")]
+    argocd: Option<String>,
+
+    /// Kind of the ArgoCD manifests: application (one per input file) or application-set
+    #[arg(
+        long = "argocd-kind",
+        value_name = "KIND",
+        default_value = "application",
+        value_parser = ["application", "application-set"]
+    )]
+    argocd_kind: String,
+
+    /// Write a kustomize base/ and an overlays/<name>/ directory per input file into this directory
+    /// instead of a base file and diffs; the input files are Kubernetes manifests
+    #[arg(
+        long = "kustomize",
+        value_name = "DIR",
+        conflicts_with_all = ["inplace", "layers", "argocd", "diff_format", "schema"]
+    )]
+    kustomize: Option<String>,
+
+    /// Patches of the kustomize overlays: strategic (strategic merge patches) or json6902
+    #[arg(
+        long = "kustomize-patches",
+        value_name = "FORMAT",
+        default_value = "strategic",
+        value_parser = ["strategic", "json6902"]
+    )]
+    kustomize_patches: String,
+
+    /// JSON Schema to validate the effective values of each input file against before anything is written
+    /// [default: the values.schema.json of a --read-base chart]
+    #[arg(long = "schema", value_name = "PATH")]
+    schema: Option<String>,
+
+    /// Extract and validate without writing any files
+    #[arg(long = "check")]
+    check: bool,
+
+    /// Number of threads reading, diffing and rendering the files [default: one per CPU]
+    #[arg(short = 'j', long = "jobs", value_name = "N")]
+    jobs: Option<usize>,
+
+    #[command(flatten)]
+    emitter: EmitterArgs,
+}
+
+/// Merge semantics, shared by extraction and `blame` so both see the same effective values
+#[derive(clap::Args)]
+struct TargetArgs {
+    /// Merge semantics of the deployment target: helm (a null deletes the key), kubernetes
+    /// (multi-document manifests with strategic merge patches) or plain [default: plain]
+    #[arg(id = "target", long = "target", value_name = "TARGET", value_parser = ["helm", "kubernetes", "plain"])]
+    name: Option<String>,
+}
+
+impl TargetArgs {
+    fn is(&self, target: &str) -> bool {
+        self.name.as_deref() == Some(target)
+    }
+
+    /// With Helm semantics a null deletes the key; Kubernetes semantics come with the strategic merge.
+    fn null_mode(&self) -> NullMode {
+        if self.is("helm") {
+            NullMode::Delete
+        } else {
+            NullMode::Keep
+        }
+    }
+}
+
+/// Output formatting options, overriding the `emitter` section of the configuration file
+#[derive(clap::Args)]
+struct EmitterArgs {
+    /// Spaces per indentation level of hashes (at least 1)
+    #[arg(long = "indent", value_name = "WIDTH", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    indent: Option<usize>,
+
+    /// Sequence style inside hashes: indented or compact
+    #[arg(long = "sequence-style", value_name = "STYLE", value_parser = ["indented", "compact"])]
+    sequence_style: Option<String>,
+
+    /// Quotes for strings that cannot be written plain: double or single
+    #[arg(long = "quote-style", value_name = "STYLE", value_parser = ["double", "single"])]
+    quote_style: Option<String>,
+
+    /// Fold long strings to keep lines within this width
+    #[arg(long = "line-width", value_name = "WIDTH")]
+    line_width: Option<usize>,
+
+    /// Write scalar lists in flow style when they fit within this width
+    #[arg(long = "flow-sequence-width", value_name = "WIDTH")]
+    flow_sequence_width: Option<usize>,
+
+    /// Start every document with `---`
+    #[arg(long = "explicit-start")]
+    explicit_start: bool,
+}
+
+impl Args {
+    /// Rejects the combinations that depend on the values of the options, which `conflicts_with`
+    /// cannot express, with the same error the parser reports for conflicting options.
+    fn check_conflicts(&self, command: &mut clap::Command) -> Result<(), clap::Error> {
+        let target = self.target.name.as_deref();
+        let conflict = if self.kustomize.is_some() && target.is_some_and(|t| t != "kubernetes") {
+            Some("--kustomize writes Kubernetes manifests and needs --target kubernetes")
+        } else if target == Some("kubernetes") && self.diff_format != "yaml" {
+            Some("--diff-format is only supported for values files, not with --target kubernetes")
+        } else if target == Some("kubernetes") && self.schema.is_some() {
+            Some("--schema validates values files, not Kubernetes manifests")
+        } else if self.argocd.is_some() && (target == Some("kubernetes") || self.diff_format != "yaml") {
+            Some("--argocd needs values files: --diff-format yaml and a target other than kubernetes")
+        } else {
+            None
+        };
+        match conflict {
+            Some(message) => Err(command.error(ErrorKind::ArgumentConflict, message)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl EmitterArgs {
+    /// Applies the command-line overrides to the configured emitter settings.
+    fn apply(&self, mut config: EmitterConfig) -> Result<EmitterConfig, Box<dyn Error>> {
+        if let Some(indent) = self.indent {
+            config.indent = indent;
+        }
+        if let Some(ref style) = self.sequence_style {
+            config.indent_sequences = style == "indented";
+        }
+        if let Some(ref style) = self.quote_style {
+            config.quote_style = style.parse::<QuoteStyle>()?;
+        }
+        if self.line_width.is_some() {
+            config.line_width = self.line_width;
+        }
+        if self.flow_sequence_width.is_some() {
+            config.flow_sequence_width = self.flow_sequence_width;
+        }
+        config.explicit_start |= self.explicit_start;
+        Ok(config)
+    }
+}
+
+/// Arguments of the `infer-config` subcommand
+#[derive(clap::Args)]
+struct InferConfigArgs {
+    /// YAML files to analyze
+    #[arg(required = true)]
+    files: Vec<String>,
+
+    /// Write the configuration to this path instead of stdout
+    #[arg(short = 'o', long = "out", value_name = "CONFIG_PATH")]
+    out: Option<String>,
+}
+
+/// Arguments of the `fmt` subcommand
+#[derive(clap::Args)]
+struct FmtArgs {
+    /// YAML files to format
+    #[arg(required = true)]
+    files: Vec<String>,
+
+    /// Report files that are not formatted instead of rewriting them
+    #[arg(long = "check")]
+    check: bool,
+
+    /// Sort configuration file path
+    #[arg(long = "sort-config-path", visible_alias = "config", default_value = "./sort-config.yaml")]
+    sort_config_path: String,
+
+    #[command(flatten)]
+    emitter: EmitterArgs,
+}
+
+/// Arguments of the `blame` subcommand
+#[derive(clap::Args)]
+struct BlameArgs {
+    /// Values files, lowest layer first
+    #[arg(required = true)]
+    files: Vec<String>,
+
+    #[command(flatten)]
+    target: TargetArgs,
+
+    /// Configuration file with the merge rules
+    #[arg(long = "sort-config-path", visible_alias = "config", default_value = "./sort-config.yaml")]
+    sort_config_path: String,
+}
+
+/// Arguments of the `infer-schema` subcommand
+#[derive(clap::Args)]
+struct InferSchemaArgs {
+    /// Values files to analyze
+    #[arg(required = true)]
+    files: Vec<String>,
+
+    /// Read-only base the files override: a values file, a chart directory or a packaged chart (.tgz)
+    #[arg(short = 'r', long = "read-base", value_name = "READ_ONLY_BASE")]
+    read_only_base: Option<String>,
+
+    /// Write the schema to this path instead of stdout
+    #[arg(short = 'o', long = "out", value_name = "SCHEMA_PATH")]
+    out: Option<String>,
+
+    /// Largest number of distinct values a string may have to be inferred as an enum
+    #[arg(long = "enum-limit", value_name = "COUNT", default_value_t = 5)]
+    enum_limit: usize,
+
+    /// Configuration file with the secret rules; secret values are never listed in an enum
+    #[arg(long = "sort-config-path", visible_alias = "config", default_value = "./sort-config.yaml")]
     sort_config_path: String,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+    let mut command = Cli::command();
+    let cli = Cli::from_arg_matches(&command.get_matches_mut()).unwrap_or_else(|e| e.exit());
 
-    if args.debug {
+    if cli.debug {
         env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).init();
     } else {
         env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     }
 
-    info!("Starting the YAML diffing program.");
+    match cli.command {
+        Some(Command::InferConfig(args)) => infer_config(args),
+        Some(Command::Fmt(args)) => fmt(args),
+        Some(Command::Blame(args)) => blame(args),
+        Some(Command::InferSchema(args)) => infer_schema_file(args),
+        None => {
+            cli.args.check_conflicts(&mut command).unwrap_or_else(|e| e.exit());
+            extract(cli.args)
+        }
+    }
+}
+
+/// Infers a sort configuration from the given files and writes it out.
+fn infer_config(args: InferConfigArgs) -> Result<(), Box<dyn Error>> {
+    let mut docs = Vec::new();
+    for filename in &args.files {
+        info!("Reading input file: {}", filename);
+        let content = read_input(filename)?;
+        docs.extend(YamlLoader::load_from_str(&content)?);
+    }
+
+    let doc_refs: Vec<&Yaml> = docs.iter().collect();
+    let config = infer_sort_config(&doc_refs);
+
+    let out_str = emit_yaml(&config)?;
+
+    match args.out {
+        Some(out) => {
+            write_output(&out, out_str)?;
+            info!("Sort configuration written to {}", out);
+        }
+        None => print!("{}", out_str),
+    }
+    Ok(())
+}
 
-    let input_filenames = args.input_files;
+/// Infers a JSON Schema from the given values files and the read-base and writes it out.
+fn infer_schema_file(args: InferSchemaArgs) -> Result<(), Box<dyn Error>> {
+    let config = load_sort_config(&args.sort_config_path)?;
+    let secrets = SecretPolicy::from_yaml(&config["secrets"]);
 
-    let quorum_percentage = (args.quorum as f64) / 100.0;
+    let read_only_base = match args.read_only_base {
+        Some(ref path) if revision_path(path).is_none() && is_chart(Path::new(path)) => {
+            info!("Reading chart values: {}", path);
+            Some(load_chart_values(Path::new(path), false)?)
+        }
+        Some(ref path) => {
+            info!("Reading helm values file: {}", path);
+            read_lower_layer(path, false)?.map(|doc| doc.yaml)
+        }
+        None => None,
+    };
 
-    let base_out_path = args.base_out_path;
+    let mut docs = Vec::new();
+    for filename in &args.files {
+        info!("Reading input file: {}", filename);
+        match read_document(filename, false)? {
+            Some(doc) if is_sops_encrypted(&doc.yaml) => warn!("Skipping {}: it is encrypted with sops", filename),
+            Some(doc) => docs.push(doc.yaml),
+            None => warn!("No YAML documents in {}", filename),
+        }
+    }
 
-    let out_folder = args.out_folder;
+    let doc_refs: Vec<&Yaml> = docs.iter().collect();
+    let schema = infer_schema(read_only_base.as_ref(), &doc_refs, args.enum_limit, &secrets);
+    let out_str = to_json(&schema);
 
-    let config = if !args.sort_config_path.is_empty() {
-        info!("Reading sort configuration file: {}", args.sort_config_path);
-        let content = fs::read_to_string(&args.sort_config_path);
-        if let Ok(content) = content {
-            YamlLoader::load_from_str(&content)?.into_iter().next().unwrap_or(Yaml::Null)
+    match args.out {
+        Some(out) => {
+            write_output(&out, out_str)?;
+            info!("Schema written to {}", out);
+        }
+        None => print!("{}", out_str),
+    }
+    Ok(())
+}
+
+/// Formats the given files in place, or only reports unformatted files in check mode.
+fn fmt(args: FmtArgs) -> Result<(), Box<dyn Error>> {
+    let config = load_sort_config(&args.sort_config_path)?;
+    let emitter_config = args.emitter.apply(EmitterConfig::from_yaml(&config["emitter"]))?;
+
+    let mut unformatted = Vec::new();
+    let mut commented = Vec::new();
+    for filename in &args.files {
+        let content = fs::read_to_string(filename)?;
+        let loaded = load_documents(&content)?;
+        if loaded.iter().any(|doc| is_sops_encrypted(&doc.yaml)) {
+            warn!("Skipping {}: it is encrypted with sops, and formatting would break its MAC", filename);
+            continue;
+        }
+        if has_comments(&content) {
+            warn!("Skipping {}: it has comments, and formatting would drop them", filename);
+            commented.push(filename.as_str());
+            continue;
+        }
+        let (docs, sources): (Vec<Yaml>, Vec<SourceMap>) = loaded
+            .into_iter()
+            .map(|doc| (sort_output(&doc.yaml, &config, None).into_owned(), doc.source))
+            .unzip();
+        let formatted = emit_documents_preserving(&docs, &sources, &emitter_config)?;
+
+        if formatted == content {
+            info!("{} is already formatted", filename);
+        } else if args.check {
+            warn!("{} is not formatted", filename);
+            unformatted.push(filename.as_str());
         } else {
-            warn!("Failed to read sort configuration file: {}", args.sort_config_path);
-            Yaml::Null
+            fs::write(filename, formatted)?;
+            info!("Formatted {}", filename);
         }
+    }
+
+    // Files with comments cannot be checked, so they fail the check
+    let mut problems = Vec::new();
+    if !unformatted.is_empty() {
+        problems.push(format!("{} file(s) are not formatted: {}", unformatted.len(), unformatted.join(", ")));
+    }
+    if args.check && !commented.is_empty() {
+        problems.push(format!("{} file(s) have comments: {}", commented.len(), commented.join(", ")));
+    }
+    if problems.is_empty() {
+        Ok(())
     } else {
-        Yaml::Null
+        Err(problems.join("; ").into())
+    }
+}
+
+/// Prints every effective value of a stack of files with the file and line that set it,
+/// and the layers it overrode.
+fn blame(args: BlameArgs) -> Result<(), Box<dyn Error>> {
+    let config = load_sort_config(&args.sort_config_path)?;
+    let kubernetes = args.target.is("kubernetes");
+    let null_mode = args.target.null_mode();
+    let options = merge_options(&config, kubernetes, null_mode);
+    let secrets = SecretPolicy::from_yaml(&config["secrets"]);
+
+    let mut layers = Vec::new();
+    for filename in &args.files {
+        info!("Reading layer: {}", filename);
+        match read_document(filename, kubernetes)? {
+            Some(doc) if is_encrypted(&doc.yaml, kubernetes) => warn!("Skipping {}: it is encrypted with sops", filename),
+            Some(doc) => layers.push((filename.as_str(), doc)),
+            None => warn!("No YAML documents in {}", filename),
+        }
+    }
+    let layer_refs: Vec<(&str, &LoadedDocument)> = layers.iter().map(|(file, doc)| (*file, doc)).collect();
+    let Some((_, provenance)) = merge_layers_traced(&layer_refs, &options) else {
+        return Ok(());
     };
 
-    let read_only_base = if let Some(ref read_only_base) = args.read_only_base {
-        info!("Reading helm values file: {}", read_only_base);
-        let content = fs::read_to_string(read_only_base)?;
-        YamlLoader::load_from_str(&content)?.into_iter().next()
+    for (path, origins) in provenance.iter() {
+        let Some((origin, overridden)) = origins.split_first() else {
+            continue;
+        };
+        // Secret values are redacted, their origins are not
+        let value = |value: &Yaml| to_json(&secrets.redact(path, value)).trim_end().to_string();
+        let mut line = format!("{}: {}  {}", path, value(&origin.value), origin);
+        // Only the layers with another value were overridden; the others set the same value
+        let (same, overridden) = same_and_overridden(&origin.value, overridden);
+        let mut notes = Vec::new();
+        if !same.is_empty() {
+            let same: Vec<String> = same.iter().map(|o| o.to_string()).collect();
+            notes.push(format!("same as {}", same.join(", ")));
+        }
+        if !overridden.is_empty() {
+            let overridden: Vec<String> = overridden
+                .iter()
+                .map(|o| format!("{} {}", o, value(&o.value)))
+                .collect();
+            notes.push(format!("overrides {}", overridden.join(", ")));
+        }
+        if !notes.is_empty() {
+            line.push_str(&format!(" ({})", notes.join("; ")));
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// The merge rules of the configuration file, with the semantics of the deployment target.
+fn merge_options(config: &Yaml, kubernetes: bool, null_mode: NullMode) -> MergeOptions {
+    let options = MergeOptions::from_yaml(&config["merge"]);
+    if kubernetes {
+        options.with_strategic_merge()
     } else {
-        None
+        options.with_null_mode(null_mode)
+    }
+}
+
+/// Reads `typeConflicts` from the configuration file, defaulting to warn.
+fn conflict_policy(config: &Yaml) -> ConflictPolicy {
+    match config["typeConflicts"].as_str().map(str::parse::<ConflictPolicy>) {
+        Some(Ok(policy)) => policy,
+        Some(Err(e)) => {
+            warn!("Ignoring typeConflicts: {}", e);
+            ConflictPolicy::default()
+        }
+        None => ConflictPolicy::default(),
+    }
+}
+
+/// Reports the paths where the files disagree on the kind of value, failing with
+/// [`ConflictPolicy::Error`] if there are any.
+fn check_type_conflicts(
+    policy: ConflictPolicy,
+    docs: &[(&str, &Yaml)],
+    options: &MergeOptions,
+) -> Result<(), Box<dyn Error>> {
+    if policy == ConflictPolicy::Ignore {
+        return Ok(());
+    }
+    let conflicts = find_type_conflicts_with(docs, options);
+    for conflict in &conflicts {
+        warn!("Type conflict at {}", conflict);
+    }
+    if policy == ConflictPolicy::Error && !conflicts.is_empty() {
+        return Err(format!("{} type conflict(s) between the input files", conflicts.len()).into());
+    }
+    Ok(())
+}
+
+/// Validates the effective values of each input file, its diff merged over `lower_layers`, against
+/// `schema`, reporting every violation with the layer that set the value.
+fn check_schema(
+    schema: &Yaml,
+    lower_layers: &[(&str, &Yaml)],
+    input_names: &[&str],
+    diffs: &[Option<Yaml>],
+    sources: &[SourceMap],
+    options: &DiffOptions,
+) -> Result<(), Box<dyn Error>> {
+    let lower: Vec<(&str, LoadedDocument)> = lower_layers
+        .iter()
+        .map(|(name, doc)| (*name, LoadedDocument { yaml: (*doc).clone(), source: SourceMap::default() }))
+        .collect();
+    // The values of a diff come from its input file, where its lines are known
+    let reports: Vec<Vec<String>> = input_names
+        .par_iter()
+        .zip(diffs)
+        .zip(sources)
+        .map(|((input, diff), source)| {
+            let diff = LoadedDocument {
+                yaml: diff.clone().unwrap_or_else(|| Yaml::Hash(Hash::new())),
+                source: source.clone(),
+            };
+            let mut stack: Vec<(&str, &LoadedDocument)> = lower.iter().map(|(name, doc)| (*name, doc)).collect();
+            stack.push((input, &diff));
+            let Some((values, provenance)) = merge_layers_traced(&stack, &options.merge) else {
+                return Vec::new();
+            };
+            validate_with(schema, &values, &options.secrets)
+                .iter()
+                .map(|violation| match origin(&provenance, &violation.path) {
+                    Some(origin) => format!("{}: {} (set in {})", input, violation, origin),
+                    None => format!("{}: {}", input, violation),
+                })
+                .collect()
+        })
+        .collect();
+    let violations: Vec<String> = reports.into_iter().flatten().collect();
+    for violation in &violations {
+        warn!("Schema violation in {}", violation);
+    }
+    if !violations.is_empty() {
+        return Err(format!("{} schema violation(s) in the effective values; nothing was written", violations.len()).into());
+    }
+    Ok(())
+}
+
+/// The layer that set the value at `path`, or for a map or list the first value inside it.
+fn origin<'a>(provenance: &'a Provenance, path: &YamlPath) -> Option<&'a Origin> {
+    provenance
+        .iter()
+        .find(|(leaf, _)| leaf.segments().starts_with(path.segments()))
+        .and_then(|(_, origins)| origins.first())
+}
+
+/// Reads the sort configuration, falling back to no sorting when it cannot be read.
+fn load_sort_config(path: &str) -> Result<Yaml, Box<dyn Error>> {
+    if path.is_empty() {
+        return Ok(Yaml::Null);
+    }
+    info!("Reading sort configuration file: {}", path);
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(YamlLoader::load_from_str(&content)?.into_iter().next().unwrap_or(Yaml::Null)),
+        Err(_) => {
+            warn!("Failed to read sort configuration file: {}", path);
+            Ok(Yaml::Null)
+        }
+    }
+}
+
+/// Extracts the common base and per-file diffs from the input files.
+fn extract(args: Args) -> Result<(), Box<dyn Error>> {
+    info!("Starting the YAML diffing program.");
+
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new().num_threads(jobs).build_global()?;
+    }
+
+    let config = load_sort_config(&args.sort_config_path)?;
+    let emitter_config = args.emitter.apply(EmitterConfig::from_yaml(&config["emitter"]))?;
+    let kubernetes = args.target.is("kubernetes") || args.kustomize.is_some();
+    let null_mode = args.target.null_mode();
+    let diff_options = DiffOptions {
+        merge: merge_options(&config, kubernetes, null_mode),
+        equality: EqualityPolicy::from_yaml(&config["equality"]),
+        secrets: SecretPolicy::from_yaml(&config["secrets"]),
+        hoist: HoistRules::from_yaml(&config["extract"]),
+    };
+
+    if args.inplace {
+        if let Some(input) = args.input_files.iter().find(|input| revision_path(input).is_some()) {
+            return Err(format!("{} is read from git and cannot be modified in place", input).into());
+        }
+    }
+
+    // The output paths are known up front, so colliding templates fail before any work
+    let (base_out_path, diff_paths) = output_file_paths(&args)?;
+
+    let inputs = Inputs::read(&args, kubernetes, diff_paths)?;
+
+    let policy = match args.type_conflicts {
+        Some(ref policy) => policy.parse::<ConflictPolicy>()?,
+        None => conflict_policy(&config),
     };
+    check_type_conflicts(policy, &inputs.named_docs(&args), &diff_options.merge)?;
 
-    // Read and parse the existing base file if provided
-    let existing_base = if let Some(ref base_path) = args.base {
-        info!("Reading existing base YAML file: {}", base_path);
-        let content = fs::read_to_string(base_path)?;
-        YamlLoader::load_from_str(&content)?.into_iter().next()
+    let quorum = (args.quorum as f64) / 100.0;
+    let (base, mut per_file_diffs) = if args.layers.is_empty() {
+        info!("Computing common base and per-file diffs among the diffs with quorum {}%.", args.quorum);
+        extract_base(&inputs, quorum, &diff_options)
     } else {
-        None
+        extract_into_stack(&args, &inputs, quorum, &diff_options)?
     };
 
-    // Read and parse each YAML input file into an object
-    let mut all_docs = Vec::new();
-    for filename in &input_filenames {
-        info!("Reading input file: {}", filename);
-        let content = fs::read_to_string(filename)?;
-        if let Some(doc) = YamlLoader::load_from_str(&content)?.into_iter().next() {
-            all_docs.push(doc);
+    // Every patch of a manifest diff must still name the resource it applies to
+    if kubernetes {
+        per_file_diffs.par_iter_mut().zip(&inputs.docs).for_each(|(diff, doc)| {
+            if let Some(diff) = diff {
+                let references: Vec<&Yaml> = [Some(doc), base.as_ref(), inputs.base.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .chain(&inputs.layers)
+                    .chain(inputs.read_base.as_ref())
+                    .collect();
+                restore_identity(diff, &references);
+            }
+        });
+    }
+
+    let empty_layer = Yaml::Hash(Hash::new());
+    let lower_layers = inputs.lower_layers(&args, base.as_ref(), &base_out_path, &empty_layer);
+
+    // The effective values are validated against the schema of the chart, or the one given
+    let schema = match args.schema {
+        Some(ref path) => {
+            info!("Reading schema: {}", path);
+            YamlLoader::load_from_str(&read_input(path)?)?.into_iter().next()
+        }
+        None => inputs.chart_schema.clone(),
+    };
+    if let Some(ref schema) = schema {
+        info!("Validating the effective values of {} input file(s) against the schema.", inputs.names.len());
+        check_schema(schema, &lower_layers, &inputs.names, &per_file_diffs, &inputs.sources, &diff_options)?;
+    }
+    if args.check {
+        info!("Check passed; no files written.");
+        return Ok(());
+    }
+
+    // A JSON patch applies to the values the diff is merged over
+    let stack: Vec<&Yaml> = lower_layers.iter().map(|(_, doc)| *doc).collect();
+    let patch_base = merge_layers(&stack, &diff_options.merge);
+
+    let renderer = Renderer {
+        config: &config,
+        emitter: &emitter_config,
+        kubernetes,
+        sort_reference: inputs.read_base.as_ref().filter(|_| args.read_base_order),
+    };
+
+    if let Some(ref dir) = args.kustomize {
+        let format = args.kustomize_patches.parse::<PatchFormat>()?;
+        write_kustomize_output(dir, format, &inputs, &per_file_diffs, patch_base.as_ref(), &diff_options.merge, &renderer)?;
+        info!("Program completed successfully.");
+        return Ok(());
+    }
+
+    let base_written = base.is_some();
+    write_base(&args, &inputs, base.as_ref(), &base_out_path, &renderer)?;
+    let has_diffs = write_diffs(&args, &inputs, &per_file_diffs, patch_base.as_ref(), &diff_options.merge, &renderer)?;
+
+    if let Some(ref argocd_path) = args.argocd {
+        let shared = shared_value_files(&args, base_written.then_some(base_out_path.as_str()));
+        let environments = environments(&inputs, has_diffs)?;
+        let kind = args.argocd_kind.parse::<ManifestKind>()?;
+        write_argocd(argocd_path, kind, &ArgoConfig::from_yaml(&config["argocd"]), &shared, &environments, &emitter_config)?;
+    }
+
+    info!("Program completed successfully.");
+    Ok(())
+}
+
+/// The path of the base file, or the `--extract-into` layer, and the paths of the diffs: the
+/// input files themselves with `--in-place`, or those of the `--diff-out-path` template.
+fn output_file_paths(args: &Args) -> Result<(String, Vec<String>), Box<dyn Error>> {
+    let extension = if args.diff_format == "yaml" { "yaml" } else { "json" };
+    let inputs: Vec<&str> = args.input_files.iter().map(String::as_str).collect();
+    let base_out_path = match args.extract_into {
+        Some(ref layer) => layer.clone(),
+        None => shared_path(&args.base_out_path.parse::<PathTemplate>()?, &inputs, &args.out_folder, "yaml")?,
+    };
+    // ArgoCD environments and kustomize overlays are named after the input files, before anything is written
+    if args.argocd.is_some() || args.kustomize.is_some() {
+        input_names(&inputs)?;
+    }
+    if args.inplace {
+        return Ok((base_out_path, args.input_files.clone()));
+    }
+    let reserved: Vec<&str> = [base_out_path.as_str()]
+        .into_iter()
+        .chain(args.read_only_base.as_deref())
+        .chain(args.base.as_deref())
+        .chain(args.layers.iter().map(String::as_str))
+        .collect();
+    let template = args.diff_out_path.parse::<PathTemplate>()?;
+    let diff_paths = output_paths(&template, &inputs, &args.out_folder, extension, &reserved)?;
+    Ok((base_out_path, diff_paths))
+}
+
+/// The files an extraction reads: the lower layers, and the input files with the presentation of
+/// their scalars.
+struct Inputs<'a> {
+    read_base: Option<Yaml>,
+    /// The `values.schema.json` of a chart read-base, unless `--schema` is given.
+    chart_schema: Option<Yaml>,
+    /// The `--base` file.
+    base: Option<Yaml>,
+    /// The `--layer` files; an empty layer file is an empty hash.
+    layers: Vec<Yaml>,
+    /// The presentation of the scalars of the `--extract-into` layer.
+    target_source: SourceMap,
+    /// The input files with values, in input order; encrypted and empty ones are skipped.
+    names: Vec<&'a str>,
+    docs: Vec<Yaml>,
+    sources: Vec<SourceMap>,
+    /// The paths the diffs of the input files go to.
+    diff_files: Vec<String>,
+}
+
+impl<'a> Inputs<'a> {
+    fn read(args: &'a Args, kubernetes: bool, diff_paths: Vec<String>) -> Result<Self, Box<dyn Error>> {
+        let (read_base, chart_schema) = match args.read_only_base {
+            Some(ref path) => read_read_base(path, args, kubernetes)?,
+            None => (None, None),
+        };
+
+        // Read and parse the existing base file if provided
+        let base = match args.base {
+            Some(ref base_path) => {
+                info!("Reading existing base YAML file: {}", base_path);
+                read_lower_layer(base_path, kubernetes)?
+            }
+            None => None,
+        };
+        let (base, mut base_source) = match base {
+            Some(doc) => (Some(doc.yaml), doc.source),
+            None => (None, SourceMap::default()),
+        };
+
+        let mut layers = Vec::new();
+        let mut target_source = SourceMap::default();
+        for layer in &args.layers {
+            info!("Reading layer: {}", layer);
+            match read_lower_layer(layer, kubernetes)? {
+                Some(doc) if !doc.yaml.is_badvalue() => {
+                    base_source.extend(&doc.source);
+                    if args.extract_into.as_ref() == Some(layer) {
+                        target_source = doc.source;
+                    }
+                    layers.push(doc.yaml);
+                }
+                _ => layers.push(Yaml::Hash(Hash::new())),
+            }
+        }
+
+        // The input files are parsed in parallel and collected in input order
+        let loaded: Vec<_> = args
+            .input_files
+            .par_iter()
+            .map(|filename| read_document(filename, kubernetes).map_err(|e| e.to_string()))
+            .collect();
+        let mut inputs = Inputs {
+            read_base,
+            chart_schema,
+            base,
+            layers,
+            target_source,
+            names: Vec::new(),
+            docs: Vec::new(),
+            sources: Vec::new(),
+            diff_files: Vec::new(),
+        };
+        for ((filename, doc), diff_file) in args.input_files.iter().zip(loaded).zip(diff_paths) {
+            info!("Reading input file: {}", filename);
+            let doc = doc?;
+            if doc.as_ref().is_some_and(|doc| is_encrypted(&doc.yaml, kubernetes)) {
+                // Extracting from the ciphertext would mangle it; the file is left as it is
+                warn!("Skipping {}: it is encrypted with sops; decrypt it to extract its values", filename);
+            } else if let Some(doc) = doc {
+                inputs.names.push(filename.as_str());
+                inputs.docs.push(doc.yaml);
+                inputs.diff_files.push(diff_file);
+                let mut source = doc.source;
+                source.extend(&base_source);
+                inputs.sources.push(source);
+            } else {
+                warn!("No YAML documents in {}", filename);
+            }
+        }
+        Ok(inputs)
+    }
+
+    /// The read-base, base, layers and input files, named by their paths.
+    fn named_docs<'b>(&'b self, args: &'b Args) -> Vec<(&'b str, &'b Yaml)> {
+        args.read_only_base
+            .as_deref()
+            .zip(self.read_base.as_ref())
+            .into_iter()
+            .chain(args.base.as_deref().zip(self.base.as_ref()))
+            .chain(args.layers.iter().map(String::as_str).zip(&self.layers))
+            .chain(self.names.iter().copied().zip(&self.docs))
+            .collect()
+    }
+
+    /// The layers each diff is merged over, lowest first: the read-base and the new `base`, or the
+    /// layer stack with the new target layer.
+    fn lower_layers<'b>(
+        &'b self,
+        args: &'b Args,
+        base: Option<&'b Yaml>,
+        base_out_path: &'b str,
+        empty_layer: &'b Yaml,
+    ) -> Vec<(&'b str, &'b Yaml)> {
+        let mut lower_layers: Vec<(&str, &Yaml)> =
+            args.read_only_base.as_deref().zip(self.read_base.as_ref()).into_iter().collect();
+        if args.layers.is_empty() {
+            lower_layers.extend(Some(base_out_path).zip(base));
         } else {
-            warn!("No YAML documents in {}", filename);
+            let new_layer = base.unwrap_or(empty_layer);
+            for (layer, doc) in args.layers.iter().zip(&self.layers) {
+                lower_layers.push((layer, if args.extract_into.as_ref() == Some(layer) { new_layer } else { doc }));
+            }
+            if args.extract_into.is_none() {
+                lower_layers.push((base_out_path, new_layer));
+            }
+        }
+        lower_layers
+    }
+}
+
+/// Reads the read-base: a values file, possibly at a git revision, or the values of a chart
+/// directory or archive, with the chart's schema unless `--schema` is given.
+fn read_read_base(path: &str, args: &Args, kubernetes: bool) -> Result<(Option<Yaml>, Option<Yaml>), Box<dyn Error>> {
+    let revision = revision_path(path);
+    if revision.is_some_and(|(_, path)| is_chart(Path::new(path))) {
+        return Err(format!("{}: a chart read-base is read from the filesystem, not from git", path).into());
+    }
+    if revision.is_none() && is_chart(Path::new(path)) {
+        if kubernetes {
+            return Err("--read-base cannot be a chart with --target kubernetes".into());
         }
+        info!("Reading chart values: {}", path);
+        let schema = match args.schema {
+            Some(_) => None,
+            None => load_chart_schema(Path::new(path))?,
+        };
+        return Ok((Some(load_chart_values(Path::new(path), args.subcharts)?), schema));
+    }
+    if args.subcharts {
+        warn!("--subcharts only applies to a chart directory or archive; ignoring it.");
     }
+    info!("Reading helm values file: {}", path);
+    Ok((read_lower_layer(path, kubernetes)?.map(|doc| doc.yaml), None))
+}
+
+/// The common values, and the diff of each input file.
+type Extraction = (Option<Yaml>, Vec<Option<Yaml>>);
+
+/// Extracts the common values of the input files into a new base file, which goes between the
+/// read-base (and `--base`) and the input files.
+fn extract_base(inputs: &Inputs, quorum: f64, options: &DiffOptions) -> Extraction {
+    // A null deletes what the layers below it set, so with Helm semantics the chart values
+    // are merged in first and the diffs are computed from the effective values
+    let helm = options.merge.null_mode() == NullMode::Delete;
+    let lower_layers: Option<Cow<Yaml>> = match (&inputs.read_base, &inputs.base) {
+        (Some(read_base), Some(base)) if helm => Some(merge_yaml_with(read_base, base, &options.merge)),
+        (Some(read_base), None) if helm => Some(Cow::Borrowed(read_base)),
+        (_, Some(base)) => Some(Cow::Borrowed(base)),
+        (_, None) => None,
+    };
 
     // Merge existing base with each input file if existing base is provided
-    let merged_objs: Vec<Cow<Yaml>> = if let Some(ref base) = existing_base {
-        input_filenames
-            .iter()
-            .zip(all_docs.iter())
-            .map(|(filename, obj)| {
-                let merged = merge_yaml(base, obj);
+    let merged_objs: Vec<Cow<Yaml>> = match lower_layers {
+        Some(ref base) => {
+            let merged = inputs.docs.par_iter().map(|obj| merge_yaml_with(base, obj, &options.merge)).collect();
+            for filename in &inputs.names {
                 info!("Merged base with input file: {}", filename);
-                merged
-            })
-            .collect()
-    } else {
-        // No existing base; use objs as merged_objs
-        all_docs.iter().map(|doc| Cow::Borrowed(doc)).collect()
+            }
+            merged
+        }
+        None => inputs.docs.iter().map(Cow::Borrowed).collect(),
     };
 
     // Compute diffs between each merged object and read-only base
-    let diffs: Vec<_> = if let Some(ref helm) = read_only_base {
-        info!("Computing diffs between merged files and helm values.");
-        merged_objs
-            .iter()
-            .map(|obj| compute_diff(obj.as_ref(), helm).unwrap_or_else(|| Cow::Owned(Yaml::Null)))
-            .collect()
-    } else {
-        // No read-only base provided values; use merged_objs as diffs
-        merged_objs.clone()
+    let diffs: Vec<_> = match inputs.read_base {
+        Some(ref read_base) => {
+            info!("Computing diffs between merged files and helm values.");
+            let merged_refs: Vec<&Yaml> = merged_objs.iter().map(|obj| obj.as_ref()).collect();
+            compute_diffs_with(&merged_refs, read_base, options)
+                .into_iter()
+                .map(|diff| diff.unwrap_or_else(|| Cow::Owned(Yaml::Null)))
+                .collect()
+        }
+        None => merged_objs,
     };
 
     // Now compute common base and per-file diffs among the diffs
     let diffs_refs: Vec<&Yaml> = diffs.iter().map(|cow| cow.as_ref()).collect();
+    let (base, per_file_diffs) = diff_and_common_multiple_with(&diffs_refs, quorum, options);
+    (
+        base.map(Cow::into_owned),
+        per_file_diffs.into_iter().map(|diff| diff.map(Cow::into_owned)).collect(),
+    )
+}
+
+/// Extracts the common values of the input files into the `--extract-into` layer of the stack
+/// of the read-base and the layers, or into a new layer right below the input files.
+fn extract_into_stack(
+    args: &Args,
+    inputs: &Inputs,
+    quorum: f64,
+    options: &DiffOptions,
+) -> Result<Extraction, Box<dyn Error>> {
+    let empty_layer = Yaml::Hash(Hash::new());
+    let mut stack: Vec<&Yaml> = inputs.read_base.iter().chain(&inputs.layers).collect();
+    let target = match args.extract_into {
+        Some(ref layer) => {
+            let index = args
+                .layers
+                .iter()
+                .position(|l| l == layer)
+                .ok_or_else(|| format!("--extract-into {} is not one of the --layer files", layer))?;
+            stack.len() - inputs.layers.len() + index
+        }
+        None => {
+            stack.push(&empty_layer);
+            stack.len() - 1
+        }
+    };
     info!(
-        "Computing common base and per-file diffs among the diffs with quorum {}%.",
+        "Extracting common values into layer {} of {} with quorum {}%.",
+        target + 1,
+        stack.len(),
         args.quorum
     );
-    let (base, per_file_diffs) = diff_and_common_multiple(&diffs_refs, quorum_percentage);
+    let doc_refs: Vec<&Yaml> = inputs.docs.iter().collect();
+    Ok(extract_into_layer(&stack, target, &doc_refs, quorum, options))
+}
 
-    // Process the base YAML if it exists
-    if let Some(base_yaml) = base {
-        let processed_yaml = if config != Yaml::Null {
-            sort_yaml(base_yaml.as_ref(), &config)
-        } else {
-            Cow::Borrowed(base_yaml.as_ref())
-        };
+/// How output documents are sorted and serialized.
+struct Renderer<'a> {
+    config: &'a Yaml,
+    emitter: &'a EmitterConfig,
+    /// Whether documents are manifest bundles, written as streams of their resources.
+    kubernetes: bool,
+    /// The read-base, whose key order comes first with `--read-base-order`.
+    sort_reference: Option<&'a Yaml>,
+}
+
+impl Renderer<'_> {
+    /// Sorts and serializes an output document, keeping the presentation its scalars had in `source`.
+    fn render(&self, doc: &Yaml, source: &SourceMap) -> Result<String, Box<dyn Error>> {
+        if !self.kubernetes {
+            let sorted = sort_output(doc, self.config, self.sort_reference);
+            return Ok(emit_yaml_preserving(&sorted, self.emitter, source)?);
+        }
+        let mut docs = Vec::new();
+        let mut sources = Vec::new();
+        for (id, resource, resource_source) in unbundle(doc, source) {
+            let reference = self.sort_reference.and_then(|r| r.as_hash()).and_then(|r| r.get(id));
+            docs.push(sort_output(resource, self.config, reference).into_owned());
+            sources.push(resource_source);
+        }
+        Ok(emit_documents_preserving(&docs, &sources, self.emitter)?)
+    }
+}
 
+/// Writes a kustomize base/ with the resources the overlays patch, the read-base with the common
+/// values, and an overlays/<name>/ directory per input file into `dir`.
+fn write_kustomize_output(
+    dir: &str,
+    format: PatchFormat,
+    inputs: &Inputs,
+    diffs: &[Option<Yaml>],
+    patch_base: Option<&Yaml>,
+    options: &MergeOptions,
+    renderer: &Renderer,
+) -> Result<(), Box<dyn Error>> {
+    let mut files = base_files(patch_base);
+    files.extend(overlays(&inputs.names, diffs, patch_base, format, options)?);
+    write_kustomize(dir, &files, renderer.config, renderer.emitter)
+}
+
+/// Writes the base file, or clears the `--extract-into` layer when there are no common values.
+fn write_base(
+    args: &Args,
+    inputs: &Inputs,
+    base: Option<&Yaml>,
+    base_out_path: &str,
+    renderer: &Renderer,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(base_yaml) = base {
+        let mut base_source = inputs.target_source.clone();
+        for source in &inputs.sources {
+            base_source.extend(source);
+        }
         info!("Writing base YAML to {}", base_out_path);
-        let mut out_str = String::new();
-        {
-            let mut emitter = YamlEmitter::new(&mut out_str);
-            emitter.dump(&processed_yaml)?;
-        }
-        out_str = out_str.trim_start_matches("---\n").to_string();
-        out_str.push('\n');
-        fs::write(base_out_path.as_str(), out_str)?;
+        write_output(base_out_path, renderer.render(base_yaml, &base_source)?)?;
         info!("Base YAML written to {}", base_out_path);
+    } else if args.extract_into.is_some() {
+        info!("No common values; clearing layer {}.", base_out_path);
+        fs::write(base_out_path, "")?;
     } else {
         info!("No base YAML to write.");
     }
+    Ok(())
+}
+
+/// Writes the diffs in the `--diff-format`: back into the input files with `--in-place`, clearing
+/// those without a diff, or to the paths of the `--diff-out-path` template. Returns whether each
+/// input file has a diff file.
+fn write_diffs(
+    args: &Args,
+    inputs: &Inputs,
+    diffs: &[Option<Yaml>],
+    patch_base: Option<&Yaml>,
+    options: &MergeOptions,
+    renderer: &Renderer,
+) -> Result<Vec<bool>, Box<dyn Error>> {
+    // Sort and serialize the diffs in parallel; they are written in input order below
+    let rendered: Vec<Option<String>> = diffs
+        .par_iter()
+        .zip(&inputs.sources)
+        .map(|(diff, source)| {
+            let Some(diff_yaml) = diff else {
+                return Ok(None);
+            };
+            let content = match args.diff_format.as_str() {
+                "yaml" => renderer.render(diff_yaml, source).map_err(|e| e.to_string())?,
+                format => render_patch(format, diff_yaml, patch_base, options),
+            };
+            Ok::<_, String>(Some(content))
+        })
+        .collect::<Result<_, _>>()?;
 
-    // Determine whether to write diffs to original files or new files
+    let has_diffs = rendered.iter().map(|content| args.inplace || content.is_some()).collect();
     if args.inplace {
         info!("Inplace mode enabled. Modifying original files.");
-        for (i, diff) in per_file_diffs.iter().enumerate() {
-            if let Some(diff_yaml) = diff {
-                let processed_diff = if config != Yaml::Null {
-                    sort_yaml(diff_yaml.as_ref(), &config)
-                } else {
-                    Cow::Borrowed(diff_yaml.as_ref())
-                };
-
-                info!("Writing diff back to original file: {}", input_filenames[i]);
-                let mut out_str = String::new();
-                {
-                    let mut emitter = YamlEmitter::new(&mut out_str);
-                    emitter.dump(&processed_diff)?;
-                }
-                out_str = out_str.trim_start_matches("---\n").to_string();
-                out_str.push('\n');
-                fs::write(&input_filenames[i], out_str)?;
-                info!(
-                    "Difference written back to original file {}",
-                    input_filenames[i]
-                );
+        for (filename, content) in inputs.names.iter().zip(rendered) {
+            if let Some(content) = content {
+                info!("Writing diff back to original file: {}", filename);
+                fs::write(filename, content)?;
+                info!("Difference written back to original file {}", filename);
             } else {
                 // If there is no diff, remove the content of the file
-                info!("No diff for {}; clearing file content.", input_filenames[i]);
-                fs::write(&input_filenames[i], "")?;
-                info!(
-                    "No difference for {}; file content cleared.",
-                    input_filenames[i]
-                );
+                info!("No diff for {}; clearing file content.", filename);
+                fs::write(filename, "")?;
+                info!("No difference for {}; file content cleared.", filename);
             }
         }
     } else {
         info!("Writing diffs to new files.");
-        for (i, diff) in per_file_diffs.iter().enumerate() {
-            if let Some(diff_yaml) = diff {
-                let processed_diff = if config != Yaml::Null {
-                    sort_yaml(diff_yaml.as_ref(), &config)
-                } else {
-                    Cow::Borrowed(diff_yaml.as_ref())
-                };
-
-                info!("Writing diff for {} to new file.", input_filenames[i]);
-                let mut out_str = String::new();
-                {
-                    let mut emitter = YamlEmitter::new(&mut out_str);
-                    emitter.dump(&processed_diff)?;
-                }
-
-                let input_path = Path::new(&input_filenames[i]);
-                let file_stem = input_path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("diff");
-                let diff_filename = format!("{}/{}_diff.yaml", out_folder, file_stem);
-                out_str = out_str.trim_start_matches("---\n").to_string();
-                out_str.push('\n');
-                fs::write(&diff_filename, out_str)?;
-                info!(
-                    "Difference for {} written to {}",
-                    input_filenames[i], diff_filename
-                );
+        for ((filename, content), diff_filename) in inputs.names.iter().zip(rendered).zip(&inputs.diff_files) {
+            if let Some(content) = content {
+                info!("Writing diff for {} to new file.", filename);
+                write_output(diff_filename, content)?;
+                info!("Difference for {} written to {}", filename, diff_filename);
             } else {
-                info!("No diff for {}; not writing a diff file.", input_filenames[i]);
+                info!("No diff for {}; not writing a diff file.", filename);
             }
         }
     }
+    Ok(has_diffs)
+}
 
-    info!("Program completed successfully.");
+/// Writes the files of a kustomize tree into `dir`, sorting resources and patches.
+fn write_kustomize(dir: &str, files: &[KustomizeFile], config: &Yaml, emitter_config: &EmitterConfig) -> Result<(), Box<dyn Error>> {
+    let rendered: Vec<String> = files
+        .par_iter()
+        .map(|file| {
+            let doc = if file.is_kustomization {
+                Cow::Borrowed(&file.doc)
+            } else {
+                sort_output(&file.doc, config, None)
+            };
+            emit_yaml_with(&doc, emitter_config).map_err(|e| e.to_string())
+        })
+        .collect::<Result<_, _>>()?;
+    for (file, content) in files.iter().zip(rendered) {
+        let path = Path::new(dir).join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+        info!("Wrote {}", path.display());
+    }
     Ok(())
+}
+
+/// Generates the ArgoCD manifests, updating those `path` already has, and writes them to `path`.
+fn write_argocd(
+    path: &str,
+    kind: ManifestKind,
+    config: &ArgoConfig,
+    shared: &[String],
+    environments: &[Environment],
+    emitter_config: &EmitterConfig,
+) -> Result<(), Box<dyn Error>> {
+    let generated = match kind {
+        ManifestKind::Application => applications(config, shared, environments),
+        ManifestKind::ApplicationSet => vec![application_set(config, shared, environments)],
+    };
+    let existing = match fs::read_to_string(path) {
+        Ok(content) => YamlLoader::load_from_str(&content)?
+            .into_iter()
+            .filter(|doc| !doc.is_null())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    let manifests = update_manifests(&existing, generated);
+    info!("Writing {} ArgoCD manifest(s) to {}", manifests.len(), path);
+    write_output(path, emit_documents(&manifests, emitter_config)?)?;
+    Ok(())
+}
+
+/// Writes an output file, creating the directories it goes into.
+fn write_output(path: &str, content: String) -> std::io::Result<()> {
+    if let Some(parent) = Path::new(path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)
+}
+
+/// Reads the values document of a file, which may be a `REV:path` in git, or with `--target kubernetes` all of its manifests
+/// bundled by resource.
+fn read_document(path: &str, kubernetes: bool) -> Result<Option<LoadedDocument>, Box<dyn Error>> {
+    let content = read_input(path)?;
+    if kubernetes {
+        Ok(Some(bundle(load_documents(&content)?)))
+    } else {
+        Ok(load_document(&content)?)
+    }
+}
+
+/// Reads the read-base, base or a layer, which cannot be used encrypted.
+fn read_lower_layer(path: &str, kubernetes: bool) -> Result<Option<LoadedDocument>, Box<dyn Error>> {
+    let doc = read_document(path, kubernetes)?;
+    if doc.as_ref().is_some_and(|doc| is_encrypted(&doc.yaml, kubernetes)) {
+        return Err(format!("{} is encrypted with sops; decrypt it first", path).into());
+    }
+    Ok(doc)
+}
+
+/// Checks whether a values document, or any resource of a manifest bundle, is encrypted with sops.
+fn is_encrypted(doc: &Yaml, kubernetes: bool) -> bool {
+    if kubernetes {
+        doc.as_hash().is_some_and(|bundle| bundle.values().any(is_sops_encrypted))
+    } else {
+        is_sops_encrypted(doc)
+    }
+}
+
+/// The value files every ArgoCD application reads below its diff, lowest first; the chart source
+/// applies the values of a chart read-base.
+fn shared_value_files(args: &Args, base_out_path: Option<&str>) -> Vec<String> {
+    let chart_values = args.read_only_base.iter().filter(|path| !is_chart(Path::new(path)));
+    let mut shared: Vec<String> = chart_values.chain(&args.layers).cloned().collect();
+    if args.extract_into.is_none() {
+        shared.extend(base_out_path.map(str::to_string));
+    }
+    shared
+}
+
+/// An ArgoCD environment per input file, named by its file stem.
+fn environments(inputs: &Inputs, has_diffs: Vec<bool>) -> Result<Vec<Environment>, TemplateError> {
+    Ok(input_names(&inputs.names)?
+        .into_iter()
+        .zip(&inputs.diff_files)
+        .zip(has_diffs)
+        .map(|((name, diff_file), has_diff)| Environment {
+            name,
+            diff_file: diff_file.clone(),
+            has_diff,
+        })
+        .collect())
+}
+
+/// Writes a diff as the JSON patch that turns `patch_base` into the values the diff produces.
+fn render_patch(format: &str, diff: &Yaml, patch_base: Option<&Yaml>, options: &MergeOptions) -> String {
+    let empty = Yaml::Hash(Hash::new());
+    let patch_base = patch_base.unwrap_or(&empty);
+    let values = merge_yaml_with(patch_base, diff, options);
+    if format == "json-patch" {
+        to_json(&operations_to_yaml(&json_patch(&values, patch_base)))
+    } else {
+        to_json(&merge_patch(&values, patch_base).unwrap_or(empty))
+    }
+}
+
+/// Sorts an output document by the sort configuration and, if given, the key order of `reference`.
+fn sort_output<'a>(doc: &'a Yaml, config: &Yaml, reference: Option<&Yaml>) -> Cow<'a, Yaml> {
+    match reference {
+        Some(reference) => sort_yaml_with_reference(doc, config, reference),
+        None if *config != Yaml::Null => sort_yaml(doc, config),
+        None => Cow::Borrowed(doc),
+    }
 }
\ No newline at end of file
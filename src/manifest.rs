@@ -0,0 +1,128 @@
+use std::fmt;
+
+use log::warn;
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::loader::{LoadedDocument, SourceMap};
+use crate::path::PathSegment;
+use crate::strategic::PATCH;
+
+/// The identity of a Kubernetes resource: API group, kind, namespace and name.
+/// The API version is not part of it, so a resource keeps its identity across versions.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceId {
+    pub group: String,
+    pub kind: String,
+    pub namespace: Option<String>,
+    pub name: String,
+}
+
+impl ResourceId {
+    /// Reads the identity of a manifest, if it has a kind and a name.
+    pub fn of(doc: &Yaml) -> Option<Self> {
+        let api_version = doc["apiVersion"].as_str().unwrap_or("");
+        Some(ResourceId {
+            group: api_version.rsplit_once('/').map_or("", |(group, _)| group).to_string(),
+            kind: doc["kind"].as_str()?.to_string(),
+            namespace: doc["metadata"]["namespace"].as_str().map(String::from),
+            name: doc["metadata"]["name"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// Renders the identity as `[group/]Kind/[namespace/]name`, e.g. `apps/Deployment/prod/web`.
+impl fmt::Display for ResourceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.group.is_empty() {
+            write!(f, "{}/", self.group)?;
+        }
+        write!(f, "{}/", self.kind)?;
+        if let Some(ref namespace) = self.namespace {
+            write!(f, "{}/", namespace)?;
+        }
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Bundles the documents of a manifest stream into one hash keyed by resource identity,
+/// so that streams can be merged and diffed resource by resource.
+/// Documents without an identity are keyed by their position in the stream.
+pub fn bundle(docs: Vec<LoadedDocument>) -> LoadedDocument {
+    let mut bundle = Hash::new();
+    let mut source = SourceMap::default();
+    for (i, doc) in docs.into_iter().enumerate() {
+        if matches!(doc.yaml, Yaml::Null | Yaml::BadValue) {
+            continue;
+        }
+        let id = match ResourceId::of(&doc.yaml) {
+            Some(id) => id.to_string(),
+            None => {
+                warn!("Document {} has no kind and name; identifying it by its position.", i + 1);
+                format!("#{}", i + 1)
+            }
+        };
+        let key = Yaml::String(id.clone());
+        if bundle.contains_key(&key) {
+            warn!("Ignoring duplicate resource {}.", id);
+            continue;
+        }
+        source.extend(&doc.source.nested(&PathSegment::Key(id)));
+        bundle.insert(key, doc.yaml);
+    }
+    LoadedDocument {
+        yaml: Yaml::Hash(bundle),
+        source,
+    }
+}
+
+/// Splits a bundle back into its documents, each with the scalar presentation recorded for it.
+pub fn unbundle<'b>(bundle: &'b Yaml, source: &SourceMap) -> Vec<(&'b Yaml, &'b Yaml, SourceMap)> {
+    bundle
+        .as_hash()
+        .into_iter()
+        .flatten()
+        .map(|(id, doc)| {
+            let id_segment = PathSegment::Key(id.as_str().unwrap_or_default().to_string());
+            (id, doc, source.subtree(&id_segment))
+        })
+        .collect()
+}
+
+/// Puts the identifying fields (`apiVersion`, `kind`, `metadata.name` and `metadata.namespace`)
+/// back into every resource of a diff bundle, taking them from the first of `references` that
+/// has the resource, so each patch still targets its resource. A resource removed in the diff
+/// (`null`) becomes a `$patch: delete` patch.
+pub fn restore_identity(diff: &mut Yaml, references: &[&Yaml]) {
+    let Yaml::Hash(diff) = diff else { return };
+    for (id, patch) in diff.iter_mut() {
+        let Some(reference) = references.iter().find_map(|r| r.as_hash().and_then(|h| h.get(id))) else {
+            continue;
+        };
+        let deleted = patch.is_null();
+        let mut with_id = Hash::new();
+        for field in ["apiVersion", "kind"] {
+            if !reference[field].is_badvalue() {
+                with_id.insert(Yaml::String(field.into()), reference[field].clone());
+            }
+        }
+        let mut metadata = Hash::new();
+        for field in ["name", "namespace"] {
+            if !reference["metadata"][field].is_badvalue() {
+                metadata.insert(Yaml::String(field.into()), reference["metadata"][field].clone());
+            }
+        }
+        if let Some(patch_metadata) = patch["metadata"].as_hash() {
+            metadata.extend(patch_metadata.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        with_id.insert(Yaml::String("metadata".into()), Yaml::Hash(metadata));
+        if deleted {
+            with_id.insert(Yaml::String(PATCH.into()), Yaml::String("delete".into()));
+        }
+        for (k, v) in patch.as_hash().into_iter().flatten() {
+            if !with_id.contains_key(k) {
+                with_id.insert(k.clone(), v.clone());
+            }
+        }
+        *patch = Yaml::Hash(with_id);
+    }
+}
\ No newline at end of file
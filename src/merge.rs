@@ -1,21 +1,288 @@
 use std::borrow::Cow;
-use yaml_rust2::Yaml;
+use std::fmt;
+
+use log::warn;
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::deep_equal::deep_equal;
+use crate::path::{key_to_string, parse_patterns, PathPattern, PathSegment, YamlPath};
+use crate::strategic::{
+    self, is_directive, patch_directive, strip_directives, DELETE_FROM_PRIMITIVE_LIST, RETAIN_KEYS,
+    SET_ELEMENT_ORDER,
+};
+
+/// How an override array is combined with the base array at the same location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The override array replaces the base array (the default).
+    Replace,
+    /// Override items are added after the base items.
+    Append,
+    /// Override items are added before the base items.
+    Prepend,
+    /// Items with the same value of this field are merged; the others are appended.
+    MergeByKey(String),
+    /// Override items not already in the base are appended.
+    Union,
+}
+
+impl MergeStrategy {
+    /// Reads the `strategy` (and `key`) of a merge rule.
+    pub fn from_rule(rule: &Yaml) -> Result<Self, String> {
+        match rule["strategy"].as_str() {
+            Some("replace") => Ok(MergeStrategy::Replace),
+            Some("append") => Ok(MergeStrategy::Append),
+            Some("prepend") => Ok(MergeStrategy::Prepend),
+            Some("union") => Ok(MergeStrategy::Union),
+            Some("mergeByKey") => match rule["key"].as_str() {
+                Some(key) => Ok(MergeStrategy::MergeByKey(key.to_string())),
+                None => Err("mergeByKey requires a key".to_string()),
+            },
+            Some(other) => Err(format!(
+                "unknown strategy '{}', expected replace, append, prepend, mergeByKey or union",
+                other
+            )),
+            None => Err("missing strategy".to_string()),
+        }
+    }
+}
+
+/// The value an array item is matched on by [`MergeStrategy::MergeByKey`].
+pub(crate) fn item_key<'y>(item: &'y Yaml, key: &str) -> Option<&'y Yaml> {
+    match item.as_hash()?.get(&Yaml::String(key.to_string()))? {
+        Yaml::Null | Yaml::BadValue => None,
+        value => Some(value),
+    }
+}
+
+impl fmt::Display for MergeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeStrategy::Replace => write!(f, "replace"),
+            MergeStrategy::Append => write!(f, "append"),
+            MergeStrategy::Prepend => write!(f, "prepend"),
+            MergeStrategy::MergeByKey(key) => write!(f, "mergeByKey({})", key),
+            MergeStrategy::Union => write!(f, "union"),
+        }
+    }
+}
+
+/// What a `null` in the override does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NullMode {
+    /// The null is stored like any other value.
+    #[default]
+    Keep,
+    /// The null deletes the key, like Helm does when it merges values. A null for a key
+    /// the base does not have is kept, so it can still delete the key from the layers below.
+    Delete,
+}
+
+/// Array merge strategies by path, read from the `merge` section of the configuration file,
+/// and the handling of nulls. The first matching rule wins; arrays matched by no rule are replaced.
+#[derive(Clone, Debug, Default)]
+pub struct MergeOptions {
+    rules: Vec<(PathPattern, MergeStrategy)>,
+    null_mode: NullMode,
+    strategic: bool,
+}
+
+impl MergeOptions {
+    pub fn new(rules: Vec<(PathPattern, MergeStrategy)>) -> Self {
+        MergeOptions {
+            rules,
+            null_mode: NullMode::Keep,
+            strategic: false,
+        }
+    }
+
+    /// Switches to Kubernetes strategic merge patch semantics: the `$patch`, `$retainKeys`,
+    /// `$setElementOrder` and `$deleteFromPrimitiveList` directives are applied, the API's
+    /// merge keys are used for lists no rule matches, and a null deletes the key.
+    pub fn with_strategic_merge(mut self) -> Self {
+        self.rules.extend(strategic::kubernetes_rules());
+        self.null_mode = NullMode::Delete;
+        self.strategic = true;
+        self
+    }
+
+    pub fn is_strategic(&self) -> bool {
+        self.strategic
+    }
+
+    pub fn with_null_mode(mut self, null_mode: NullMode) -> Self {
+        self.null_mode = null_mode;
+        self
+    }
+
+    pub fn null_mode(&self) -> NullMode {
+        self.null_mode
+    }
+
+    /// Reads a list of `{path, strategy, key}` rules, skipping (and reporting) invalid ones.
+    pub fn from_yaml(rules: &Yaml) -> Self {
+        let rules = parse_patterns(rules)
+            .into_iter()
+            .filter_map(|(pattern, rule)| match MergeStrategy::from_rule(rule) {
+                Ok(strategy) => Some((pattern, strategy)),
+                Err(e) => {
+                    warn!("Ignoring merge rule for '{}': {}", pattern, e);
+                    None
+                }
+            })
+            .collect();
+        MergeOptions::new(rules)
+    }
+
+    /// The strategy for the array at `path`.
+    pub fn strategy(&self, path: &YamlPath) -> &MergeStrategy {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map_or(&MergeStrategy::Replace, |(_, strategy)| strategy)
+    }
+}
 
 /// Merges two YAML documents.
 pub fn merge_yaml<'a>(base: &'a Yaml, override_yaml: &'a Yaml) -> Cow<'a, Yaml> {
+    merge_yaml_with(base, override_yaml, &MergeOptions::default())
+}
+
+/// Merges two YAML documents, combining arrays with the strategies of `options`.
+pub fn merge_yaml_with<'a>(base: &'a Yaml, override_yaml: &'a Yaml, options: &MergeOptions) -> Cow<'a, Yaml> {
+    merge_node(base, override_yaml, options, &mut YamlPath::root())
+}
+
+pub(crate) fn merge_node<'a>(
+    base: &'a Yaml,
+    override_yaml: &'a Yaml,
+    options: &MergeOptions,
+    path: &mut YamlPath,
+) -> Cow<'a, Yaml> {
     match (base, override_yaml) {
+        (Yaml::Hash(base_hash), Yaml::Hash(override_hash)) if options.strategic => {
+            Cow::Owned(merge_strategic_hash(base_hash, override_hash, options, path))
+        }
         (Yaml::Hash(base_hash), Yaml::Hash(override_hash)) => {
             let mut merged = base_hash.clone();
             for (key, override_value) in override_hash {
+                if options.null_mode == NullMode::Delete
+                    && override_value.is_null()
+                    && merged.get(key).is_some_and(|base_value| !base_value.is_null())
+                {
+                    merged.remove(key);
+                    continue;
+                }
+                path.push(PathSegment::Key(key_to_string(key)));
                 merged.entry(key.clone())
                     .and_modify(|base_value| {
-                        let merged_value = merge_yaml(base_value, override_value);
+                        let merged_value = merge_node(base_value, override_value, options, path);
                         *base_value = merged_value.into_owned();
                     })
                     .or_insert_with(|| override_value.clone());
+                path.pop();
             }
             Cow::Owned(Yaml::Hash(merged))
         }
+        (Yaml::Array(base_array), Yaml::Array(override_array)) => match options.strategy(path) {
+            MergeStrategy::Replace => Cow::Borrowed(override_yaml),
+            MergeStrategy::Append => Cow::Owned(Yaml::Array(
+                base_array.iter().chain(override_array).cloned().collect(),
+            )),
+            MergeStrategy::Prepend => Cow::Owned(Yaml::Array(
+                override_array.iter().chain(base_array).cloned().collect(),
+            )),
+            MergeStrategy::Union => {
+                let mut merged = base_array.clone();
+                for item in override_array {
+                    if !merged.iter().any(|existing| deep_equal(existing, item)) {
+                        merged.push(item.clone());
+                    }
+                }
+                Cow::Owned(Yaml::Array(merged))
+            }
+            MergeStrategy::MergeByKey(_)
+                if options.strategic
+                    && override_array.iter().any(|item| patch_directive(item) == Some("replace")) =>
+            {
+                Cow::Owned(strip_directives(override_yaml).unwrap_or(Yaml::Null))
+            }
+            MergeStrategy::MergeByKey(key) => {
+                let mut merged = base_array.clone();
+                for item in override_array {
+                    if options.strategic && patch_directive(item) == Some("delete") {
+                        if let Some(value) = item_key(item, key) {
+                            merged.retain(|existing| item_key(existing, key) != Some(value));
+                        }
+                        continue;
+                    }
+                    let matched = item_key(item, key).and_then(|value| {
+                        merged
+                            .iter()
+                            .position(|existing| item_key(existing, key) == Some(value))
+                    });
+                    match matched {
+                        Some(index) => {
+                            path.push(PathSegment::Index(index));
+                            let merged_item = merge_node(&merged[index], item, options, path).into_owned();
+                            path.pop();
+                            merged[index] = merged_item;
+                        }
+                        None if options.strategic => merged.extend(strip_directives(item)),
+                        None => merged.push(item.clone()),
+                    }
+                }
+                Cow::Owned(Yaml::Array(merged))
+            }
+        },
+        (_, override_val) if options.strategic => Cow::Owned(strip_directives(override_val).unwrap_or(Yaml::Null)),
         (_, override_val) => Cow::Borrowed(override_val),
     }
+}
+
+/// Merges a strategic merge patch map over a base map.
+fn merge_strategic_hash(base: &Hash, patch: &Hash, options: &MergeOptions, path: &mut YamlPath) -> Yaml {
+    let patch_yaml = Yaml::Hash(patch.clone());
+    if patch_directive(&patch_yaml) == Some("replace") {
+        return strip_directives(&patch_yaml).unwrap_or(Yaml::Null);
+    }
+
+    let mut merged = base.clone();
+    for (key, value) in patch {
+        if is_directive(key) {
+            continue;
+        }
+        let deletes = patch_directive(value) == Some("delete")
+            || (value.is_null() && merged.get(key).is_some_and(|base_value| !base_value.is_null()));
+        if deletes {
+            merged.remove(key);
+            continue;
+        }
+        path.push(PathSegment::Key(key_to_string(key)));
+        let merged_value = match merged.get(key) {
+            Some(base_value) => merge_node(base_value, value, options, path).into_owned(),
+            None => strip_directives(value).unwrap_or(Yaml::Null),
+        };
+        path.pop();
+        merged.insert(key.clone(), merged_value);
+    }
+
+    for (key, value) in patch {
+        let (Some(directive), Some(list)) = (key.as_str(), value.as_vec()) else {
+            continue;
+        };
+        if let Some(field) = directive.strip_prefix(DELETE_FROM_PRIMITIVE_LIST) {
+            if let Some(Yaml::Array(items)) = merged.get_mut(&Yaml::String(field.to_string())) {
+                items.retain(|item| !list.contains(item));
+            }
+        } else if let Some(field) = directive.strip_prefix(SET_ELEMENT_ORDER) {
+            let strategy = options.strategy(&path.child_key(&Yaml::String(field.to_string()))).clone();
+            if let Some(Yaml::Array(items)) = merged.get_mut(&Yaml::String(field.to_string())) {
+                strategic::set_element_order(items, list, &strategy);
+            }
+        } else if directive == RETAIN_KEYS {
+            merged.retain(|k, _| list.contains(k));
+        }
+    }
+    Yaml::Hash(merged)
 }
\ No newline at end of file
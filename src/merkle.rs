@@ -0,0 +1,214 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash as _, Hasher};
+use std::marker::PhantomData;
+
+use yaml_rust2::Yaml;
+
+use crate::deep_equal::{deep_equal_at, EqualityPolicy};
+use crate::path::{key_to_string, PathSegment, YamlPath};
+use crate::quantity::{is_quantity_path, Quantity};
+
+/// Structural (Merkle-style) hashes of every node of a set of documents, computed once per
+/// subtree from the hashes of its children. Nodes equal under the policy hash alike, so different
+/// hashes tell values apart in constant time; as different values can share a hash, a match is
+/// confirmed by a deep comparison. Under `coerceStrings`, strings that load as numbers or
+/// booleans are hashed as those values.
+///
+/// Nodes are identified by address, so the index borrows the documents it was built from.
+pub struct MerkleIndex<'a> {
+    hashes: HashMap<usize, u64>,
+    /// An index of other documents, shared rather than hashed again (see [`MerkleIndex::over`]).
+    parent: Option<&'a MerkleIndex<'a>>,
+    policy: EqualityPolicy,
+    /// Whether nodes are hashed at all; without hashes every comparison is a deep one.
+    hashing: bool,
+    documents: PhantomData<&'a Yaml>,
+}
+
+impl<'a> MerkleIndex<'a> {
+    /// Hashes every node of `docs`.
+    pub fn new(docs: &[&'a Yaml], policy: &EqualityPolicy) -> Self {
+        let mut index = MerkleIndex {
+            hashes: HashMap::new(),
+            parent: None,
+            policy: *policy,
+            hashing: true,
+            documents: PhantomData,
+        };
+        for doc in docs {
+            index.insert(doc, &mut YamlPath::root());
+        }
+        index
+    }
+
+    /// Hashes every node of `docs` on top of `parent`, whose documents are looked up there
+    /// rather than hashed again. This way a document compared against many others, like the
+    /// read-base, is hashed once.
+    pub fn over(parent: &'a MerkleIndex<'a>, docs: &[&'a Yaml]) -> Self {
+        let mut index = MerkleIndex {
+            hashes: HashMap::new(),
+            parent: Some(parent),
+            policy: parent.policy,
+            hashing: parent.hashing,
+            documents: PhantomData,
+        };
+        if index.hashing {
+            for doc in docs {
+                index.insert(doc, &mut YamlPath::root());
+            }
+        }
+        index
+    }
+
+    /// An index that hashes nothing: all nodes share a hash, so every comparison is a deep one.
+    pub fn unhashed(policy: &EqualityPolicy) -> Self {
+        MerkleIndex {
+            hashes: HashMap::new(),
+            parent: None,
+            policy: *policy,
+            hashing: false,
+            documents: PhantomData,
+        }
+    }
+
+    /// The hash of a node of the indexed documents. Other nodes, like the null standing in for a
+    /// missing key, are hashed on the spot.
+    pub fn hash(&self, node: &Yaml) -> u64 {
+        if !self.hashing {
+            return 0;
+        }
+        match self.indexed(node) {
+            Some(hash) => hash,
+            None => node_hash(node, &self.policy, &mut YamlPath::root(), &mut |_, _| {}),
+        }
+    }
+
+    fn indexed(&self, node: &Yaml) -> Option<u64> {
+        let address = node as *const Yaml as usize;
+        let mut index = Some(self);
+        while let Some(current) = index {
+            if let Some(hash) = current.hashes.get(&address) {
+                return Some(*hash);
+            }
+            index = current.parent;
+        }
+        None
+    }
+
+    /// Checks whether two nodes at `path` are equal under the policy: their hashes must match,
+    /// and then their values. For list items, the path of their list will do.
+    pub fn equal(&self, a: &Yaml, b: &Yaml, path: &YamlPath) -> bool {
+        std::ptr::eq(a, b) || (self.hash(a) == self.hash(b) && deep_equal_at(a, b, &self.policy, path))
+    }
+
+    fn insert(&mut self, doc: &'a Yaml, path: &mut YamlPath) {
+        let hashes = &mut self.hashes;
+        node_hash(doc, &self.policy, path, &mut |node, hash| {
+            hashes.insert(node as *const Yaml as usize, hash);
+        });
+    }
+}
+
+/// Hashes `node` bottom-up, reporting the hash of every node of the subtree to `record`.
+fn node_hash(node: &Yaml, policy: &EqualityPolicy, path: &mut YamlPath, record: &mut dyn FnMut(&Yaml, u64)) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match node {
+        Yaml::Hash(hash) => {
+            // Entries are combined in an order-independent way, as maps are compared by key
+            let mut entries: u64 = 0;
+            for (key, value) in hash {
+                path.push(PathSegment::Key(key_to_string(key)));
+                let value_hash = node_hash(value, policy, path, record);
+                path.pop();
+                if policy.is_absent(value) {
+                    continue;
+                }
+                let mut entry = DefaultHasher::new();
+                node_hash(key, &EqualityPolicy::default(), &mut YamlPath::root(), &mut |_, _| {}).hash(&mut entry);
+                value_hash.hash(&mut entry);
+                entries = entries.wrapping_add(entry.finish());
+            }
+            "map".hash(&mut hasher);
+            entries.hash(&mut hasher);
+        }
+        Yaml::Array(array) => {
+            "list".hash(&mut hasher);
+            array.len().hash(&mut hasher);
+            for (i, item) in array.iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                node_hash(item, policy, path, record).hash(&mut hasher);
+                path.pop();
+            }
+        }
+        scalar => {
+            let quantity = policy.quantities && is_quantity_path(path);
+            scalar_hash(scalar, policy, quantity, &mut hasher);
+        }
+    }
+    let hash = hasher.finish();
+    record(node, hash);
+    hash
+}
+
+/// Hashes a scalar in the normal form of the policy.
+fn scalar_hash(value: &Yaml, policy: &EqualityPolicy, quantity: bool, hasher: &mut DefaultHasher) {
+    if quantity {
+        if let Some(quantity) = Quantity::from_yaml(value) {
+            "quantity".hash(hasher);
+            quantity.hash(hasher);
+            return;
+        }
+    }
+    if let Some(loaded) = value.as_str().filter(|_| policy.coerce_strings).and_then(coerced) {
+        return scalar_hash(&loaded, policy, false, hasher);
+    }
+    match value {
+        Yaml::String(s) if policy.ignore_whitespace => {
+            "string".hash(hasher);
+            for word in s.split_whitespace() {
+                word.hash(hasher);
+            }
+        }
+        Yaml::String(s) => {
+            "string".hash(hasher);
+            s.hash(hasher);
+        }
+        Yaml::Integer(_) | Yaml::Real(_) if policy.numeric => {
+            let number = match value {
+                Yaml::Integer(i) => *i as f64,
+                other => other.as_f64().unwrap_or(f64::NAN),
+            };
+            "number".hash(hasher);
+            // Normalize -0.0 to 0.0 so equal numbers hash alike
+            (number + 0.0).to_bits().hash(hasher);
+        }
+        Yaml::Integer(i) => {
+            "integer".hash(hasher);
+            i.hash(hasher);
+        }
+        Yaml::Real(s) => {
+            "real".hash(hasher);
+            s.hash(hasher);
+        }
+        Yaml::Boolean(b) => {
+            "boolean".hash(hasher);
+            b.hash(hasher);
+        }
+        Yaml::Null => "null".hash(hasher),
+        Yaml::Alias(a) => {
+            "alias".hash(hasher);
+            a.hash(hasher);
+        }
+        Yaml::BadValue => "bad".hash(hasher),
+        Yaml::Hash(_) | Yaml::Array(_) => unreachable!("not a scalar"),
+    }
+}
+
+/// The number or boolean a string loads as, if any.
+fn coerced(s: &str) -> Option<Yaml> {
+    match Yaml::from_str(s.trim()) {
+        loaded @ (Yaml::Integer(_) | Yaml::Real(_) | Yaml::Boolean(_)) => Some(loaded),
+        _ => None,
+    }
+}
\ No newline at end of file
@@ -0,0 +1,144 @@
+use log::warn;
+use yaml_rust2::yaml::{Array, Hash, Yaml};
+
+use crate::deep_equal::deep_equal;
+use crate::path::key_to_string;
+
+/// An RFC 6902 JSON Patch operation; `path` is a JSON Pointer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatchOperation {
+    Add { path: String, value: Yaml },
+    Remove { path: String },
+    Replace { path: String, value: Yaml },
+}
+
+impl PatchOperation {
+    /// The operation as a `{op, path, value}` map.
+    pub fn to_yaml(&self) -> Yaml {
+        let (op, path, value) = match self {
+            PatchOperation::Add { path, value } => ("add", path, Some(value)),
+            PatchOperation::Remove { path } => ("remove", path, None),
+            PatchOperation::Replace { path, value } => ("replace", path, Some(value)),
+        };
+        let mut hash = Hash::new();
+        hash.insert(Yaml::String("op".into()), Yaml::String(op.into()));
+        hash.insert(Yaml::String("path".into()), Yaml::String(path.clone()));
+        if let Some(value) = value {
+            hash.insert(Yaml::String("value".into()), value.clone());
+        }
+        Yaml::Hash(hash)
+    }
+}
+
+/// A list of operations as a YAML array, ready to be written as JSON.
+pub fn operations_to_yaml(operations: &[PatchOperation]) -> Yaml {
+    Yaml::Array(operations.iter().map(PatchOperation::to_yaml).collect::<Array>())
+}
+
+/// Computes the RFC 7386 JSON Merge Patch that turns `base` into `obj`.
+/// Keys missing from `obj` are removed with `null` and arrays are replaced as a whole.
+/// Returns `None` if the documents are equal.
+pub fn merge_patch(obj: &Yaml, base: &Yaml) -> Option<Yaml> {
+    merge_patch_node(obj, base, "")
+}
+
+fn merge_patch_node(obj: &Yaml, base: &Yaml, pointer: &str) -> Option<Yaml> {
+    match (obj, base) {
+        (Yaml::Hash(obj_hash), Yaml::Hash(base_hash)) => {
+            let mut patch = Hash::new();
+            for (key, value) in obj_hash {
+                let child = child_pointer(pointer, &key_to_string(key));
+                let changed = match base_hash.get(key) {
+                    Some(base_value) => merge_patch_node(value, base_value, &child),
+                    None => Some(set_value(value, &child)),
+                };
+                if let Some(changed) = changed {
+                    patch.insert(key.clone(), changed);
+                }
+            }
+            for key in base_hash.keys() {
+                if !obj_hash.contains_key(key) {
+                    patch.insert(key.clone(), Yaml::Null);
+                }
+            }
+            (!patch.is_empty()).then_some(Yaml::Hash(patch))
+        }
+        _ if deep_equal(obj, base) => None,
+        _ => Some(set_value(obj, pointer)),
+    }
+}
+
+/// A value written by a merge patch, where a null would delete the key instead.
+fn set_value(value: &Yaml, pointer: &str) -> Yaml {
+    if contains_null(value) {
+        warn!(
+            "{}: null values cannot be set by a JSON Merge Patch; they will remove their keys.",
+            if pointer.is_empty() { "/" } else { pointer }
+        );
+    }
+    value.clone()
+}
+
+fn contains_null(value: &Yaml) -> bool {
+    match value {
+        Yaml::Null => true,
+        Yaml::Hash(hash) => hash.values().any(contains_null),
+        _ => false,
+    }
+}
+
+/// Computes the RFC 6902 JSON Patch operations that turn `base` into `obj`.
+/// Maps are compared key by key and arrays index by index; items are removed from the end
+/// and appended in order, so the operations apply one after the other.
+pub fn json_patch(obj: &Yaml, base: &Yaml) -> Vec<PatchOperation> {
+    let mut operations = Vec::new();
+    json_patch_node(obj, base, "", &mut operations);
+    operations
+}
+
+fn json_patch_node(obj: &Yaml, base: &Yaml, pointer: &str, operations: &mut Vec<PatchOperation>) {
+    match (obj, base) {
+        (Yaml::Hash(obj_hash), Yaml::Hash(base_hash)) => {
+            for key in base_hash.keys() {
+                if !obj_hash.contains_key(key) {
+                    operations.push(PatchOperation::Remove {
+                        path: child_pointer(pointer, &key_to_string(key)),
+                    });
+                }
+            }
+            for (key, value) in obj_hash {
+                let path = child_pointer(pointer, &key_to_string(key));
+                match base_hash.get(key) {
+                    Some(base_value) => json_patch_node(value, base_value, &path, operations),
+                    None => operations.push(PatchOperation::Add { path, value: value.clone() }),
+                }
+            }
+        }
+        (Yaml::Array(obj_array), Yaml::Array(base_array)) => {
+            for (i, (value, base_value)) in obj_array.iter().zip(base_array).enumerate() {
+                json_patch_node(value, base_value, &child_pointer(pointer, &i.to_string()), operations);
+            }
+            for i in (obj_array.len()..base_array.len()).rev() {
+                operations.push(PatchOperation::Remove {
+                    path: child_pointer(pointer, &i.to_string()),
+                });
+            }
+            for (i, value) in obj_array.iter().enumerate().skip(base_array.len()) {
+                operations.push(PatchOperation::Add {
+                    path: child_pointer(pointer, &i.to_string()),
+                    value: value.clone(),
+                });
+            }
+        }
+        _ if deep_equal(obj, base) => {}
+        _ => operations.push(PatchOperation::Replace {
+            path: pointer.to_string(),
+            value: obj.clone(),
+        }),
+    }
+}
+
+/// Appends a reference token to a JSON Pointer, escaping `~` and `/`.
+fn child_pointer(pointer: &str, token: &str) -> String {
+    format!("{}/{}", pointer, token.replace('~', "~0").replace('/', "~1"))
+}
\ No newline at end of file
@@ -0,0 +1,310 @@
+use std::fmt;
+use std::str::FromStr;
+
+use yaml_rust2::Yaml;
+
+/// A single step from a YAML node into one of its children.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// The location of a node inside a YAML document, e.g. `spec.containers[0].name`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct YamlPath(Vec<PathSegment>);
+
+impl YamlPath {
+    pub fn root() -> Self {
+        YamlPath(Vec::new())
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn push(&mut self, segment: PathSegment) {
+        self.0.push(segment);
+    }
+
+    pub fn pop(&mut self) -> Option<PathSegment> {
+        self.0.pop()
+    }
+
+    /// Returns a new path pointing at the value stored under `key`.
+    pub fn child_key(&self, key: &Yaml) -> YamlPath {
+        let mut path = self.clone();
+        path.push(PathSegment::Key(key_to_string(key)));
+        path
+    }
+
+    /// Returns a new path pointing at the array item at `index`.
+    pub fn child_index(&self, index: usize) -> YamlPath {
+        let mut path = self.clone();
+        path.push(PathSegment::Index(index));
+        path
+    }
+}
+
+impl fmt::Display for YamlPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) => write_key(f, key, i == 0)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renders a hash key the way it is addressed in a path.
+pub fn key_to_string(key: &Yaml) -> String {
+    match key {
+        Yaml::String(s) | Yaml::Real(s) => s.clone(),
+        Yaml::Integer(i) => i.to_string(),
+        Yaml::Boolean(b) => b.to_string(),
+        Yaml::Null => "~".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn write_key(f: &mut fmt::Formatter<'_>, key: &str, first: bool) -> fmt::Result {
+    let needs_quotes = key.is_empty()
+        || key == "*"
+        || key == "**"
+        || key.contains(['.', '[', ']', '"']);
+    if needs_quotes {
+        write!(f, "[\"{}\"]", key.replace('\\', "\\\\").replace('"', "\\\""))
+    } else if first {
+        write!(f, "{}", key)
+    } else {
+        write!(f, ".{}", key)
+    }
+}
+
+/// A single step of a [`PathPattern`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PatternSegment {
+    /// Matches a hash key with exactly this name.
+    Key(String),
+    /// Matches an array item at exactly this index.
+    Index(usize),
+    /// `*`: matches any single key or index.
+    Any,
+    /// `[*]`: matches any array index.
+    AnyIndex,
+    /// `**`: matches zero or more segments.
+    AnyDepth,
+}
+
+/// A glob-like pattern over [`YamlPath`]s, e.g. `spec.containers[*].resources` or `**.image`.
+///
+/// The empty pattern matches the document root only.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PathPattern(Vec<PatternSegment>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPatternError(String);
+
+impl fmt::Display for PathPatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid path pattern: {}", self.0)
+    }
+}
+
+impl std::error::Error for PathPatternError {}
+
+impl PathPattern {
+    pub fn new(segments: Vec<PatternSegment>) -> Self {
+        PathPattern(segments)
+    }
+
+    pub fn segments(&self) -> &[PatternSegment] {
+        &self.0
+    }
+
+    /// Checks whether the pattern matches the whole of `path`.
+    pub fn matches(&self, path: &YamlPath) -> bool {
+        matches_segments(&self.0, path.segments())
+    }
+
+    /// Checks whether the pattern matches `path` or any of its ancestors.
+    pub fn matches_prefix_of(&self, path: &YamlPath) -> bool {
+        (0..=path.segments().len()).any(|len| matches_segments(&self.0, &path.segments()[..len]))
+    }
+}
+
+fn matches_segments(pattern: &[PatternSegment], path: &[PathSegment]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((PatternSegment::AnyDepth, rest)) => {
+            (0..=path.len()).any(|skip| matches_segments(rest, &path[skip..]))
+        }
+        Some((head, rest)) => match path.split_first() {
+            None => false,
+            Some((segment, path_rest)) => {
+                let matched = match (head, segment) {
+                    (PatternSegment::Key(k), PathSegment::Key(s)) => k == s,
+                    (PatternSegment::Index(i), PathSegment::Index(j)) => i == j,
+                    (PatternSegment::Any, _) => true,
+                    (PatternSegment::AnyIndex, PathSegment::Index(_)) => true,
+                    _ => false,
+                };
+                matched && matches_segments(rest, path_rest)
+            }
+        },
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = PathPatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = s.chars().collect();
+        let mut segments = Vec::new();
+        let mut i = 0;
+        let mut expect_segment = true;
+
+        while i < chars.len() {
+            match chars[i] {
+                '.' => {
+                    if expect_segment {
+                        return Err(PathPatternError(format!("empty segment in '{}'", s)));
+                    }
+                    expect_segment = true;
+                    i += 1;
+                }
+                '[' => {
+                    let close = chars[i..]
+                        .iter()
+                        .position(|&c| c == ']')
+                        .map(|p| p + i)
+                        .ok_or_else(|| PathPatternError(format!("unclosed '[' in '{}'", s)))?;
+                    if chars.get(i + 1) == Some(&'"') {
+                        let (key, end) = parse_quoted(&chars, i + 1)
+                            .ok_or_else(|| PathPatternError(format!("unterminated quote in '{}'", s)))?;
+                        if chars.get(end) != Some(&']') {
+                            return Err(PathPatternError(format!("expected ']' in '{}'", s)));
+                        }
+                        segments.push(PatternSegment::Key(key));
+                        i = end + 1;
+                    } else {
+                        let inner: String = chars[i + 1..close].iter().collect();
+                        let segment = match inner.trim() {
+                            "*" => PatternSegment::AnyIndex,
+                            n => PatternSegment::Index(n.parse().map_err(|_| {
+                                PathPatternError(format!("invalid index '{}' in '{}'", n, s))
+                            })?),
+                        };
+                        segments.push(segment);
+                        i = close + 1;
+                    }
+                    expect_segment = false;
+                }
+                _ => {
+                    if !expect_segment {
+                        return Err(PathPatternError(format!("expected '.' or '[' in '{}'", s)));
+                    }
+                    let start = i;
+                    while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                        i += 1;
+                    }
+                    let name: String = chars[start..i].iter().collect();
+                    segments.push(match name.as_str() {
+                        "*" => PatternSegment::Any,
+                        "**" => PatternSegment::AnyDepth,
+                        _ => PatternSegment::Key(name),
+                    });
+                    expect_segment = false;
+                }
+            }
+        }
+
+        if expect_segment && !segments.is_empty() {
+            return Err(PathPatternError(format!("trailing '.' in '{}'", s)));
+        }
+        Ok(PathPattern(segments))
+    }
+}
+
+fn parse_quoted(chars: &[char], open: usize) -> Option<(String, usize)> {
+    let mut out = String::new();
+    let mut i = open + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                out.push(*chars.get(i + 1)?);
+                i += 2;
+            }
+            '"' => return Some((out, i + 1)),
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    None
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PatternSegment::Key(key) => write_key(f, key, i == 0)?,
+                PatternSegment::Index(index) => write!(f, "[{}]", index)?,
+                PatternSegment::AnyIndex => write!(f, "[*]")?,
+                PatternSegment::Any => write!(f, "{}*", if i == 0 { "" } else { "." })?,
+                PatternSegment::AnyDepth => write!(f, "{}**", if i == 0 { "" } else { "." })?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the `path` entries of a list of rule hashes, skipping (and reporting) invalid ones.
+pub fn parse_patterns(rules: &Yaml) -> Vec<(PathPattern, &Yaml)> {
+    rules
+        .as_vec()
+        .map(|rules| {
+            rules
+                .iter()
+                .filter_map(|rule| {
+                    let path = rule["path"].as_str()?;
+                    match path.parse::<PathPattern>() {
+                        Ok(pattern) => Some((pattern, rule)),
+                        Err(e) => {
+                            log::warn!("Ignoring rule: {}", e);
+                            None
+                        }
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parses a list of path patterns, like the `paths` of the `secrets` section, skipping (and
+/// reporting) invalid ones. `what` names the list in the reports.
+pub fn parse_pattern_list(patterns: &Yaml, what: &str) -> Vec<PathPattern> {
+    patterns
+        .as_vec()
+        .into_iter()
+        .flatten()
+        .filter_map(|pattern| match pattern.as_str().map(str::parse::<PathPattern>) {
+            Some(Ok(pattern)) => Some(pattern),
+            Some(Err(e)) => {
+                log::warn!("Ignoring {} path: {}", what, e);
+                None
+            }
+            None => {
+                log::warn!("Ignoring invalid {} path: {:?}", what, pattern);
+                None
+            }
+        })
+        .collect()
+}
\ No newline at end of file
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use yaml_rust2::yaml::{Array, Yaml};
+
+use crate::deep_equal::deep_equal;
+use crate::loader::{LoadedDocument, SourceMap};
+use crate::merge::{item_key, merge_yaml_with, MergeOptions, MergeStrategy};
+use crate::path::YamlPath;
+
+/// Where a value was set: the file of its layer and, where available, its line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Origin {
+    pub file: String,
+    pub line: Option<usize>,
+    /// The value the layer set.
+    pub value: Yaml,
+}
+
+/// Renders the origin as `file:line`, or `file` when the line is unknown.
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.file, line),
+            None => write!(f, "{}", self.file),
+        }
+    }
+}
+
+/// The origins of the leaves of a merged document, in document order. For every leaf the
+/// first origin is the layer that set the effective value, followed by the layers it
+/// overrode, the most recent first.
+#[derive(Clone, Debug, Default)]
+pub struct Provenance {
+    entries: Vec<(YamlPath, Vec<Origin>)>,
+}
+
+impl Provenance {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The origins of the leaf at `path`.
+    pub fn get(&self, path: &YamlPath) -> Option<&[Origin]> {
+        self.entries
+            .iter()
+            .find(|(entry_path, _)| entry_path == path)
+            .map(|(_, origins)| origins.as_slice())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&YamlPath, &[Origin])> {
+        self.entries.iter().map(|(path, origins)| (path, origins.as_slice()))
+    }
+}
+
+/// Splits the origins a leaf overrode into those that set the same `value`, so the override did
+/// not change it, and those that set another value, keeping their order.
+pub fn same_and_overridden<'o>(value: &Yaml, overridden: &'o [Origin]) -> (Vec<&'o Origin>, Vec<&'o Origin>) {
+    overridden.iter().partition(|origin| deep_equal(&origin.value, value))
+}
+
+/// Merges `overlay` from `file` over `base` like [`merge_yaml_with`], and traces which layer
+/// set each leaf of the result, given the provenance of `base`.
+pub fn merge_yaml_traced(
+    base: &Yaml,
+    provenance: &Provenance,
+    file: &str,
+    overlay: &LoadedDocument,
+    options: &MergeOptions,
+) -> (Yaml, Provenance) {
+    let merged = merge_yaml_with(base, &overlay.yaml, options).into_owned();
+    let provenance = trace(&merged, Some(base), provenance, file, overlay, options);
+    (merged, provenance)
+}
+
+/// Merges a stack of layers, lowest first, tracing the origin of every leaf.
+/// Returns `None` for an empty stack.
+pub fn merge_layers_traced(layers: &[(&str, &LoadedDocument)], options: &MergeOptions) -> Option<(Yaml, Provenance)> {
+    let ((file, first), rest) = layers.split_first()?;
+    let provenance = trace(&first.yaml, None, &Provenance::default(), file, first, options);
+    let mut merged = (first.yaml.clone(), provenance);
+    for (file, layer) in rest {
+        merged = merge_yaml_traced(&merged.0, &merged.1, file, layer, options);
+    }
+    Some(merged)
+}
+
+fn trace(
+    merged: &Yaml,
+    base: Option<&Yaml>,
+    provenance: &Provenance,
+    file: &str,
+    overlay: &LoadedDocument,
+    options: &MergeOptions,
+) -> Provenance {
+    let mut tracer = Tracer {
+        previous: provenance.entries.iter().map(|(path, origins)| (path, origins.as_slice())).collect(),
+        file,
+        source: &overlay.source,
+        options,
+        entries: Vec::new(),
+    };
+    let root = YamlPath::root();
+    tracer.walk(
+        merged,
+        &root,
+        base.map(|base| (base, root.clone())),
+        Some((&overlay.yaml, root.clone())),
+    );
+    Provenance { entries: tracer.entries }
+}
+
+/// Walks a merged document along with its counterparts in the base and in the layer merged
+/// over it, which can sit at other paths when list items moved.
+struct Tracer<'p> {
+    previous: HashMap<&'p YamlPath, &'p [Origin]>,
+    file: &'p str,
+    source: &'p SourceMap,
+    options: &'p MergeOptions,
+    entries: Vec<(YamlPath, Vec<Origin>)>,
+}
+
+impl Tracer<'_> {
+    fn walk(&mut self, value: &Yaml, path: &YamlPath, base: Option<(&Yaml, YamlPath)>, layer: Option<(&Yaml, YamlPath)>) {
+        match value {
+            Yaml::Hash(hash) if !hash.is_empty() => {
+                for (key, child) in hash {
+                    let (child_base, child_layer) = (child_of(&base, key), child_of(&layer, key));
+                    self.walk(child, &path.child_key(key), child_base, child_layer);
+                }
+            }
+            Yaml::Array(array) if !array.is_empty() => {
+                let strategy = self.options.strategy(path).clone();
+                for (i, item) in array.iter().enumerate() {
+                    let item_of = |node| item_of(node, item, i, &strategy);
+                    let (item_base, item_layer) = (item_of(&base), item_of(&layer));
+                    self.walk(item, &path.child_index(i), item_base, item_layer);
+                }
+            }
+            _ => {
+                let history = base.map_or_else(Vec::new, |(_, base_path)| self.history(&base_path));
+                let set = layer.as_ref().is_some_and(|(layer_value, _)| deep_equal(layer_value, value));
+                let origins = if set || history.is_empty() {
+                    let line = layer
+                        .as_ref()
+                        .and_then(|(_, layer_path)| self.source.lookup(layer_path, value))
+                        .map(|source| source.line);
+                    let origin = Origin {
+                        file: self.file.to_string(),
+                        line,
+                        value: value.clone(),
+                    };
+                    std::iter::once(origin).chain(history).collect()
+                } else {
+                    history
+                };
+                self.entries.push((path.clone(), origins));
+            }
+        }
+    }
+
+    /// The origins of the base value at `path`; a subtree replaced as a whole contributes
+    /// the current origin of each of its leaves.
+    fn history(&self, path: &YamlPath) -> Vec<Origin> {
+        if let Some(origins) = self.previous.get(path) {
+            return origins.to_vec();
+        }
+        let prefix = path.segments();
+        let mut leaves: Vec<_> = self
+            .previous
+            .iter()
+            .filter(|(leaf, _)| leaf.segments().starts_with(prefix))
+            .collect();
+        leaves.sort_by_key(|(leaf, _)| **leaf);
+        leaves.into_iter().filter_map(|(_, origins)| origins.first().cloned()).collect()
+    }
+}
+
+/// The value under `key` of a counterpart map, with its path.
+fn child_of<'y>(node: &Option<(&'y Yaml, YamlPath)>, key: &Yaml) -> Option<(&'y Yaml, YamlPath)> {
+    match node {
+        Some((Yaml::Hash(hash), path)) => hash.get(key).map(|value| (value, path.child_key(key))),
+        _ => None,
+    }
+}
+
+/// The item of a counterpart list that `item`, at `index` of the merged list, came from, with its path.
+fn item_of<'y>(
+    node: &Option<(&'y Yaml, YamlPath)>,
+    item: &Yaml,
+    index: usize,
+    strategy: &MergeStrategy,
+) -> Option<(&'y Yaml, YamlPath)> {
+    match node {
+        Some((Yaml::Array(array), path)) => {
+            counterpart(array, item, index, strategy).map(|i| (&array[i], path.child_index(i)))
+        }
+        _ => None,
+    }
+}
+
+/// The index of the item of `array` that `item`, at `index` of the merged list, came from.
+fn counterpart(array: &Array, item: &Yaml, index: usize, strategy: &MergeStrategy) -> Option<usize> {
+    if array.get(index).is_some_and(|candidate| deep_equal(candidate, item)) {
+        return Some(index);
+    }
+    match strategy {
+        MergeStrategy::Replace => (index < array.len()).then_some(index),
+        MergeStrategy::MergeByKey(key) => match item_key(item, key) {
+            Some(value) => array.iter().position(|candidate| item_key(candidate, key) == Some(value)),
+            None => array.iter().position(|candidate| deep_equal(candidate, item)),
+        },
+        _ => array.iter().position(|candidate| deep_equal(candidate, item)),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,187 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+use yaml_rust2::Yaml;
+
+use crate::path::{PathPattern, YamlPath};
+
+/// Values under these paths are Kubernetes resource quantities (`500m`, `1Gi`, ...).
+const QUANTITY_PATHS: &[&str] = &[
+    "**.resources",
+    "**.limits",
+    "**.requests",
+    "**.hard",
+    "**.capacity",
+    "**.allocatable",
+    "**.overhead",
+    "**.sizeLimit",
+    "**.storage",
+];
+
+/// Decimal suffixes by power of ten.
+const DECIMAL_SUFFIXES: &[(&str, i32)] = &[
+    ("n", -9),
+    ("u", -6),
+    ("m", -3),
+    ("", 0),
+    ("k", 3),
+    ("M", 6),
+    ("G", 9),
+    ("T", 12),
+    ("P", 15),
+    ("E", 18),
+];
+
+/// Binary suffixes by power of 1024.
+const BINARY_SUFFIXES: &[(&str, u32)] = &[("Ki", 1), ("Mi", 2), ("Gi", 3), ("Ti", 4), ("Pi", 5), ("Ei", 6)];
+
+/// A Kubernetes resource quantity, stored exactly in units of 10^-9.
+#[derive(Clone, Copy, Debug)]
+pub struct Quantity {
+    nanos: i128,
+    /// Whether it was written with a binary suffix, which its canonical form keeps.
+    binary: bool,
+}
+
+/// Quantities are equal when their values are, however they are written.
+impl PartialEq for Quantity {
+    fn eq(&self, other: &Self) -> bool {
+        self.nanos == other.nanos
+    }
+}
+
+impl Eq for Quantity {}
+
+impl Hash for Quantity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.nanos.hash(state);
+    }
+}
+
+impl Quantity {
+    /// Parses a quantity such as `1.5`, `500m`, `1Gi` or `1e3`. Returns `None` for anything
+    /// else, or for values finer than 10^-9.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let number_len = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+        let (number, suffix) = s.split_at(number_len);
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if (whole.is_empty() && fraction.is_empty()) || fraction.contains('.') {
+            return None;
+        }
+
+        let mut digits: i128 = 0;
+        for c in whole.chars().chain(fraction.chars()) {
+            digits = digits.checked_mul(10)?.checked_add(c.to_digit(10)? as i128)?;
+        }
+        let mut exponent = 9 - fraction.len() as i32;
+        let mut binary = false;
+        let mut multiplier: i128 = 1;
+        if let Some(e) = suffix.strip_prefix(['e', 'E']).filter(|e| !e.is_empty()) {
+            exponent += e.parse::<i32>().ok()?;
+        } else if let Some((_, power)) = BINARY_SUFFIXES.iter().find(|(name, _)| *name == suffix) {
+            binary = true;
+            multiplier = 1i128 << (10 * power);
+        } else {
+            exponent += DECIMAL_SUFFIXES.iter().find(|(name, _)| *name == suffix)?.1;
+        }
+
+        let mut nanos = digits.checked_mul(multiplier)?;
+        if exponent >= 0 {
+            nanos = nanos.checked_mul(10i128.checked_pow(exponent as u32)?)?;
+        } else {
+            let divisor = 10i128.checked_pow(exponent.unsigned_abs())?;
+            if nanos % divisor != 0 {
+                return None;
+            }
+            nanos /= divisor;
+        }
+        Some(Quantity {
+            nanos: if negative { -nanos } else { nanos },
+            binary,
+        })
+    }
+
+    /// Reads a quantity from a number or a string.
+    pub fn from_yaml(value: &Yaml) -> Option<Self> {
+        match value {
+            Yaml::Integer(i) => Quantity::parse(&i.to_string()),
+            Yaml::Real(s) | Yaml::String(s) => Quantity::parse(s),
+            _ => None,
+        }
+    }
+
+    /// The canonical form as a YAML value: a number when it has no suffix, a string otherwise.
+    pub fn to_yaml(&self) -> Yaml {
+        let canonical = self.to_string();
+        match canonical.parse::<i64>() {
+            Ok(i) => Yaml::Integer(i),
+            Err(_) => Yaml::String(canonical),
+        }
+    }
+}
+
+/// Renders the canonical form: the largest suffix that keeps the value whole, binary if the
+/// quantity was written with a binary suffix and it is a whole multiple of 1024, decimal otherwise.
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.nanos < 0 { "-" } else { "" };
+        let nanos = self.nanos.unsigned_abs();
+        if nanos == 0 {
+            return write!(f, "0");
+        }
+        let units = 1_000_000_000u128;
+        if self.binary && nanos.is_multiple_of(units) {
+            let value = nanos / units;
+            if let Some((suffix, power)) = BINARY_SUFFIXES
+                .iter()
+                .rev()
+                .find(|(_, power)| value.is_multiple_of(1u128 << (10 * power)))
+            {
+                return write!(f, "{}{}{}", sign, value >> (10 * power), suffix);
+            }
+        }
+        for (suffix, exponent) in DECIMAL_SUFFIXES.iter().rev() {
+            let divisor = 10u128.pow((exponent + 9) as u32);
+            if nanos.is_multiple_of(divisor) {
+                return write!(f, "{}{}{}", sign, nanos / divisor, suffix);
+            }
+        }
+        write!(f, "{}{}n", sign, nanos)
+    }
+}
+
+/// Checks whether the value at `path` is a resource quantity field.
+pub fn is_quantity_path(path: &YamlPath) -> bool {
+    static PATTERNS: OnceLock<Vec<PathPattern>> = OnceLock::new();
+    PATTERNS
+        .get_or_init(|| {
+            QUANTITY_PATHS
+                .iter()
+                .map(|pattern| pattern.parse().expect("built-in quantity pattern"))
+                .collect()
+        })
+        .iter()
+        .any(|pattern| pattern.matches_prefix_of(path))
+}
+
+/// Checks whether two values are the same quantity, e.g. `1000m` and `1`.
+pub fn quantity_equal(a: &Yaml, b: &Yaml) -> bool {
+    matches!((Quantity::from_yaml(a), Quantity::from_yaml(b)), (Some(a), Some(b)) if a == b)
+}
+
+/// The canonical form of a set of equal quantities, keeping a binary form if any of them has one.
+pub fn canonical_quantity<'y>(values: impl IntoIterator<Item = &'y Yaml>) -> Option<Yaml> {
+    let quantities: Vec<Quantity> = values.into_iter().map(Quantity::from_yaml).collect::<Option<_>>()?;
+    let first = quantities.first()?;
+    let quantity = Quantity {
+        nanos: first.nanos,
+        binary: quantities.iter().any(|q| q.binary),
+    };
+    Some(quantity.to_yaml())
+}
\ No newline at end of file
@@ -0,0 +1,44 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Splits an input of the form `REV:path` into the revision and the path in it, e.g.
+/// `main:values/prod.yaml` or `v1.2.0:./base.yaml`. Returns `None` for plain paths,
+/// including files whose name has a colon and Windows drive letters.
+pub fn revision_path(input: &str) -> Option<(&str, &str)> {
+    if Path::new(input).exists() {
+        return None;
+    }
+    let (revision, path) = input.split_once(':')?;
+    let drive_letter = revision.len() == 1 && path.starts_with(['\\', '/']);
+    if revision.is_empty() || path.is_empty() || drive_letter {
+        return None;
+    }
+    Some((revision, path))
+}
+
+/// Reads an input file from the filesystem, or a `REV:path` input from the object database of
+/// the git repository of the working directory.
+pub fn read_input(input: &str) -> io::Result<String> {
+    match revision_path(input) {
+        Some((revision, path)) => read_revision(Path::new("."), revision, path),
+        None => fs::read_to_string(input),
+    }
+}
+
+/// Reads the file at `path` as of `revision` from the git repository at `repository`. A path is
+/// relative to the root of the repository, or to `repository` when it starts with `./`.
+pub fn read_revision(repository: &Path, revision: &str, path: &str) -> io::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(["cat-file", "blob", &format!("{}:{}", revision, path)])
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("cannot run git to read {}:{}: {}", revision, path, e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("cannot read {}:{}: {}", revision, path, stderr.trim())));
+    }
+    String::from_utf8(output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
\ No newline at end of file
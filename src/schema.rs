@@ -0,0 +1,425 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use log::warn;
+use regex::Regex;
+use yaml_rust2::yaml::{Array, Hash, Yaml};
+
+use crate::deep_equal::deep_equal;
+use crate::json::to_json;
+use crate::path::{key_to_string, PathSegment, YamlPath};
+use crate::secrets::{SecretPolicy, REDACTED};
+
+/// A value that does not match a JSON Schema: where it is and what is wrong with it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaViolation {
+    pub path: YamlPath,
+    pub message: String,
+}
+
+/// Renders the violation as `path: message`.
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_root() {
+            write!(f, "document root: {}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Validates `values` against a JSON Schema, like the `values.schema.json` of a chart, read as YAML.
+///
+/// Supports the keywords Helm charts use: `type`, `enum`, `const`, the numeric, string, array
+/// and object constraints, `allOf`, `anyOf`, `oneOf`, `not`, `if`/`then`/`else` and local
+/// `$ref`s to `#/definitions` or `#/$defs`. Other keywords, like `format`, are not checked.
+pub fn validate(schema: &Yaml, values: &Yaml) -> Vec<SchemaViolation> {
+    validate_with(schema, values, &SecretPolicy::default())
+}
+
+/// Validates `values` like [`validate`], writing the values that `secrets` marks as secret as
+/// `<redacted>` in the messages.
+pub fn validate_with(schema: &Yaml, values: &Yaml, secrets: &SecretPolicy) -> Vec<SchemaViolation> {
+    let mut validator = Validator { root: schema, secrets, following: Vec::new(), violations: Vec::new() };
+    validator.check(schema, values, &mut YamlPath::root());
+    validator.violations
+}
+
+struct Validator<'a> {
+    root: &'a Yaml,
+    secrets: &'a SecretPolicy,
+    /// The references being followed and the paths they were followed at; following one again
+    /// at the same path would never end.
+    following: Vec<(&'a str, YamlPath)>,
+    violations: Vec<SchemaViolation>,
+}
+
+impl<'a> Validator<'a> {
+    fn report(&mut self, path: &YamlPath, message: String) {
+        self.violations.push(SchemaViolation { path: path.clone(), message });
+    }
+
+    /// The value at `path` as a message shows it: as JSON, unless it is secret.
+    fn shown(&self, value: &Yaml, path: &YamlPath) -> String {
+        if self.secrets.is_secret(path, &[value]) {
+            REDACTED.to_string()
+        } else {
+            compact_json(value)
+        }
+    }
+
+    /// Checks whether `value` matches `schema` without reporting anything.
+    fn matches(&self, schema: &'a Yaml, value: &Yaml, path: &mut YamlPath) -> bool {
+        let mut validator = Validator {
+            root: self.root,
+            secrets: self.secrets,
+            following: self.following.clone(),
+            violations: Vec::new(),
+        };
+        validator.check(schema, value, path);
+        validator.violations.is_empty()
+    }
+
+    fn check(&mut self, schema: &'a Yaml, value: &Yaml, path: &mut YamlPath) {
+        match schema {
+            Yaml::Boolean(false) => return self.report(path, "no value is allowed here".into()),
+            Yaml::Hash(_) => {}
+            _ => return,
+        }
+        if let Some(reference) = schema["$ref"].as_str() {
+            if self.following.iter().any(|(followed, at)| *followed == reference && at == path) {
+                return self.report(path, format!("the schema reference {} refers back to itself", reference));
+            }
+            match self.resolve(reference) {
+                Some(target) => {
+                    self.following.push((reference, path.clone()));
+                    self.check(target, value, path);
+                    self.following.pop();
+                }
+                None => warn!("Ignoring unresolvable schema reference {}", reference),
+            }
+        }
+        if !schema["type"].is_badvalue() {
+            let types: Vec<&str> = match &schema["type"] {
+                Yaml::Array(types) => types.iter().filter_map(Yaml::as_str).collect(),
+                other => other.as_str().into_iter().collect(),
+            };
+            if !types.iter().any(|t| has_type(value, t)) {
+                return self.report(path, format!("expected {}, found {}", types.join(" or "), type_name(value)));
+            }
+        }
+        if let Some(allowed) = schema["enum"].as_vec() {
+            if !allowed.iter().any(|a| deep_equal(a, value)) {
+                let allowed: Vec<String> = allowed.iter().map(compact_json).collect();
+                self.report(path, format!("{} is not one of {}", self.shown(value, path), allowed.join(", ")));
+            }
+        }
+        if !schema["const"].is_badvalue() && !deep_equal(&schema["const"], value) {
+            self.report(path, format!("{} is not {}", self.shown(value, path), compact_json(&schema["const"])));
+        }
+        match value {
+            Yaml::Integer(_) | Yaml::Real(_) => self.check_number(schema, value, path),
+            Yaml::String(s) => self.check_string(schema, value, s, path),
+            Yaml::Array(items) => self.check_array(schema, items, path),
+            Yaml::Hash(_) => self.check_object(schema, value, path),
+            _ => {}
+        }
+        self.check_combinations(schema, value, path);
+    }
+
+    fn check_number(&mut self, schema: &Yaml, value: &Yaml, path: &YamlPath) {
+        let (n, shown) = (number(value), self.shown(value, path));
+        let limit = |key: &str| number(&schema[key]);
+        if !limit("minimum").is_nan() {
+            // Draft 4 writes an exclusive minimum as a flag next to it
+            let exclusive = schema["exclusiveMinimum"].as_bool() == Some(true);
+            if n < limit("minimum") || (exclusive && n == limit("minimum")) {
+                self.report(path, format!("{} is less than the minimum of {}", shown, limit("minimum")));
+            }
+        }
+        if !limit("maximum").is_nan() {
+            let exclusive = schema["exclusiveMaximum"].as_bool() == Some(true);
+            if n > limit("maximum") || (exclusive && n == limit("maximum")) {
+                self.report(path, format!("{} is greater than the maximum of {}", shown, limit("maximum")));
+            }
+        }
+        if n <= limit("exclusiveMinimum") {
+            self.report(path, format!("{} is not greater than {}", shown, limit("exclusiveMinimum")));
+        }
+        if n >= limit("exclusiveMaximum") {
+            self.report(path, format!("{} is not less than {}", shown, limit("exclusiveMaximum")));
+        }
+        let multiple_of = limit("multipleOf");
+        if multiple_of > 0.0 && ((n / multiple_of) - (n / multiple_of).round()).abs() > 1e-9 {
+            self.report(path, format!("{} is not a multiple of {}", shown, multiple_of));
+        }
+    }
+
+    fn check_string(&mut self, schema: &Yaml, value: &Yaml, s: &str, path: &YamlPath) {
+        let length = s.chars().count() as i64;
+        if let Some(min) = schema["minLength"].as_i64().filter(|min| length < *min) {
+            self.report(path, format!("is shorter than {} characters", min));
+        }
+        if let Some(max) = schema["maxLength"].as_i64().filter(|max| length > *max) {
+            self.report(path, format!("is longer than {} characters", max));
+        }
+        if let Some(pattern) = schema["pattern"].as_str() {
+            match Regex::new(pattern) {
+                Ok(regex) if !regex.is_match(s) => {
+                    self.report(path, format!("{} does not match {}", self.shown(value, path), pattern))
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Ignoring invalid schema pattern {}: {}", pattern, e),
+            }
+        }
+    }
+
+    fn check_array(&mut self, schema: &'a Yaml, items: &[Yaml], path: &mut YamlPath) {
+        let len = items.len() as i64;
+        if let Some(min) = schema["minItems"].as_i64().filter(|min| len < *min) {
+            self.report(path, format!("has fewer than {} items", min));
+        }
+        if let Some(max) = schema["maxItems"].as_i64().filter(|max| len > *max) {
+            self.report(path, format!("has more than {} items", max));
+        }
+        if schema["uniqueItems"].as_bool() == Some(true) {
+            let duplicate = (0..items.len()).any(|i| items[..i].iter().any(|other| deep_equal(other, &items[i])));
+            if duplicate {
+                self.report(path, "has duplicate items".into());
+            }
+        }
+        // `items` is a schema for all items, or in older drafts a list of schemas by position
+        let positional = schema["prefixItems"].as_vec().or_else(|| schema["items"].as_vec());
+        for (i, item) in items.iter().enumerate() {
+            let item_schema = match positional {
+                Some(schemas) => schemas.get(i),
+                None => Some(&schema["items"]),
+            };
+            if let Some(item_schema) = item_schema {
+                path.push(PathSegment::Index(i));
+                self.check(item_schema, item, path);
+                path.pop();
+            }
+        }
+    }
+
+    fn check_object(&mut self, schema: &'a Yaml, value: &Yaml, path: &mut YamlPath) {
+        let Some(hash) = value.as_hash() else { return };
+        for required in schema["required"].as_vec().into_iter().flatten().filter_map(Yaml::as_str) {
+            if !hash.contains_key(&Yaml::String(required.into())) {
+                self.report(path, format!("required property {} is missing", required));
+            }
+        }
+        let len = hash.len() as i64;
+        if let Some(min) = schema["minProperties"].as_i64().filter(|min| len < *min) {
+            self.report(path, format!("has fewer than {} properties", min));
+        }
+        if let Some(max) = schema["maxProperties"].as_i64().filter(|max| len > *max) {
+            self.report(path, format!("has more than {} properties", max));
+        }
+        let patterns: Vec<(Regex, &'a Yaml)> = schema["patternProperties"]
+            .as_hash()
+            .into_iter()
+            .flatten()
+            .filter_map(|(pattern, schema)| Some((Regex::new(pattern.as_str()?).ok()?, schema)))
+            .collect();
+        for (key, child) in hash {
+            let name = key_to_string(key);
+            let mut matched = false;
+            path.push(PathSegment::Key(name.clone()));
+            if let Some(property) = schema["properties"].as_hash().and_then(|properties| properties.get(key)) {
+                matched = true;
+                self.check(property, child, path);
+            }
+            for (regex, property) in &patterns {
+                if regex.is_match(&name) {
+                    matched = true;
+                    self.check(property, child, path);
+                }
+            }
+            if !matched {
+                match &schema["additionalProperties"] {
+                    Yaml::Boolean(false) => self.report(path, "not an allowed property".into()),
+                    additional @ Yaml::Hash(_) => self.check(additional, child, path),
+                    _ => {}
+                }
+            }
+            path.pop();
+        }
+    }
+
+    fn check_combinations(&mut self, schema: &'a Yaml, value: &Yaml, path: &mut YamlPath) {
+        for subschema in schema["allOf"].as_vec().into_iter().flatten() {
+            self.check(subschema, value, path);
+        }
+        if let Some(subschemas) = schema["anyOf"].as_vec() {
+            if !subschemas.iter().any(|subschema| self.matches(subschema, value, path)) {
+                self.report(path, "does not match any of the anyOf schemas".into());
+            }
+        }
+        if let Some(subschemas) = schema["oneOf"].as_vec() {
+            let matching = subschemas.iter().filter(|subschema| self.matches(subschema, value, path)).count();
+            if matching != 1 {
+                self.report(path, format!("matches {} of the oneOf schemas instead of one", matching));
+            }
+        }
+        if !schema["not"].is_badvalue() && self.matches(&schema["not"], value, path) {
+            self.report(path, "matches the schema it must not".into());
+        }
+        if !schema["if"].is_badvalue() {
+            let branch = if self.matches(&schema["if"], value, path) { "then" } else { "else" };
+            self.check(&schema[branch], value, path);
+        }
+    }
+
+    /// Resolves a reference within the schema, a JSON pointer like `#/definitions/port`.
+    fn resolve(&self, reference: &str) -> Option<&'a Yaml> {
+        let pointer = reference.strip_prefix('#')?;
+        let mut target = self.root;
+        for token in pointer.split('/').skip(1) {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            target = match target {
+                Yaml::Array(items) => items.get(token.parse::<usize>().ok()?)?,
+                Yaml::Hash(hash) => hash.get(&Yaml::String(token))?,
+                _ => return None,
+            };
+        }
+        Some(target)
+    }
+}
+
+fn has_type(value: &Yaml, schema_type: &str) -> bool {
+    match schema_type {
+        "integer" => matches!(value, Yaml::Integer(_)) || (matches!(value, Yaml::Real(_)) && number(value).fract() == 0.0),
+        "number" => matches!(value, Yaml::Integer(_) | Yaml::Real(_)),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Yaml) -> &'static str {
+    match value {
+        Yaml::Hash(_) => "object",
+        Yaml::Array(_) => "array",
+        Yaml::String(_) => "string",
+        Yaml::Integer(_) => "integer",
+        Yaml::Real(_) => "number",
+        Yaml::Boolean(_) => "boolean",
+        _ => "null",
+    }
+}
+
+/// The value of a number, or NaN for anything else.
+fn number(value: &Yaml) -> f64 {
+    match value {
+        Yaml::Integer(i) => *i as f64,
+        Yaml::Real(_) => value.as_f64().unwrap_or(f64::NAN),
+        _ => f64::NAN,
+    }
+}
+
+/// A value as JSON on a single line, for messages.
+fn compact_json(value: &Yaml) -> String {
+    to_json(value).lines().map(str::trim).collect::<Vec<_>>().join(" ")
+}
+
+/// Infers a JSON Schema that `inputs` and the `read_base` they override all match.
+///
+/// Every path gets the types its values have; maps get their properties, and as required
+/// the keys every input has; lists get a schema for their items. Strings with between 2 and
+/// `enum_limit` distinct values, one of which repeats, are limited to them, except where the
+/// values are secret.
+pub fn infer_schema(read_base: Option<&Yaml>, inputs: &[&Yaml], enum_limit: usize, secrets: &SecretPolicy) -> Yaml {
+    // Whether a value counts for the required keys: the read-base has every key of the chart
+    let observations: Vec<(&Yaml, bool)> = read_base
+        .map(|doc| (doc, false))
+        .into_iter()
+        .chain(inputs.iter().map(|doc| (*doc, true)))
+        .collect();
+    let mut schema = Hash::new();
+    schema.insert(
+        Yaml::String("$schema".into()),
+        Yaml::String("http://json-schema.org/draft-07/schema#".into()),
+    );
+    if let Yaml::Hash(inferred) = infer_node(&observations, enum_limit, secrets, &mut YamlPath::root()) {
+        schema.extend(inferred);
+    }
+    Yaml::Hash(schema)
+}
+
+/// The schema of the values the files have at `path`, in the order they were first seen.
+fn infer_node(values: &[(&Yaml, bool)], enum_limit: usize, secrets: &SecretPolicy, path: &mut YamlPath) -> Yaml {
+    let mut schema = Hash::new();
+    let mut types: Vec<&str> = Vec::new();
+    for (value, _) in values {
+        let name = match type_name(value) {
+            "integer" if types.contains(&"number") => continue,
+            "number" => {
+                types.retain(|t| *t != "integer");
+                "number"
+            }
+            other => other,
+        };
+        if !types.contains(&name) {
+            types.push(name);
+        }
+    }
+    let type_value = match types.as_slice() {
+        [single] => Yaml::String(single.to_string()),
+        _ => Yaml::Array(types.iter().map(|t| Yaml::String(t.to_string())).collect()),
+    };
+    schema.insert(Yaml::String("type".into()), type_value);
+
+    // Maps: the properties of all of them, and the keys every input has
+    let maps: Vec<(&Hash, bool)> = values.iter().filter_map(|(value, input)| Some((value.as_hash()?, *input))).collect();
+    if !maps.is_empty() {
+        let mut seen = HashSet::new();
+        let keys: Vec<&Yaml> = maps.iter().flat_map(|(map, _)| map.keys()).filter(|key| seen.insert(*key)).collect();
+        let mut properties = Hash::new();
+        for key in &keys {
+            let children: Vec<(&Yaml, bool)> =
+                maps.iter().filter_map(|(map, input)| Some((map.get(key)?, *input))).collect();
+            path.push(PathSegment::Key(key_to_string(key)));
+            properties.insert(Yaml::String(key_to_string(key)), infer_node(&children, enum_limit, secrets, path));
+            path.pop();
+        }
+        schema.insert(Yaml::String("properties".into()), Yaml::Hash(properties));
+        // A null deletes the key from the effective values of Helm
+        let inputs: Vec<&Hash> = maps.iter().filter(|(_, input)| *input).map(|(map, _)| *map).collect();
+        let required: Array = keys
+            .iter()
+            .filter(|key| !inputs.is_empty() && inputs.iter().all(|map| map.get(key).is_some_and(|value| !value.is_null())))
+            .map(|key| Yaml::String(key_to_string(key)))
+            .collect();
+        if !required.is_empty() {
+            schema.insert(Yaml::String("required".into()), Yaml::Array(required));
+        }
+    }
+
+    // Lists: a schema all of their items match
+    let items: Vec<(&Yaml, bool)> = values
+        .iter()
+        .filter_map(|(value, input)| Some(value.as_vec()?.iter().map(move |item| (item, *input))))
+        .flatten()
+        .collect();
+    if !items.is_empty() {
+        path.push(PathSegment::Index(0));
+        schema.insert(Yaml::String("items".into()), infer_node(&items, enum_limit, secrets, path));
+        path.pop();
+    }
+
+    // Strings with few distinct values that repeat: an enum, unless they are secret
+    if types.iter().all(|t| *t == "string" || *t == "null") && types.contains(&"string") {
+        let all: Vec<&Yaml> = values.iter().map(|(value, _)| *value).collect();
+        let mut distinct: Vec<&Yaml> = Vec::new();
+        for value in &all {
+            if !distinct.iter().any(|d| deep_equal(d, value)) {
+                distinct.push(value);
+            }
+        }
+        let strings = distinct.iter().filter(|value| value.as_str().is_some()).count();
+        if (2..=enum_limit).contains(&strings) && all.len() > distinct.len() && !secrets.is_secret(path, &all) {
+            schema.insert(Yaml::String("enum".into()), Yaml::Array(distinct.into_iter().cloned().collect()));
+        }
+    }
+    Yaml::Hash(schema)
+}
\ No newline at end of file
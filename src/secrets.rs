@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use log::warn;
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::path::{key_to_string, parse_pattern_list, PathPattern, PathSegment, YamlPath};
+
+/// What a secret value is replaced with in logs and reports.
+pub const REDACTED: &str = "<redacted>";
+
+/// Parts of key names that mark their values as secret, compared in lowercase without `-` and `_`.
+const SECRET_WORDS: [&str; 8] = [
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "apikey",
+    "privatekey",
+    "accesskey",
+    "credential",
+];
+
+/// Key names that refer to a secret rather than hold one, like `existingSecret` or `tokenFile`.
+const REFERENCE_SUFFIXES: [&str; 5] = ["name", "ref", "file", "path", "enabled"];
+
+/// Strings of at least this many characters with at least this entropy, in bits per character,
+/// look generated.
+const MIN_ENTROPY_LENGTH: usize = 20;
+const MIN_ENTROPY: f64 = 4.0;
+
+/// Characters that separate the parts of structured values, like URLs and image references.
+const SEPARATORS: [char; 3] = ['.', '/', ':'];
+
+/// Which values are secret: they are never hoisted into a shared layer and are redacted in logs
+/// and reports. The default treats nothing as secret.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SecretPolicy {
+    /// Paths whose values are secret, e.g. `**.auth.password`.
+    pub paths: Vec<PathPattern>,
+    /// Treat the strings of keys like `password`, `token` or `apiKey`, and the data of Kubernetes
+    /// Secrets, as secret. Off unless configured, as settings like `maxTokens` match too.
+    pub key_names: bool,
+    /// Treat long random-looking strings, like generated tokens, as secret. Off unless configured,
+    /// as ordinary values can look random too.
+    pub entropy: bool,
+}
+
+impl SecretPolicy {
+    /// Reads the `secrets` section of the configuration file, e.g. `{paths: ["**.dsn"], entropy: true}`.
+    /// Key names and entropy are only checked when turned on; invalid settings are reported and
+    /// ignored.
+    pub fn from_yaml(yaml: &Yaml) -> Self {
+        let mut policy = SecretPolicy {
+            paths: parse_pattern_list(&yaml["paths"], "secret"),
+            key_names: false,
+            entropy: false,
+        };
+        for (key, flag) in [("keyNames", &mut policy.key_names), ("entropy", &mut policy.entropy)] {
+            match &yaml[key] {
+                Yaml::Boolean(b) => *flag = *b,
+                Yaml::BadValue => {}
+                other => warn!("Ignoring invalid secrets {}: {:?}", key, other),
+            }
+        }
+        policy
+    }
+
+    /// Checks whether the values the files have at `path` are secret, or hold secrets in lists.
+    /// Maps are not secret as a whole unless a path rule says so; their keys are checked one by one.
+    /// A key name only makes strings secret, so `automountServiceAccountToken: true` is not.
+    pub fn is_secret(&self, path: &YamlPath, values: &[&Yaml]) -> bool {
+        if self.paths.iter().any(|pattern| pattern.matches(path)) {
+            return true;
+        }
+        let scalars = values.iter().all(|value| value.as_hash().is_none());
+        let text = values.iter().all(|value| is_text(value));
+        if self.key_names && text && (is_secret_key_at(path) || is_secret_data(path)) {
+            return true;
+        }
+        scalars && values.iter().any(|value| self.holds_secret(value))
+    }
+
+    /// A copy of `value`, found at `path`, with its secret values replaced by [`REDACTED`].
+    pub fn redact(&self, path: &YamlPath, value: &Yaml) -> Yaml {
+        if self.is_secret(path, &[value]) {
+            return Yaml::String(REDACTED.into());
+        }
+        match value {
+            Yaml::Hash(hash) => {
+                let mut redacted = Hash::new();
+                for (key, child) in hash {
+                    redacted.insert(key.clone(), self.redact(&path.child_key(key), child));
+                }
+                Yaml::Hash(redacted)
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Checks a value that is not a map for generated-looking strings, and maps in lists for
+    /// secret keys.
+    fn holds_secret(&self, value: &Yaml) -> bool {
+        match value {
+            Yaml::String(s) => self.entropy && is_high_entropy(s),
+            Yaml::Array(items) => items.iter().any(|item| self.holds_secret(item)),
+            Yaml::Hash(hash) => hash.iter().any(|(key, value)| {
+                let secret_key = self.key_names && is_text(value) && is_secret_key(&key_to_string(key));
+                secret_key || self.holds_secret(value)
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// Checks whether a key name suggests a secret value, like `password`, `authToken` or `API_KEY`,
+/// and not a reference to one, like `existingSecret` or `secretName`.
+pub fn is_secret_key(key: &str) -> bool {
+    let key: String = key.chars().filter(|c| *c != '-' && *c != '_').collect::<String>().to_lowercase();
+    SECRET_WORDS.iter().any(|word| key.contains(word))
+        && !key.starts_with("existing")
+        && !REFERENCE_SUFFIXES.iter().any(|suffix| key.ends_with(suffix))
+}
+
+/// Checks whether a string looks generated: long, without whitespace, mixing letters and digits,
+/// not a hex digest or UUID, with a high Shannon entropy. Strings with `.`, `/` or `:` separators,
+/// like URLs, image references and host names, are structured rather than generated.
+pub fn is_high_entropy(s: &str) -> bool {
+    if s.len() < MIN_ENTROPY_LENGTH || s.chars().any(|c| c.is_whitespace() || SEPARATORS.contains(&c)) {
+        return false;
+    }
+    let hex = s.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+    let mixed = s.chars().any(|c| c.is_ascii_digit()) && s.chars().any(|c| c.is_ascii_alphabetic());
+    !hex && mixed && entropy(s) >= MIN_ENTROPY
+}
+
+/// Shannon entropy in bits per character.
+fn entropy(s: &str) -> f64 {
+    let mut counts = HashMap::new();
+    for c in s.chars() {
+        *counts.entry(c).or_insert(0usize) += 1;
+    }
+    let len = s.chars().count() as f64;
+    counts.values().map(|&n| n as f64 / len).map(|p| -p * p.log2()).sum()
+}
+
+/// Checks whether a document is encrypted with sops: it has a top-level `sops` map with its metadata.
+pub fn is_sops_encrypted(doc: &Yaml) -> bool {
+    let sops = &doc["sops"];
+    sops.as_hash().is_some() && (!sops["mac"].is_badvalue() || !sops["lastmodified"].is_badvalue())
+}
+
+/// Strings, and lists that may hold them, like `tokens: [...]`.
+fn is_text(value: &Yaml) -> bool {
+    matches!(value, Yaml::String(_) | Yaml::Array(_))
+}
+
+fn is_secret_key_at(path: &YamlPath) -> bool {
+    match path.segments().last() {
+        Some(PathSegment::Key(key)) => is_secret_key(key),
+        _ => false,
+    }
+}
+
+/// The `data` and `stringData` of a Kubernetes Secret in a manifest bundle.
+fn is_secret_data(path: &YamlPath) -> bool {
+    match path.segments() {
+        [PathSegment::Key(resource), PathSegment::Key(field), ..] => {
+            resource.starts_with("Secret/") && (field == "data" || field == "stringData")
+        }
+        _ => false,
+    }
+}
\ No newline at end of file
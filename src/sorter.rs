@@ -1,37 +1,124 @@
 use yaml_rust2::yaml::{Array, Hash, Yaml};
 use std::borrow::Cow;
 
+use crate::path::{key_to_string, parse_patterns, PathPattern, PathSegment, YamlPath};
+
+/// Sort settings that apply at a single location of the document.
+struct SortRule<'c> {
+    sort_key: Option<&'c str>,
+    /// `keepOrder: true` leaves arrays in their original order, whatever the global `sortKey`.
+    keep_order: bool,
+    pre_order: Option<Vec<&'c str>>,
+}
+
+impl<'c> SortRule<'c> {
+    fn from_yaml(yaml: &'c Yaml) -> Self {
+        SortRule {
+            sort_key: yaml["sortKey"].as_str(),
+            keep_order: yaml["keepOrder"].as_bool().unwrap_or(false),
+            pre_order: yaml["preOrder"].as_vec().map(|pre_order_vec| {
+                pre_order_vec
+                    .iter()
+                    .filter_map(|x| x.as_str())
+                    .collect::<Vec<&str>>()
+            }),
+        }
+    }
+}
+
+/// The global rule plus the path-scoped `rules` of a sort configuration.
+///
+/// A scoped rule replaces the global `sortKey` or `preOrder` at the paths it matches;
+/// the first matching rule wins.
+struct SortConfig<'c> {
+    global: SortRule<'c>,
+    scoped: Vec<(PathPattern, SortRule<'c>)>,
+}
+
+impl<'c> SortConfig<'c> {
+    fn from_yaml(config: &'c Yaml) -> Self {
+        SortConfig {
+            global: SortRule::from_yaml(config),
+            scoped: parse_patterns(&config["rules"])
+                .into_iter()
+                .map(|(pattern, rule)| (pattern, SortRule::from_yaml(rule)))
+                .collect(),
+        }
+    }
+
+    fn sort_key(&self, path: &YamlPath) -> Option<&'c str> {
+        self.scoped
+            .iter()
+            .find(|(pattern, rule)| (rule.sort_key.is_some() || rule.keep_order) && pattern.matches(path))
+            .map_or(self.global.sort_key, |(_, rule)| rule.sort_key.filter(|_| !rule.keep_order))
+    }
+
+    fn pre_order(&self, path: &YamlPath) -> Option<&[&'c str]> {
+        self.scoped
+            .iter()
+            .find(|(pattern, rule)| rule.pre_order.is_some() && pattern.matches(path))
+            .map_or(self.global.pre_order.as_deref(), |(_, rule)| rule.pre_order.as_deref())
+    }
+}
+
 pub fn sort_yaml<'a>(doc: &'a Yaml, config: &Yaml) -> Cow<'a, Yaml> {
+    let config = SortConfig::from_yaml(config);
+    sort_node(doc, &config, None, &mut YamlPath::root())
+}
+
+/// Sorts like [`sort_yaml`], but keys that also appear at the same location of `reference`
+/// (typically the chart's values.yaml) come first, in the order of `reference`.
+pub fn sort_yaml_with_reference<'a>(doc: &'a Yaml, config: &Yaml, reference: &Yaml) -> Cow<'a, Yaml> {
+    let config = SortConfig::from_yaml(config);
+    sort_node(doc, &config, Some(reference), &mut YamlPath::root())
+}
+
+fn sort_node<'a>(
+    doc: &'a Yaml,
+    config: &SortConfig,
+    reference: Option<&Yaml>,
+    path: &mut YamlPath,
+) -> Cow<'a, Yaml> {
     match doc {
         Yaml::Array(v) => {
-            if let Some(sort_key) = config["sortKey"].as_str() {
-                let mut new_v = v.clone();
+            let sort_key = config.sort_key(path);
+            let reference = reference.and_then(|r| r.as_vec());
+            if sort_key.is_none() && config.scoped.is_empty() && reference.is_none() {
+                return Cow::Borrowed(doc);
+            }
+            let mut new_v = v.clone();
+            if let Some(sort_key) = sort_key {
                 array_sorter(&mut new_v, sort_key);
-                for x in &mut new_v {
-                    let sorted = sort_yaml(x, config);
-                    *x = sorted.into_owned();
-                }
-                Cow::Owned(Yaml::Array(new_v))
-            } else {
-                Cow::Borrowed(doc)
             }
+            for (i, x) in new_v.iter_mut().enumerate() {
+                let item_reference = reference.and_then(|r| r.get(i).or_else(|| r.first()));
+                path.push(PathSegment::Index(i));
+                let sorted = sort_node(x, config, item_reference, path);
+                *x = sorted.into_owned();
+                path.pop();
+            }
+            Cow::Owned(Yaml::Array(new_v))
         }
         Yaml::Hash(h) => {
-            if let Some(pre_order_vec) = config["preOrder"].as_vec() {
-                let mut new_h = h.clone();
-                let pre_order = pre_order_vec
-                    .iter()
-                    .filter_map(|x| x.as_str())
-                    .collect::<Vec<&str>>();
-                hash_sorter(&mut new_h, &pre_order);
-                for (_, v) in &mut new_h {
-                    let sorted = sort_yaml(v, config);
-                    *v = sorted.into_owned();
-                }
-                Cow::Owned(Yaml::Hash(new_h))
-            } else {
-                Cow::Borrowed(doc)
+            let pre_order = config.pre_order(path);
+            let reference = reference.and_then(|r| r.as_hash());
+            if pre_order.is_none() && config.scoped.is_empty() && reference.is_none() {
+                return Cow::Borrowed(doc);
+            }
+            let mut new_h = h.clone();
+            match (reference, pre_order) {
+                (Some(reference), pre_order) => hash_sorter_by_reference(&mut new_h, reference, pre_order),
+                (None, Some(pre_order)) => hash_sorter(&mut new_h, pre_order),
+                (None, None) => {}
+            }
+            for (k, v) in &mut new_h {
+                let child_reference = reference.and_then(|r| r.get(k));
+                path.push(PathSegment::Key(key_to_string(k)));
+                let sorted = sort_node(v, config, child_reference, path);
+                *v = sorted.into_owned();
+                path.pop();
             }
+            Cow::Owned(Yaml::Hash(new_h))
         }
         _ => Cow::Borrowed(doc),
     }
@@ -49,7 +136,7 @@ pub fn hash_sorter(hash: &mut Hash, pre_order: &[&str]) {
 
     // Collect the remaining keys
     let mut hash_keys: Vec<Yaml> = hash.keys().cloned().collect();
-    hash_keys.sort_by(|a, b| a.cmp(b));
+    hash_keys.sort();
 
     for key in hash_keys {
         if let Some((k, v)) = hash.remove_entry(&key) {
@@ -60,6 +147,25 @@ pub fn hash_sorter(hash: &mut Hash, pre_order: &[&str]) {
     *hash = result;
 }
 
+/// Puts the keys of `hash` that appear in `reference` first, in the reference order.
+/// The remaining keys follow, ordered by `pre_order` when given and left as they are otherwise.
+pub fn hash_sorter_by_reference(hash: &mut Hash, reference: &Hash, pre_order: Option<&[&str]>) {
+    let mut result = Hash::new();
+
+    for key in reference.keys() {
+        if let Some((k, v)) = hash.remove_entry(key) {
+            result.insert(k, v);
+        }
+    }
+
+    if let Some(pre_order) = pre_order {
+        hash_sorter(hash, pre_order);
+    }
+    result.extend(std::mem::take(hash));
+
+    *hash = result;
+}
+
 pub fn array_sorter(array: &mut Array, sort_key: &str) {
     array.sort_by(|a, b| match (a[sort_key].as_str(), b[sort_key].as_str()) {
         (Some(a_str), Some(b_str)) => a_str.cmp(b_str),
@@ -67,4 +173,4 @@ pub fn array_sorter(array: &mut Array, sort_key: &str) {
         (None, Some(_)) => std::cmp::Ordering::Greater,
         (None, None) => std::cmp::Ordering::Equal,
     });
-}
\ No newline at end of file
+}
@@ -0,0 +1,120 @@
+use yaml_rust2::yaml::{Array, Hash, Yaml};
+
+use crate::merge::{item_key, MergeStrategy};
+use crate::path::PathPattern;
+
+/// `$patch: replace|delete` on a map or list item.
+pub const PATCH: &str = "$patch";
+/// `$retainKeys: [...]`: only these keys of the map are kept after the merge.
+pub const RETAIN_KEYS: &str = "$retainKeys";
+/// `$setElementOrder/<field>: [...]`: the order of the merged list `<field>`.
+pub const SET_ELEMENT_ORDER: &str = "$setElementOrder/";
+/// `$deleteFromPrimitiveList/<field>: [...]`: values removed from the merged list `<field>`.
+pub const DELETE_FROM_PRIMITIVE_LIST: &str = "$deleteFromPrimitiveList/";
+
+/// Lists the Kubernetes API merges by key, with their `patchMergeKey`.
+const MERGE_KEYS: &[(&str, &str)] = &[
+    ("**.containers", "name"),
+    ("**.initContainers", "name"),
+    ("**.ephemeralContainers", "name"),
+    ("**.containers[*].ports", "containerPort"),
+    ("**.initContainers[*].ports", "containerPort"),
+    ("**.ephemeralContainers[*].ports", "containerPort"),
+    ("**.env", "name"),
+    ("**.volumeMounts", "mountPath"),
+    ("**.volumeDevices", "devicePath"),
+    ("**.resizePolicy", "resourceName"),
+    ("**.volumes", "name"),
+    ("**.imagePullSecrets", "name"),
+    ("**.hostAliases", "ip"),
+    ("**.topologySpreadConstraints", "topologyKey"),
+    ("**.resourceClaims", "name"),
+    ("**.schedulingGates", "name"),
+    ("**.spec.ports", "port"),
+    ("**.metadata.ownerReferences", "uid"),
+];
+
+/// Lists of scalars the Kubernetes API merges as sets.
+const SET_LISTS: &[&str] = &["**.metadata.finalizers"];
+
+/// The merge strategies of the Kubernetes API lists, to be used after any user rules.
+pub fn kubernetes_rules() -> Vec<(PathPattern, MergeStrategy)> {
+    MERGE_KEYS
+        .iter()
+        .map(|(path, key)| (parse(path), MergeStrategy::MergeByKey(key.to_string())))
+        .chain(SET_LISTS.iter().map(|path| (parse(path), MergeStrategy::Union)))
+        .collect()
+}
+
+fn parse(path: &str) -> PathPattern {
+    path.parse().expect("built-in merge key pattern")
+}
+
+/// Checks whether a map key is a patch directive rather than a field.
+pub fn is_directive(key: &Yaml) -> bool {
+    key.as_str().is_some_and(|k| k.starts_with('$'))
+}
+
+/// The `$patch` directive of a map, if any.
+pub fn patch_directive(value: &Yaml) -> Option<&str> {
+    value.as_hash()?.get(&Yaml::String(PATCH.into()))?.as_str()
+}
+
+/// Removes the directives from a value that has nothing to be merged into.
+/// Returns `None` if the value deletes itself with `$patch: delete`.
+pub fn strip_directives(value: &Yaml) -> Option<Yaml> {
+    match value {
+        Yaml::Hash(hash) => {
+            if patch_directive(value) == Some("delete") {
+                return None;
+            }
+            let mut stripped = Hash::new();
+            for (k, v) in hash {
+                if is_directive(k) {
+                    continue;
+                }
+                if let Some(v) = strip_directives(v) {
+                    stripped.insert(k.clone(), v);
+                }
+            }
+            Some(Yaml::Hash(stripped))
+        }
+        Yaml::Array(array) => Some(Yaml::Array(
+            array
+                .iter()
+                .filter(|item| patch_directive(item) != Some("replace"))
+                .filter_map(strip_directives)
+                .collect(),
+        )),
+        other => Some(other.clone()),
+    }
+}
+
+/// Orders `list` as given by a `$setElementOrder` directive; items it does not name keep
+/// their relative order after the named ones.
+pub fn set_element_order(list: &mut Array, order: &Array, strategy: &MergeStrategy) {
+    let position = |item: &Yaml| {
+        order.iter().position(|entry| match strategy {
+            MergeStrategy::MergeByKey(key) => item_key(entry, key).is_some() && item_key(entry, key) == item_key(item, key),
+            _ => entry == item,
+        })
+    };
+    list.sort_by_key(|item| position(item).unwrap_or(usize::MAX));
+}
+
+/// The `$setElementOrder` entries describing the order of `list`.
+pub fn element_order(list: &Array, strategy: &MergeStrategy) -> Array {
+    list.iter()
+        .map(|item| match strategy {
+            MergeStrategy::MergeByKey(key) => match item_key(item, key) {
+                Some(value) => {
+                    let mut entry = Hash::new();
+                    entry.insert(Yaml::String(key.clone()), value.clone());
+                    Yaml::Hash(entry)
+                }
+                None => item.clone(),
+            },
+            _ => item.clone(),
+        })
+        .collect()
+}
\ No newline at end of file
@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::revision::revision_path;
+
+/// An error in an output path template, or paths that collide.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateError(String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid output path: {}", self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// A value an output path template refers to with `{name}`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Placeholder {
+    /// The output folder.
+    Out,
+    /// The directory of the input file.
+    Dir,
+    /// The file name of the input file without its extension.
+    Stem,
+    /// The extension of the input file, e.g. `yaml`.
+    Ext,
+    /// The extension of the output format, `yaml` or `json`.
+    Format,
+    /// The name of the directory of the input file.
+    Group,
+    /// The position of the input file on the command line, starting at 1.
+    Index,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// An output path with placeholders, e.g. `{out}/{stem}_diff.{format}` or `{dir}/overrides.yaml`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathTemplate {
+    segments: Vec<Segment>,
+}
+
+impl FromStr for PathTemplate {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| TemplateError(format!("unclosed '{{' in {}", s)))?;
+            let placeholder = match &rest[start + 1..start + end] {
+                "out" => Placeholder::Out,
+                "dir" => Placeholder::Dir,
+                "stem" => Placeholder::Stem,
+                "ext" => Placeholder::Ext,
+                "format" => Placeholder::Format,
+                "group" => Placeholder::Group,
+                "index" => Placeholder::Index,
+                other => return Err(TemplateError(format!("unknown placeholder {{{}}} in {}", other, s))),
+            };
+            segments.push(Segment::Placeholder(placeholder));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Ok(PathTemplate { segments })
+    }
+}
+
+impl PathTemplate {
+    /// The path for the input file `input`, the `index`-th on the command line from 0, writing
+    /// `format` files under the output folder `out`. A `REV:path` input is named after its path.
+    ///
+    /// A placeholder that is empty, like the `{group}` of a file in the current directory,
+    /// leaves out its path component rather than leaving it empty.
+    pub fn render(&self, input: &str, index: usize, out: &str, format: &str) -> String {
+        let path = Path::new(revision_path(input).map_or(input, |(_, path)| path));
+        let dir = path.parent().and_then(Path::to_str).filter(|dir| !dir.is_empty()).unwrap_or(".");
+        let group = Path::new(dir).file_name().and_then(|name| name.to_str()).unwrap_or("");
+        let index = (index + 1).to_string();
+        let mut components = vec![String::new()];
+        let mut absolute = None;
+        for segment in &self.segments {
+            let text = match segment {
+                Segment::Literal(literal) => literal.as_str(),
+                Segment::Placeholder(Placeholder::Out) => out,
+                Segment::Placeholder(Placeholder::Dir) => dir,
+                Segment::Placeholder(Placeholder::Stem) => path.file_stem().and_then(|s| s.to_str()).unwrap_or("diff"),
+                Segment::Placeholder(Placeholder::Ext) => path.extension().and_then(|s| s.to_str()).unwrap_or(""),
+                Segment::Placeholder(Placeholder::Format) => format,
+                Segment::Placeholder(Placeholder::Group) => group,
+                Segment::Placeholder(Placeholder::Index) => &index,
+            };
+            // The path is absolute if the template or its first placeholder starts with `/`
+            absolute.get_or_insert(text.starts_with('/'));
+            if text.is_empty() {
+                continue;
+            }
+            let mut parts = text.split('/');
+            components.last_mut().unwrap().push_str(parts.next().unwrap_or(""));
+            components.extend(parts.map(str::to_string));
+        }
+        components.retain(|component| !component.is_empty());
+        let rendered = components.join("/");
+        if absolute == Some(true) {
+            format!("/{}", rendered)
+        } else {
+            rendered
+        }
+    }
+
+    /// Whether the template refers to the file name or the position of an input file.
+    fn is_per_input(&self) -> bool {
+        self.segments.iter().any(|segment| {
+            matches!(segment, Segment::Placeholder(Placeholder::Stem) | Segment::Placeholder(Placeholder::Index))
+        })
+    }
+}
+
+/// The paths of the diffs of `inputs`, checked to be distinct from each other and from the
+/// `reserved` paths, like the base file.
+pub fn output_paths(
+    template: &PathTemplate,
+    inputs: &[&str],
+    out: &str,
+    format: &str,
+    reserved: &[&str],
+) -> Result<Vec<String>, TemplateError> {
+    let paths: Vec<String> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| template.render(input, index, out, format))
+        .collect();
+    let reserved: HashSet<PathBuf> = reserved.iter().map(|path| normalize(path)).collect();
+    let mut taken: HashMap<PathBuf, &str> = HashMap::new();
+    for (input, path) in inputs.iter().zip(&paths) {
+        if reserved.contains(&normalize(path)) {
+            return Err(TemplateError(format!("the diff of {} would overwrite {}", input, path)));
+        }
+        if let Some(other) = taken.insert(normalize(path), input) {
+            return Err(TemplateError(format!("{} and {} would both be written to {}", other, input, path)));
+        }
+    }
+    Ok(paths)
+}
+
+/// The path of a file shared by all `inputs`, like the base: the template must render the same
+/// path for each of them, so it cannot refer to `{stem}` or `{index}`.
+pub fn shared_path(template: &PathTemplate, inputs: &[&str], out: &str, format: &str) -> Result<String, TemplateError> {
+    if template.is_per_input() {
+        return Err(TemplateError("{stem} and {index} only apply to the paths of the diffs".into()));
+    }
+    let mut paths = inputs.iter().map(|input| template.render(input, 0, out, format));
+    let first = paths.next().unwrap_or_else(|| template.render("", 0, out, format));
+    match paths.find(|path| normalize(path) != normalize(&first)) {
+        Some(other) => Err(TemplateError(format!(
+            "the input files are in different directories, the path is {} for some and {} for others",
+            first, other
+        ))),
+        None => Ok(first),
+    }
+}
+
+/// The names of the environments or overlays written for `inputs`: the file names of the input
+/// files without their extensions, like `{stem}`, checked to be distinct from each other.
+pub fn input_names(inputs: &[&str]) -> Result<Vec<String>, TemplateError> {
+    let mut taken: HashMap<&str, &str> = HashMap::new();
+    let mut names = Vec::new();
+    for input in inputs {
+        let path = Path::new(revision_path(input).map_or(*input, |(_, path)| path));
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("diff");
+        if let Some(other) = taken.insert(name, input) {
+            return Err(TemplateError(format!("{} and {} would both be named {}", other, input, name)));
+        }
+        names.push(name.to_string());
+    }
+    Ok(names)
+}
+
+/// The path without `.` components, so `./out/a.yaml` and `out/a.yaml` compare equal.
+fn normalize(path: &str) -> PathBuf {
+    Path::new(path).components().filter(|c| *c != Component::CurDir).collect()
+}
\ No newline at end of file
@@ -0,0 +1,98 @@
+use yabe::argocd::{application_set, applications, update_manifests, ArgoConfig, Environment};
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+fn config() -> ArgoConfig {
+    ArgoConfig::from_yaml(&load(
+        "chart: {repoURL: https://charts.example.com, chart: web, targetRevision: 1.2.3}
+values: {repoURL: https://github.com/example/gitops.git, targetRevision: main}
+destination: {server: https://kubernetes.default.svc, namespace: 'web-{env}'}",
+    ))
+}
+
+fn environments() -> Vec<Environment> {
+    vec![
+        Environment { name: "dev".into(), diff_file: "./out/dev_diff.yaml".into(), has_diff: true },
+        Environment { name: "prod".into(), diff_file: "./out/prod_diff.yaml".into(), has_diff: false },
+    ]
+}
+
+fn strings(value: &Yaml) -> Vec<&str> {
+    value.as_vec().unwrap().iter().map(|v| v.as_str().unwrap()).collect()
+}
+
+#[test]
+fn test_applications_list_value_files_in_order() {
+    let shared = vec!["chart/values.yaml".to_string(), "./base.yaml".to_string()];
+    let apps = applications(&config(), &shared, &environments());
+
+    assert_eq!(apps.len(), 2);
+    assert_eq!(apps[0]["metadata"]["name"].as_str(), Some("web-dev"));
+    assert_eq!(apps[0]["spec"]["destination"]["namespace"].as_str(), Some("web-dev"));
+    let sources = &apps[0]["spec"]["sources"];
+    assert_eq!(sources[0]["chart"].as_str(), Some("web"));
+    assert_eq!(
+        strings(&sources[0]["helm"]["valueFiles"]),
+        ["$values/chart/values.yaml", "$values/base.yaml", "$values/out/dev_diff.yaml"]
+    );
+    assert_eq!(sources[1]["ref"].as_str(), Some("values"));
+    // Without a diff, only the shared files apply
+    assert_eq!(
+        strings(&apps[1]["spec"]["sources"][0]["helm"]["valueFiles"]),
+        ["$values/chart/values.yaml", "$values/base.yaml"]
+    );
+}
+
+#[test]
+fn test_application_set_has_an_element_per_environment() {
+    let set = application_set(&config(), &["base.yaml".to_string()], &environments());
+
+    assert_eq!(set["kind"].as_str(), Some("ApplicationSet"));
+    let elements = &set["spec"]["generators"][0]["list"]["elements"];
+    assert_eq!(elements[1]["env"].as_str(), Some("prod"));
+    assert_eq!(elements[1]["diffFile"].as_str(), Some("out/prod_diff.yaml"));
+    let template = &set["spec"]["template"];
+    assert_eq!(template["metadata"]["name"].as_str(), Some("web-{{.env}}"));
+    assert_eq!(
+        strings(&template["spec"]["sources"][0]["helm"]["valueFiles"]),
+        ["$values/base.yaml", "$values/{{.diffFile}}"]
+    );
+}
+
+#[test]
+fn test_update_keeps_settings_and_drops_removed_applications() {
+    let existing = YamlLoader::load_from_str(
+        "apiVersion: argoproj.io/v1alpha1
+kind: Application
+metadata: {name: web-dev, namespace: argocd}
+spec:
+  sources:
+    - repoURL: https://charts.example.com
+      chart: web
+      helm: {releaseName: web, valueFiles: [$values/old.yaml]}
+  syncPolicy: {automated: {}}
+---
+apiVersion: argoproj.io/v1alpha1
+kind: Application
+metadata: {name: web-staging, namespace: argocd}
+spec: {}",
+    )
+    .unwrap();
+    let generated = applications(&config(), &["base.yaml".to_string()], &environments());
+
+    let updated = update_manifests(&existing, generated);
+
+    let names: Vec<&str> = updated.iter().map(|doc| doc["metadata"]["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["web-dev", "web-prod"]);
+    let dev = &updated[0]["spec"];
+    assert!(!dev["syncPolicy"]["automated"].is_badvalue());
+    assert_eq!(dev["sources"][0]["helm"]["releaseName"].as_str(), Some("web"));
+    assert_eq!(
+        strings(&dev["sources"][0]["helm"]["valueFiles"]),
+        ["$values/base.yaml", "$values/out/dev_diff.yaml"]
+    );
+    assert_eq!(dev["sources"][1]["ref"].as_str(), Some("values"));
+}
\ No newline at end of file
@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use yabe::chart::{is_chart, load_chart_schema, load_chart_values};
+use yabe::deep_equal::deep_equal;
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+const CHART: &[(&str, &str)] = &[
+    (
+        "Chart.yaml",
+        "name: web\ndependencies:\n  - name: redis\n    alias: cache\n  - name: redis\n    alias: queue",
+    ),
+    ("values.yaml", "replicas: 2\ncache:\n  port: 6380\n  auth: ~\nglobal:\n  region: eu"),
+    ("templates/deployment.yaml", "kind: Deployment"),
+    ("charts/redis/Chart.yaml", "name: redis"),
+    ("charts/redis/values.yaml", "port: 6379\nauth: {enabled: true}\nglobal:\n  region: us\n  storageClass: ssd"),
+];
+
+/// Writes the files of a chart into a fresh directory under the system temp dir.
+fn chart_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("yabe-test-chart-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for (path, content) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+/// Packages files as a chart archive, inside a directory named after the chart.
+fn archive(chart: &str, files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (path, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, Path::new(chart).join(path), *content).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+#[test]
+fn test_read_values_of_chart_directory() {
+    let dir = chart_dir("plain", CHART);
+
+    assert!(is_chart(&dir));
+    let values = load_chart_values(&dir, false).unwrap();
+
+    assert!(deep_equal(&values, &load("replicas: 2\ncache:\n  port: 6380\n  auth: ~\nglobal:\n  region: eu")));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_read_chart_schema() {
+    let schema = r#"{"type": "object", "properties": {"replicas": {"type": "integer", "minimum": 1}}}"#;
+    let mut files = CHART.to_vec();
+    files.push(("values.schema.json", schema));
+    let dir = chart_dir("schema", &files);
+
+    let without = chart_dir("no-schema", CHART);
+
+    let loaded = load_chart_schema(&dir).unwrap().unwrap();
+
+    assert_eq!(loaded["properties"]["replicas"]["minimum"].as_i64(), Some(1));
+    assert!(load_chart_schema(&without).unwrap().is_none());
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(without).unwrap();
+}
+
+#[test]
+fn test_fold_subchart_defaults_under_aliases() {
+    let dir = chart_dir("subcharts", CHART);
+
+    let values = load_chart_values(&dir, true).unwrap();
+
+    // The parent's values win, and its null deletes the subchart default
+    let expected = load(
+        "replicas: 2
+cache: {port: 6380}
+global: {region: eu, storageClass: ssd}
+queue: {port: 6379, auth: {enabled: true}}",
+    );
+    assert!(deep_equal(&values, &expected), "{:?}", values);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_read_packaged_chart_with_packaged_subchart() {
+    let redis = archive("redis", &[("Chart.yaml", b"name: redis"), ("values.yaml", b"port: 6379")]);
+    let web = archive(
+        "web",
+        &[
+            ("Chart.yaml", b"name: web\ndependencies: [{name: redis}]"),
+            ("values.yaml", b"replicas: 1"),
+            ("charts/redis-1.0.0.tgz", &redis),
+        ],
+    );
+    let path = std::env::temp_dir().join(format!("yabe-test-web-{}.tgz", std::process::id()));
+    fs::write(&path, web).unwrap();
+
+    assert!(is_chart(&path));
+    assert!(deep_equal(&load_chart_values(&path, false).unwrap(), &load("replicas: 1")));
+    assert!(deep_equal(&load_chart_values(&path, true).unwrap(), &load("replicas: 1\nredis: {port: 6379}")));
+    fs::remove_file(path).unwrap();
+}
\ No newline at end of file
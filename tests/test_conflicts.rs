@@ -0,0 +1,62 @@
+use yabe::conflicts::{find_type_conflicts, find_type_conflicts_with, ConflictPolicy};
+use yabe::merge::{MergeOptions, MergeStrategy};
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+#[test]
+fn test_finds_shape_conflicts() {
+    let chart = load("resources:\n  limits: {cpu: 1}\nimage:\n  tag: '1.0'\nlist: [a]");
+    let app = load("resources: ''\nimage:\n  tag: 1.0\nlist: [{a: 1}]");
+
+    let conflicts = find_type_conflicts(&[("chart.yaml", &chart), ("app.yaml", &app)]);
+
+    let rendered: Vec<String> = conflicts.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        rendered,
+        [
+            "resources: map in chart.yaml, string in app.yaml",
+            "image.tag: string in chart.yaml, number in app.yaml",
+            "list[0]: string in chart.yaml, map in app.yaml",
+        ]
+    );
+}
+
+#[test]
+fn test_finds_conflicts_in_list_items_by_merge_key() {
+    let chart = load("env:\n  - {name: A, value: '1'}\n  - {name: B, value: {x: 1}}\nports: [{port: 80}]");
+    let app = load("env:\n  - {name: B, value: x}\nports: [{port: http}]");
+    let rule = vec![("env".parse().unwrap(), MergeStrategy::MergeByKey("name".into()))];
+
+    let conflicts = find_type_conflicts_with(&[("chart.yaml", &chart), ("app.yaml", &app)], &MergeOptions::new(rule));
+
+    let rendered: Vec<String> = conflicts.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        rendered,
+        [
+            "env[1].value: map in chart.yaml, string in app.yaml",
+            "ports[0].port: number in chart.yaml, string in app.yaml",
+        ]
+    );
+}
+
+#[test]
+fn test_ignores_nulls_and_missing_keys() {
+    let chart = load("a: {b: 1}\nc: 1\nd: 1.5");
+    let env = load("a: ~\ne: x");
+    let app = load("a: {b: 2, f: [1]}\nd: 2");
+
+    assert!(find_type_conflicts(&[("chart.yaml", &chart), ("env.yaml", &env), ("app.yaml", &app)]).is_empty());
+}
+
+#[test]
+fn test_conflict_policy() {
+    assert_eq!("error".parse::<ConflictPolicy>(), Ok(ConflictPolicy::Error));
+    assert_eq!(ConflictPolicy::default(), ConflictPolicy::Warn);
+    assert!("strict".parse::<ConflictPolicy>().is_err());
+
+    let conflicts = find_type_conflicts(&[("a.yaml", &load("[1]")), ("b.yaml", &load("x: 1"))]);
+    assert_eq!(conflicts[0].to_string(), "document root: list in a.yaml, map in b.yaml");
+}
\ No newline at end of file
@@ -1,4 +1,4 @@
-use yabe::deep_equal::deep_equal;
+use yabe::deep_equal::{deep_equal, deep_equal_with, EqualityPolicy};
 use yaml_rust2::{YamlLoader, Yaml};
 
 #[test]
@@ -76,4 +76,41 @@ fn test_deep_equal_arrays_nested() {
 
     assert!(deep_equal(&yaml1, &yaml2));
     assert!(!deep_equal(&yaml1, &yaml3));
+}
+
+#[test]
+fn test_deep_equal_with_numeric_policy() {
+    let policy = EqualityPolicy { numeric: true, ..EqualityPolicy::default() };
+    let yaml = YamlLoader::load_from_str("[1.0, 1.00, 1e0, 1]").unwrap().into_iter().next().unwrap();
+    let items = yaml.as_vec().unwrap();
+
+    assert!(!deep_equal(&items[0], &items[1]));
+    assert!(items.iter().all(|item| deep_equal_with(item, &items[0], &policy)));
+    assert!(!deep_equal_with(&items[0], &Yaml::Real("1.5".into()), &policy));
+}
+
+#[test]
+fn test_deep_equal_with_string_policies() {
+    let coerce = EqualityPolicy { coerce_strings: true, ..EqualityPolicy::default() };
+    let whitespace = EqualityPolicy { ignore_whitespace: true, ..EqualityPolicy::default() };
+    let yaml1 = YamlLoader::load_from_str("port: 80\nflag: true\nscript: |\n  echo  hi\n").unwrap().into_iter().next().unwrap();
+    let yaml2 = YamlLoader::load_from_str("port: '80'\nflag: 'true'\nscript: echo hi").unwrap().into_iter().next().unwrap();
+
+    assert!(!deep_equal(&yaml1["port"], &yaml2["port"]));
+    assert!(deep_equal_with(&yaml1["port"], &yaml2["port"], &coerce));
+    assert!(deep_equal_with(&yaml1["flag"], &yaml2["flag"], &coerce));
+    assert!(!deep_equal_with(&Yaml::String("web".into()), &Yaml::Integer(80), &coerce));
+    assert!(!deep_equal_with(&yaml1["script"], &yaml2["script"], &coerce));
+    assert!(deep_equal_with(&yaml1["script"], &yaml2["script"], &whitespace));
+}
+
+#[test]
+fn test_deep_equal_with_empty_maps_absent() {
+    let policy = EqualityPolicy { empty_map_is_absent: true, ..EqualityPolicy::default() };
+    let yaml1 = YamlLoader::load_from_str("a: 1\nannotations: {}").unwrap().into_iter().next().unwrap();
+    let yaml2 = YamlLoader::load_from_str("a: 1").unwrap().into_iter().next().unwrap();
+
+    assert!(!deep_equal(&yaml1, &yaml2));
+    assert!(deep_equal_with(&yaml1, &yaml2, &policy));
+    assert!(deep_equal_with(&yaml2, &yaml1, &policy));
 }
\ No newline at end of file
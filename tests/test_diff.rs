@@ -1,6 +1,9 @@
-use yabe::diff::{compute_diff, diff_and_common_multiple};
-use yabe::deep_equal::deep_equal;
-use yaml_rust2::YamlLoader;
+use yabe::diff::{
+    compute_diff, compute_diff_with, diff_and_common_multiple, diff_and_common_multiple_with, DiffOptions, HoistRules,
+};
+use yabe::deep_equal::{deep_equal, EqualityPolicy};
+use yabe::merge::{merge_yaml_with, MergeOptions, NullMode};
+use yaml_rust2::{Yaml, YamlLoader};
 
 #[test]
 fn test_compute_diff_identical() {
@@ -196,4 +199,193 @@ fn test_arrays_with_base() {
             None => assert!(diff.is_none()),
         }
     }
+}
+
+fn strategy_options() -> DiffOptions {
+    let rules = YamlLoader::load_from_str(
+        "- {path: args, strategy: append}\n- {path: tags, strategy: union}\n- {path: containers, strategy: mergeByKey, key: name}",
+    )
+    .unwrap()
+    .into_iter()
+    .next()
+    .unwrap();
+    DiffOptions { merge: MergeOptions::from_yaml(&rules), ..DiffOptions::default() }
+}
+
+#[test]
+fn test_compute_diff_with_merge_strategies() {
+    let options = strategy_options();
+    let helm_yaml = YamlLoader::load_from_str("args: [a]\ntags: [x, y]\ncontainers:\n  - {name: app, image: app:1, port: 80}").unwrap().into_iter().next().unwrap();
+    let obj_yaml = YamlLoader::load_from_str("args: [a, b]\ntags: [y, x, z]\ncontainers:\n  - {name: app, image: app:2, port: 80}\n  - {name: extra}").unwrap().into_iter().next().unwrap();
+
+    let diff = compute_diff_with(&obj_yaml, &helm_yaml, &options).unwrap();
+    let expected_diff = YamlLoader::load_from_str("args: [b]\ntags: [z]\ncontainers:\n  - {name: app, image: app:2}\n  - {name: extra}").unwrap().into_iter().next().unwrap();
+
+    assert!(deep_equal(&diff, &expected_diff));
+    let merged = merge_yaml_with(&helm_yaml, &diff, &options.merge);
+    assert!(deep_equal(&merged["args"], &obj_yaml["args"]));
+    assert!(deep_equal(&merged["containers"], &obj_yaml["containers"]));
+}
+
+#[test]
+fn test_compute_diff_with_unreachable_list() {
+    let options = strategy_options();
+    let helm_yaml = YamlLoader::load_from_str("args: [a, b]").unwrap().into_iter().next().unwrap();
+    let obj_yaml = YamlLoader::load_from_str("args: [b]").unwrap().into_iter().next().unwrap();
+
+    let diff = compute_diff_with(&obj_yaml, &helm_yaml, &options).unwrap();
+
+    assert!(deep_equal(&diff, &obj_yaml));
+}
+
+#[test]
+fn test_diff_and_common_multiple_with_merge_strategies() {
+    let options = strategy_options();
+    let yaml1 = YamlLoader::load_from_str("args: [a, b, c]\ntags: [x, y]\ncontainers:\n  - {name: app, image: app:1}\n  - {name: one}").unwrap().into_iter().next().unwrap();
+    let yaml2 = YamlLoader::load_from_str("args: [a, b]\ntags: [y, z]\ncontainers:\n  - {name: app, image: app:1, port: 80}").unwrap().into_iter().next().unwrap();
+
+    let objs = vec![&yaml1, &yaml2];
+    let (base, diffs) = diff_and_common_multiple_with(&objs, 1.0, &options);
+
+    let expected_base = YamlLoader::load_from_str("args: [a, b]\ntags: [y]\ncontainers:\n  - {name: app, image: app:1}").unwrap().into_iter().next().unwrap();
+    assert!(deep_equal(base.as_ref().unwrap(), &expected_base));
+    for (obj, diff) in objs.iter().zip(&diffs) {
+        let merged = merge_yaml_with(&expected_base, diff.as_ref().unwrap(), &options.merge);
+        assert!(deep_equal(&merged["args"], &obj["args"]));
+        assert!(deep_equal(&merged["containers"], &obj["containers"]));
+        // Union lists keep their items, not their order
+        let mut tags = merged["tags"].as_vec().unwrap().clone();
+        tags.sort();
+        assert!(deep_equal(&Yaml::Array(tags), &obj["tags"]));
+    }
+}
+
+#[test]
+fn test_array_items_hoisted_by_quorum() {
+    let options = strategy_options();
+    let yaml1 = YamlLoader::load_from_str("args: [a, b, x]\ntags: [x, y]\ncontainers:\n  - {name: app, image: app:1}\n  - {name: one}").unwrap().into_iter().next().unwrap();
+    let yaml2 = YamlLoader::load_from_str("args: [a, b]\ntags: [y, x, z]\ncontainers:\n  - {name: app, image: app:1, port: 80}").unwrap().into_iter().next().unwrap();
+    let yaml3 = YamlLoader::load_from_str("args: [c]\ntags: [q]\ncontainers:\n  - {name: other}").unwrap().into_iter().next().unwrap();
+
+    let objs = vec![&yaml1, &yaml2, &yaml3];
+    let (base, diffs) = diff_and_common_multiple_with(&objs, 0.6, &options);
+
+    let expected_base = YamlLoader::load_from_str("args: [a, b]\ntags: [x, y]\ncontainers:\n  - {name: app, image: app:1}").unwrap().into_iter().next().unwrap();
+    assert!(deep_equal(base.as_ref().unwrap(), &expected_base));
+    for (obj, diff) in objs.iter().zip(&diffs).take(2) {
+        let merged = merge_yaml_with(&expected_base, diff.as_ref().unwrap(), &options.merge);
+        assert!(deep_equal(&merged["args"], &obj["args"]));
+        assert!(deep_equal(&merged["containers"], &obj["containers"]));
+    }
+    // The lists of the third file lack the common items, so they are written whole
+    assert!(deep_equal(diffs[2].as_ref().unwrap(), &yaml3));
+}
+
+#[test]
+fn test_array_items_with_duplicate_keys_or_another_order() {
+    let options = strategy_options();
+    let yaml1 = YamlLoader::load_from_str("containers:\n  - {name: a, v: 1}\n  - {name: b, v: 2}").unwrap().into_iter().next().unwrap();
+    let yaml2 = YamlLoader::load_from_str("containers:\n  - {name: a, v: 1}\n  - {name: b, v: 2}\n  - {name: a, v: 3}").unwrap().into_iter().next().unwrap();
+    let yaml3 = YamlLoader::load_from_str("containers:\n  - {name: b, v: 2}\n  - {name: a, v: 1}").unwrap().into_iter().next().unwrap();
+
+    let objs = vec![&yaml1, &yaml2, &yaml3];
+    let (base, diffs) = diff_and_common_multiple_with(&objs, 1.0, &options);
+
+    assert!(deep_equal(base.as_ref().unwrap(), &yaml1));
+    assert!(diffs[0].is_none());
+    // The second item named a is not dropped, and the other order is kept
+    assert!(deep_equal(diffs[1].as_ref().unwrap(), &yaml2));
+    assert!(deep_equal(diffs[2].as_ref().unwrap(), &yaml3));
+}
+
+#[test]
+fn test_compute_diff_with_null_deletion() {
+    let options = DiffOptions { merge: MergeOptions::default().with_null_mode(NullMode::Delete), ..DiffOptions::default() };
+    let helm_yaml = YamlLoader::load_from_str("a: 1\nb:\n  c: 1\n  d: 2\ne: ~").unwrap().into_iter().next().unwrap();
+    let obj_yaml = YamlLoader::load_from_str("b:\n  c: 1\nf: 3").unwrap().into_iter().next().unwrap();
+
+    let diff = compute_diff_with(&obj_yaml, &helm_yaml, &options).unwrap();
+    let expected_diff = YamlLoader::load_from_str("a: ~\nb:\n  d: ~\nf: 3").unwrap().into_iter().next().unwrap();
+
+    assert!(deep_equal(&diff, &expected_diff));
+    let effective = merge_yaml_with(&helm_yaml, &diff, &options.merge);
+    assert!(deep_equal(&effective, &YamlLoader::load_from_str("b:\n  c: 1\ne: ~\nf: 3").unwrap()[0]));
+}
+
+#[test]
+fn test_diff_and_common_multiple_with_null_deletion() {
+    let options = DiffOptions { merge: MergeOptions::default().with_null_mode(NullMode::Delete), ..DiffOptions::default() };
+    let yaml1 = YamlLoader::load_from_str("a: 1\nb: 2").unwrap().into_iter().next().unwrap();
+    let yaml2 = YamlLoader::load_from_str("a: 1\nb: ~").unwrap().into_iter().next().unwrap();
+
+    let objs = vec![&yaml1, &yaml2];
+    let (base, diffs) = diff_and_common_multiple_with(&objs, 1.0, &options);
+
+    let expected_base = YamlLoader::load_from_str("a: 1").unwrap().into_iter().next().unwrap();
+    assert!(deep_equal(&base.unwrap(), &expected_base));
+    assert!(deep_equal(diffs[0].as_ref().unwrap(), &YamlLoader::load_from_str("b: 2").unwrap()[0]));
+    assert!(deep_equal(diffs[1].as_ref().unwrap(), &YamlLoader::load_from_str("b: ~").unwrap()[0]));
+
+    // A key missing from one file is not a deletion in it
+    let yaml3 = YamlLoader::load_from_str("a: 1").unwrap().into_iter().next().unwrap();
+    let objs = vec![&yaml3, &yaml2];
+    let (base, diffs) = diff_and_common_multiple_with(&objs, 1.0, &options);
+    assert!(deep_equal(&base.unwrap(), &expected_base));
+    assert!(diffs[0].is_none());
+    assert!(deep_equal(diffs[1].as_ref().unwrap(), &YamlLoader::load_from_str("b: ~").unwrap()[0]));
+}
+
+#[test]
+fn test_diff_with_equality_policy() {
+    let equality = EqualityPolicy { numeric: true, coerce_strings: true, empty_map_is_absent: true, ..EqualityPolicy::default() };
+    let options = DiffOptions { equality, ..DiffOptions::default() };
+    let helm_yaml = YamlLoader::load_from_str("cpu: 1.0\nport: 80\nname: web").unwrap().into_iter().next().unwrap();
+    let obj_yaml = YamlLoader::load_from_str("cpu: 1\nport: '80'\nname: api\nlabels: {}").unwrap().into_iter().next().unwrap();
+
+    let diff = compute_diff_with(&obj_yaml, &helm_yaml, &options).unwrap();
+    assert!(deep_equal(&diff, &YamlLoader::load_from_str("name: api").unwrap()[0]));
+
+    let yaml1 = YamlLoader::load_from_str("replicas: 2\nextra: {}").unwrap().into_iter().next().unwrap();
+    let yaml2 = YamlLoader::load_from_str("replicas: 2.0").unwrap().into_iter().next().unwrap();
+    let objs = vec![&yaml1, &yaml2];
+    let (base, diffs) = diff_and_common_multiple_with(&objs, 1.0, &options);
+    assert!(deep_equal(&base.unwrap(), &YamlLoader::load_from_str("replicas: 2").unwrap()[0]));
+    assert!(diffs.iter().all(Option::is_none));
+}
+
+#[test]
+fn test_keep_local_and_force_shared() {
+    let rules = YamlLoader::load_from_str("keepLocal: [image.tag]\nforceShared: [global]").unwrap().into_iter().next().unwrap();
+    let options = DiffOptions { hoist: HoistRules::from_yaml(&rules), ..DiffOptions::default() };
+    let yaml1 = YamlLoader::load_from_str("image: {repository: web, tag: v1}\nglobal: {region: eu, tier: a}").unwrap().into_iter().next().unwrap();
+    let yaml2 = YamlLoader::load_from_str("image: {repository: web, tag: v1}\nglobal: {region: us, tier: a}").unwrap().into_iter().next().unwrap();
+    let yaml3 = YamlLoader::load_from_str("image: {repository: web, tag: v1}\nglobal: {region: ap, tier: b}").unwrap().into_iter().next().unwrap();
+    let objs = vec![&yaml1, &yaml2, &yaml3];
+
+    let (base, diffs) = diff_and_common_multiple_with(&objs, 1.0, &options);
+
+    // The tag stays in every file; the first of the tied regions and the most common tier are hoisted
+    let expected_base = YamlLoader::load_from_str("image: {repository: web}\nglobal: {region: eu, tier: a}").unwrap();
+    assert!(deep_equal(&base.unwrap(), &expected_base[0]));
+    let expected_diffs = [
+        "image: {tag: v1}",
+        "image: {tag: v1}\nglobal: {region: us}",
+        "image: {tag: v1}\nglobal: {region: ap, tier: b}",
+    ];
+    for (diff, expected) in diffs.iter().zip(expected_diffs) {
+        assert!(deep_equal(diff.as_ref().unwrap(), &YamlLoader::load_from_str(expected).unwrap()[0]));
+    }
+}
+
+#[test]
+fn test_force_shared_needs_every_file_to_set_the_value() {
+    let rules = YamlLoader::load_from_str("forceShared: [debug]").unwrap().into_iter().next().unwrap();
+    let options = DiffOptions { hoist: HoistRules::from_yaml(&rules), ..DiffOptions::default() };
+    let yaml1 = YamlLoader::load_from_str("debug: true").unwrap().into_iter().next().unwrap();
+    let yaml2 = YamlLoader::load_from_str("name: b").unwrap().into_iter().next().unwrap();
+    let objs = vec![&yaml1, &yaml2];
+
+    // Hoisting the value would set it for the file without it
+    let (base, _) = diff_and_common_multiple_with(&objs, 1.0, &options);
+    assert!(base.is_none());
 }
\ No newline at end of file
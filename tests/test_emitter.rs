@@ -0,0 +1,118 @@
+use yabe::emitter::{emit_documents, emit_yaml, emit_yaml_with, EmitterConfig, QuoteStyle};
+use yabe::sorter::sort_yaml;
+use std::fs;
+use yaml_rust2::{Yaml, YamlEmitter, YamlLoader};
+
+fn format(content: &str, config: &Yaml) -> String {
+    let docs: Vec<Yaml> = YamlLoader::load_from_str(content)
+        .unwrap()
+        .iter()
+        .map(|doc| sort_yaml(doc, config).into_owned())
+        .collect();
+    emit_documents(&docs, &EmitterConfig::default()).unwrap()
+}
+
+#[test]
+fn test_emit_yaml() {
+    let doc = YamlLoader::load_from_str("b: 1\na:\n  - x").unwrap().into_iter().next().unwrap();
+    assert_eq!(emit_yaml(&doc).unwrap(), "b: 1\na:\n  - x\n");
+}
+
+#[test]
+fn test_emit_documents() {
+    let docs = YamlLoader::load_from_str("a: 1\n---\nb: 2").unwrap();
+    assert_eq!(emit_documents(&docs, &EmitterConfig::default()).unwrap(), "a: 1\n---\nb: 2\n");
+    assert_eq!(emit_documents(&[], &EmitterConfig::default()).unwrap(), "");
+}
+
+#[test]
+fn test_format_is_idempotent() {
+    let config = YamlLoader::load_from_str(&fs::read_to_string("sort-config.yaml").unwrap())
+        .unwrap()
+        .into_iter()
+        .next()
+        .unwrap();
+    let content = "spec:\n  template: {spec: {containers: [{name: b, image: 'x:1'}, {name: a}]}}\nkind: Deployment\nmultiline: \"a\\nb\"\n---\nz: 1\na: true\n";
+
+    let once = format(content, &config);
+    let twice = format(&once, &config);
+
+    assert_eq!(once, twice);
+}
+
+
+#[test]
+fn test_default_matches_yaml_rust2() {
+    let content = fs::read_to_string("examples/full/in/a.yaml").unwrap()
+        + "\nextra:\n  - - a\n    - b\n  - {}\n  - []\n  - k: v\n    l: [1, 2.5, ~]\nquoted: ['yes', '1', 'a: b', \"x\\ny\"]\n";
+    let doc = YamlLoader::load_from_str(&content).unwrap().into_iter().next().unwrap();
+
+    let mut expected = String::new();
+    YamlEmitter::new(&mut expected).dump(&doc).unwrap();
+
+    assert_eq!(emit_yaml(&doc).unwrap(), format!("{}\n", expected.trim_start_matches("---\n")));
+}
+
+#[test]
+fn test_emitter_options() {
+    let doc = YamlLoader::load_from_str(
+        "a:\n  list: [x, y]\n  long: [aaaa, bbbb, cccc, dddd]\n  text: 'yes'\n  items:\n    - name: n\n      value: v",
+    )
+    .unwrap()
+    .into_iter()
+    .next()
+    .unwrap();
+    let config = EmitterConfig {
+        indent: 4,
+        indent_sequences: false,
+        quote_style: QuoteStyle::Single,
+        flow_sequence_width: Some(10),
+        explicit_start: true,
+        ..EmitterConfig::default()
+    };
+
+    let out = emit_yaml_with(&doc, &config).unwrap();
+
+    assert_eq!(
+        out,
+        "---\na:\n    list: [x, y]\n    long:\n    - aaaa\n    - bbbb\n    - cccc\n    - dddd\n    text: 'yes'\n    items:\n    - name: n\n      value: v\n"
+    );
+    assert_eq!(YamlLoader::load_from_str(&out).unwrap()[0], doc);
+}
+
+#[test]
+fn test_indent_applies_to_hashes_not_sequence_items() {
+    let doc = YamlLoader::load_from_str("a:\n  items:\n    - name: n\n      value: v\n    - [x, y]\n  b: 1")
+        .unwrap()
+        .into_iter()
+        .next()
+        .unwrap();
+    let config = EmitterConfig {
+        indent: 4,
+        ..EmitterConfig::default()
+    };
+
+    let out = emit_yaml_with(&doc, &config).unwrap();
+
+    assert_eq!(
+        out,
+        "a:\n    items:\n        - name: n\n          value: v\n        - - x\n          - y\n    b: 1\n"
+    );
+    assert_eq!(YamlLoader::load_from_str(&out).unwrap()[0], doc);
+}
+
+#[test]
+fn test_line_width_folding() {
+    let text = "a fairly long sentence that does not fit into forty columns at all";
+    let doc = YamlLoader::load_from_str(&format!("key:\n  nested: {}", text)).unwrap().into_iter().next().unwrap();
+    let config = EmitterConfig {
+        line_width: Some(40),
+        ..EmitterConfig::default()
+    };
+
+    let out = emit_yaml_with(&doc, &config).unwrap();
+
+    assert!(out.lines().all(|line| line.len() <= 40), "{}", out);
+    assert!(out.contains(">-"));
+    assert_eq!(YamlLoader::load_from_str(&out).unwrap()[0], doc);
+}
\ No newline at end of file
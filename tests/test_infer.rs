@@ -0,0 +1,84 @@
+use yabe::infer::infer_sort_config;
+use yabe::sorter::sort_yaml;
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(s: &str) -> Yaml {
+    YamlLoader::load_from_str(s).unwrap().into_iter().next().unwrap()
+}
+
+#[test]
+fn test_infer_reproduces_dominant_order() {
+    let yaml1 = load("name: a\nkind: Service\napiVersion: v1\nspec:\n  type: ClusterIP\n  port: 80");
+    let yaml2 = load("name: b\nkind: Deployment\napiVersion: apps/v1\nspec:\n  replicas: 2");
+    let docs = vec![&yaml1, &yaml2];
+
+    let config = infer_sort_config(&docs);
+
+    assert!(config["preOrder"].is_array());
+    for doc in docs {
+        assert_eq!(sort_yaml(doc, &config).into_owned(), *doc);
+    }
+}
+
+#[test]
+fn test_infer_global_pre_order() {
+    let yaml1 = load("name: a\nkind: Service\napiVersion: v1");
+    let yaml2 = load("nested:\n  name: b\n  kind: Deployment\n  apiVersion: apps/v1");
+    let docs = vec![&yaml1, &yaml2];
+
+    let config = infer_sort_config(&docs);
+
+    let expected = load("- name\n- kind");
+    assert_eq!(config["preOrder"], expected);
+    assert!(config["rules"].is_badvalue());
+}
+
+#[test]
+fn test_infer_path_scoped_rule() {
+    let yaml1 = load("tag: v1\nalpha: 2\nimage:\n  repository: nginx\n  tag: v1");
+    let yaml2 = load("tag: v2\nalpha: 4\nimage:\n  repository: redis\n  tag: v2");
+    let docs = vec![&yaml1, &yaml2];
+
+    let config = infer_sort_config(&docs);
+
+    for doc in &docs {
+        assert_eq!(sort_yaml(doc, &config).into_owned(), **doc);
+    }
+    let rules = config["rules"].as_vec().unwrap();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0]["path"].as_str(), Some("image"));
+    assert_eq!(rules[0]["preOrder"], Yaml::Array(vec![]));
+}
+
+#[test]
+fn test_infer_sort_key() {
+    let yaml1 = load("env:\n  - name: A\n    value: z\n  - name: B\n    value: y");
+    let yaml2 = load("env:\n  - name: C\n    value: x\n  - name: D\n    value: w");
+    let docs = vec![&yaml1, &yaml2];
+
+    let config = infer_sort_config(&docs);
+
+    assert_eq!(config["sortKey"].as_str(), Some("name"));
+    for doc in docs {
+        assert_eq!(sort_yaml(doc, &config).into_owned(), *doc);
+    }
+}
+
+#[test]
+fn test_infer_keep_order_rule() {
+    let yaml1 = load("env:\n  - name: A\n  - name: B\nhosts:\n  - name: b\n  - name: a");
+    let yaml2 = load("env:\n  - name: C\n  - name: D\nvolumes:\n  - name: config\n  - name: data");
+    let docs = vec![&yaml1, &yaml2];
+
+    let config = infer_sort_config(&docs);
+
+    assert_eq!(config["sortKey"].as_str(), Some("name"));
+    let rules = config["rules"].as_vec().unwrap();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0]["path"].as_str(), Some("hosts"));
+    assert_eq!(rules[0]["keepOrder"].as_bool(), Some(true));
+    assert!(rules[0]["sortKey"].is_badvalue());
+    for doc in docs {
+        assert_eq!(sort_yaml(doc, &config).into_owned(), *doc);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,22 @@
+use yabe::json::to_json;
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+#[test]
+fn test_to_json() {
+    let doc = load("a: 1\nb: [true, ~, 1.5e3]\nc: {}\nd: []\n1: x");
+
+    let expected = "{\n  \"a\": 1,\n  \"b\": [\n    true,\n    null,\n    1500\n  ],\n  \"c\": {},\n  \"d\": [],\n  \"1\": \"x\"\n}\n";
+    assert_eq!(to_json(&doc), expected);
+}
+
+#[test]
+fn test_to_json_escapes_strings() {
+    let doc = load("s: \"quote \\\" back \\\\ tab \\t nl \\n bell \\a é\"");
+
+    assert_eq!(to_json(&doc), "{\n  \"s\": \"quote \\\" back \\\\ tab \\t nl \\n bell \\u0007 é\"\n}\n");
+    assert_eq!(to_json(&load(".inf")), "null\n");
+}
\ No newline at end of file
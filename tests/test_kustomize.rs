@@ -0,0 +1,101 @@
+use yabe::kustomize::{base_files, overlay_files, overlays, PatchFormat};
+use yabe::loader::load_documents;
+use yabe::manifest::bundle;
+use yabe::merge::MergeOptions;
+use yaml_rust2::Yaml;
+
+fn load_bundle(content: &str) -> Yaml {
+    bundle(load_documents(content).unwrap()).yaml
+}
+
+const BASE: &str = "apiVersion: apps/v1
+kind: Deployment
+metadata: {name: web, namespace: shop}
+spec: {replicas: 1}
+---
+apiVersion: v1
+kind: Service
+metadata: {name: web, namespace: shop}
+spec: {ports: [{port: 80}]}";
+
+const DIFF: &str = "apiVersion: apps/v1
+kind: Deployment
+metadata: {name: web, namespace: shop}
+spec: {replicas: 3}
+---
+apiVersion: v1
+kind: ConfigMap
+metadata: {name: extra}
+data: {a: b}";
+
+fn strings(value: &Yaml) -> Vec<&str> {
+    value.as_vec().unwrap().iter().map(|v| v.as_str().unwrap()).collect()
+}
+
+#[test]
+fn test_base_has_a_file_per_resource() {
+    let base = load_bundle(BASE);
+
+    let files = base_files(Some(&base));
+
+    let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        ["base/shop-deployment-web.yaml", "base/shop-service-web.yaml", "base/kustomization.yaml"]
+    );
+    assert_eq!(files[0].doc["spec"]["replicas"].as_i64(), Some(1));
+    let kustomization = &files[2];
+    assert!(kustomization.is_kustomization);
+    assert_eq!(strings(&kustomization.doc["resources"]), ["shop-deployment-web.yaml", "shop-service-web.yaml"]);
+}
+
+#[test]
+fn test_overlay_patches_base_resources_and_adds_new_ones() {
+    let (base, diff) = (load_bundle(BASE), load_bundle(DIFF));
+
+    let files = overlay_files("prod", Some(&diff), Some(&base), PatchFormat::Strategic, &MergeOptions::default());
+
+    assert_eq!(files.len(), 3);
+    assert_eq!(files[0].path, "overlays/prod/shop-deployment-web.yaml");
+    assert_eq!(files[0].doc["spec"]["replicas"].as_i64(), Some(3));
+    assert_eq!(files[0].doc["metadata"]["name"].as_str(), Some("web"));
+    let kustomization = &files[2].doc;
+    assert_eq!(strings(&kustomization["resources"]), ["../../base", "configmap-extra.yaml"]);
+    assert_eq!(kustomization["patches"][0]["path"].as_str(), Some("shop-deployment-web.yaml"));
+    assert!(kustomization["patches"][0]["target"].is_badvalue());
+}
+
+#[test]
+fn test_json6902_patches_select_their_target() {
+    let (base, diff) = (load_bundle(BASE), load_bundle(DIFF));
+
+    let files = overlay_files("prod", Some(&diff), Some(&base), PatchFormat::Json6902, &MergeOptions::default());
+
+    let operations = files[0].doc.as_vec().unwrap();
+    assert_eq!(operations.len(), 1);
+    assert_eq!(operations[0]["op"].as_str(), Some("replace"));
+    assert_eq!(operations[0]["path"].as_str(), Some("/spec/replicas"));
+    let target = &files[2].doc["patches"][0]["target"];
+    assert_eq!(target["group"].as_str(), Some("apps"));
+    assert_eq!(target["version"].as_str(), Some("v1"));
+    assert_eq!(target["kind"].as_str(), Some("Deployment"));
+    assert_eq!(target["namespace"].as_str(), Some("shop"));
+
+    // An overlay without a diff only refers to the base
+    let files = overlay_files("dev", None, Some(&base), PatchFormat::Json6902, &MergeOptions::default());
+    assert_eq!(files.len(), 1);
+    assert_eq!(strings(&files[0].doc["resources"]), ["../../base"]);
+}
+#[test]
+fn test_overlays_are_named_after_distinct_input_files() {
+    let (base, diff) = (load_bundle(BASE), load_bundle(DIFF));
+    let diffs = [Some(diff), None];
+    let options = MergeOptions::default();
+
+    let files = overlays(&["eu/prod.yaml", "eu/dev.yaml"], &diffs, Some(&base), PatchFormat::Strategic, &options).unwrap();
+    assert_eq!(files.first().unwrap().path, "overlays/prod/shop-deployment-web.yaml");
+    assert_eq!(files.last().unwrap().path, "overlays/dev/kustomization.yaml");
+
+    // Both would be written to overlays/prod/
+    assert!(overlays(&["eu/prod.yaml", "us/prod.yaml"], &diffs, Some(&base), PatchFormat::Strategic, &options).is_err());
+}
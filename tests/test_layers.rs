@@ -0,0 +1,90 @@
+use yabe::deep_equal::deep_equal;
+use yabe::diff::DiffOptions;
+use yabe::layers::{extract_into_layer, merge_layers};
+use yabe::merge::MergeOptions;
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(s: &str) -> Yaml {
+    YamlLoader::load_from_str(s).unwrap().into_iter().next().unwrap()
+}
+
+/// Merges the stack with each diff on top and compares the result with the original effective values.
+fn assert_reproduces(layers: &[&Yaml], inputs: &[&Yaml], new_layers: &[&Yaml], diffs: &[Option<Yaml>]) {
+    let options = MergeOptions::default();
+    for (input, diff) in inputs.iter().zip(diffs) {
+        let mut before: Vec<&Yaml> = layers.to_vec();
+        before.push(input);
+        let mut after: Vec<&Yaml> = new_layers.to_vec();
+        after.extend(diff.as_ref());
+        let before = merge_layers(&before, &options).unwrap();
+        let after = merge_layers(&after, &options).unwrap();
+        assert!(deep_equal(&before, &after), "{:?} != {:?}", before, after);
+    }
+}
+
+#[test]
+fn test_merge_layers() {
+    let chart = load("a: 1\nb: {c: 1}");
+    let org = load("b: {d: 2}");
+    let env = load("a: 3");
+
+    let merged = merge_layers(&[&chart, &org, &env], &MergeOptions::default()).unwrap();
+
+    assert!(deep_equal(&merged, &load("a: 3\nb: {c: 1, d: 2}")));
+    assert!(merge_layers(&[], &MergeOptions::default()).is_none());
+}
+
+#[test]
+fn test_extract_into_middle_layer() {
+    let chart = load("replicas: 1\nimage: app");
+    let org = load("replicas: 2\nteam: x");
+    let region = load("region: eu");
+    let a = load("env: dev\ncpu: 1\nregion: us");
+    let b = load("env: prod\ncpu: 1\nregion: us");
+    let layers = [&chart, &org, &region];
+
+    let (layer, diffs) = extract_into_layer(&layers, 1, &[&a, &b], 1.0, &DiffOptions::default());
+
+    // `region` is shadowed by the region layer, so it stays in the diffs
+    let layer = layer.unwrap();
+    assert!(deep_equal(&layer, &load("replicas: 2\nteam: x\ncpu: 1")));
+    assert!(deep_equal(diffs[0].as_ref().unwrap(), &load("env: dev\nregion: us")));
+    assert_reproduces(&layers, &[&a, &b], &[&chart, &layer, &region], &diffs);
+}
+
+#[test]
+fn test_extract_into_new_top_layer() {
+    let chart = load("a: 1\nb: 2");
+    let empty = load("{}");
+    let x = load("a: 1\nb: 3\nc: 4");
+    let y = load("b: 3\nc: 5");
+    let z = load("b: 3\nc: 4");
+    let layers = [&chart, &empty];
+
+    let (layer, diffs) = extract_into_layer(&layers, 1, &[&x, &y, &z], 0.6, &DiffOptions::default());
+
+    let layer = layer.unwrap();
+    assert!(deep_equal(&layer, &load("b: 3\nc: 4")));
+    assert!(diffs[0].is_none());
+    assert!(deep_equal(diffs[1].as_ref().unwrap(), &load("c: 5")));
+    assert_reproduces(&layers, &[&x, &y, &z], &[&chart, &layer], &diffs);
+}
+
+#[test]
+fn test_extraction_does_not_depend_on_threads() {
+    let chart = load("a: 1\nb: 2");
+    let empty = load("{}");
+    let inputs: Vec<Yaml> = (0..64).map(|i| load(&format!("a: {}\nb: 3\nc: {}\nd: [x, {}]", i % 3, i % 2, i))).collect();
+    let input_refs: Vec<&Yaml> = inputs.iter().collect();
+    let layers = [&chart, &empty];
+
+    let extract = |threads: usize| {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| extract_into_layer(&layers, 1, &input_refs, 0.5, &DiffOptions::default()))
+    };
+    let (single_layer, single_diffs) = extract(1);
+    let (layer, diffs) = extract(4);
+
+    assert_eq!(layer, single_layer);
+    assert_eq!(diffs, single_diffs);
+}
\ No newline at end of file
@@ -0,0 +1,97 @@
+use yabe::diff::compute_diff;
+use yabe::emitter::{emit_yaml_preserving, EmitterConfig};
+use yabe::loader::{has_comments, load_document, load_documents, ScalarStyle};
+use yabe::path::YamlPath;
+use yaml_rust2::{Yaml, YamlLoader};
+
+const CONTENT: &str = "mode: 0755
+mask: 0x1F
+version: '1.0'
+name: \"web\"
+ports: [0x50, 443]
+script: |
+  set -e
+  echo hi
+folded: >-
+  first line continued
+
+  second paragraph
+keep: |+
+  a
+
+";
+
+#[test]
+fn test_load_matches_yaml_loader() {
+    let content = "a: &x {b: 1}\nc: *x\nd: !!str 12\ne: [1, \"2\"]\n---\n---\nf: ~\n";
+    let docs: Vec<Yaml> = load_documents(content).unwrap().into_iter().map(|doc| doc.yaml).collect();
+    assert_eq!(docs, YamlLoader::load_from_str(content).unwrap());
+    assert!(load_documents("a: 1\na: 2").is_err());
+}
+
+#[test]
+fn test_load_records_presentation() {
+    let doc = load_document(CONTENT).unwrap().unwrap();
+    let path = |p: &str| -> YamlPath {
+        let mut path = YamlPath::root();
+        for key in p.split('.') {
+            path.push(yabe::path::PathSegment::Key(key.to_string()));
+        }
+        path
+    };
+
+    let mode = doc.source.lookup(&path("mode"), &Yaml::Integer(755)).unwrap();
+    assert_eq!((mode.style, mode.raw.as_str(), mode.line), (ScalarStyle::Plain, "0755", 1));
+    assert_eq!(doc.source.get(&path("version"))[0].style, ScalarStyle::SingleQuoted);
+    assert_eq!(doc.source.get(&path("script"))[0].style, ScalarStyle::Literal);
+    assert_eq!(doc.source.get(&path("ports").child_index(0))[0].raw, "0x50");
+    assert!(doc.source.lookup(&path("mode"), &Yaml::Integer(1)).is_none());
+}
+
+#[test]
+fn test_emit_preserves_unchanged_scalars() {
+    let doc = load_document(CONTENT).unwrap().unwrap();
+    let config = EmitterConfig { flow_sequence_width: Some(80), ..EmitterConfig::default() };
+
+    let out = emit_yaml_preserving(&doc.yaml, &config, &doc.source).unwrap();
+
+    assert_eq!(out, CONTENT);
+    assert_eq!(YamlLoader::load_from_str(&out).unwrap()[0], doc.yaml);
+}
+
+#[test]
+fn test_emit_preserves_diff_values() {
+    let input = load_document("mode: 0644\nkeep: '1'\nscript: |\n  echo hi\n").unwrap().unwrap();
+    let base = YamlLoader::load_from_str("mode: 0600\nkeep: '1'\nscript: echo\n").unwrap().remove(0);
+
+    let diff = compute_diff(&input.yaml, &base).unwrap();
+    let out = emit_yaml_preserving(&diff, &EmitterConfig::default(), &input.source).unwrap();
+
+    assert_eq!(out, "mode: 0644\nscript: |\n  echo hi\n");
+}
+
+#[test]
+fn test_emit_falls_back_for_changed_values() {
+    let source = load_document("mode: 0755\nname: 'a'\nscript: |\n  a\n").unwrap().unwrap().source;
+    let doc = YamlLoader::load_from_str("mode: 8\nname: b\nscript: \"  b\\n\"").unwrap().remove(0);
+
+    let out = emit_yaml_preserving(&doc, &EmitterConfig::default(), &source).unwrap();
+
+    assert_eq!(out, "mode: 8\nname: b\nscript: \"  b\\n\"\n");
+}
+
+#[test]
+fn test_has_comments() {
+    assert!(has_comments("# top comment\nimage:\n  tag: v1\n"));
+    assert!(has_comments("image:\n  tag: v1 # the tag\n"));
+    assert!(has_comments("a: 1\n  # trailing\n"));
+
+    assert!(!has_comments("image:\n  tag: v1\n"));
+    assert!(!has_comments("url: http://example.com/#anchor\ncolor: '#fff'\nnote: \"a # b\"\n"));
+    assert!(!has_comments("script: |\n  # not a comment\n  echo hi\n"));
+    assert!(!has_comments("a: \"x\n  # y\"\n"));
+    assert!(!has_comments("items:\n  - name: a\n    script: |\n      # step\n\n      run\n  - |\n    # entry\n"));
+    assert!(has_comments("script: | # how to run\n  run\n"));
+    assert!(has_comments("- script: |\n    run\n  # after the script\n  name: a\n"));
+    assert!(has_comments("a: 'it''s' # quoted\n"));
+}
\ No newline at end of file
@@ -0,0 +1,58 @@
+use yabe::loader::load_documents;
+use yabe::manifest::{bundle, restore_identity, unbundle, ResourceId};
+use yaml_rust2::{Yaml, YamlLoader};
+
+const STREAM: &str = "apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+  namespace: prod
+spec:
+  replicas: 2
+---
+apiVersion: v1
+kind: Service
+metadata:
+  name: web
+---
+data: {}
+";
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+#[test]
+fn test_resource_id() {
+    let docs = YamlLoader::load_from_str(STREAM).unwrap();
+
+    assert_eq!(ResourceId::of(&docs[0]).unwrap().to_string(), "apps/Deployment/prod/web");
+    assert_eq!(ResourceId::of(&docs[1]).unwrap().to_string(), "Service/web");
+    assert!(ResourceId::of(&docs[2]).is_none());
+}
+
+#[test]
+fn test_bundle_and_unbundle() {
+    let bundled = bundle(load_documents(STREAM).unwrap());
+
+    let ids: Vec<&str> = bundled.yaml.as_hash().unwrap().keys().map(|k| k.as_str().unwrap()).collect();
+    assert_eq!(ids, ["apps/Deployment/prod/web", "Service/web", "#3"]);
+
+    let docs = unbundle(&bundled.yaml, &bundled.source);
+    assert_eq!(docs.len(), 3);
+    assert_eq!(docs[0].1["spec"]["replicas"], Yaml::Integer(2));
+    assert!(!docs[0].2.is_empty());
+}
+
+#[test]
+fn test_restore_identity() {
+    let reference = bundle(load_documents(STREAM).unwrap()).yaml;
+    let mut diff = load("apps/Deployment/prod/web:\n  spec:\n    replicas: 3\nService/web: ~\n");
+
+    restore_identity(&mut diff, &[&reference]);
+
+    let expected = load(
+        "apps/Deployment/prod/web:\n  apiVersion: apps/v1\n  kind: Deployment\n  metadata: {name: web, namespace: prod}\n  spec:\n    replicas: 3\nService/web:\n  apiVersion: v1\n  kind: Service\n  metadata: {name: web}\n  $patch: delete\n",
+    );
+    assert_eq!(diff, expected);
+}
\ No newline at end of file
@@ -0,0 +1,71 @@
+use yabe::deep_equal::deep_equal;
+use yabe::merge::{merge_yaml, merge_yaml_with, MergeOptions, MergeStrategy, NullMode};
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(s: &str) -> Yaml {
+    YamlLoader::load_from_str(s).unwrap().into_iter().next().unwrap()
+}
+
+fn options(rules: &str) -> MergeOptions {
+    MergeOptions::from_yaml(&load(rules))
+}
+
+#[test]
+fn test_merge_replaces_arrays_by_default() {
+    let base = load("a: 1\nlist: [x, y]\nnested: {b: 1, c: 2}");
+    let override_yaml = load("list: [z]\nnested: {c: 3}");
+
+    let merged = merge_yaml(&base, &override_yaml);
+
+    assert!(deep_equal(&merged, &load("a: 1\nlist: [z]\nnested: {b: 1, c: 3}")));
+}
+
+#[test]
+fn test_merge_append_prepend_union() {
+    let base = load("args: [a, b]\nenv: [a, b]\ntags: [a, b]");
+    let override_yaml = load("args: [c]\nenv: [c]\ntags: [b, c]");
+    let options = options(
+        "- {path: args, strategy: append}\n- {path: env, strategy: prepend}\n- {path: tags, strategy: union}",
+    );
+
+    let merged = merge_yaml_with(&base, &override_yaml, &options);
+
+    assert!(deep_equal(&merged, &load("args: [a, b, c]\nenv: [c, a, b]\ntags: [a, b, c]")));
+}
+
+#[test]
+fn test_merge_by_key() {
+    let base = load("containers:\n  - {name: app, image: app:1, port: 80}\n  - {name: sidecar, image: proxy:1}");
+    let override_yaml = load("containers:\n  - {name: app, image: app:2}\n  - {name: extra, image: extra:1}");
+    let options = options("- {path: '**.containers', strategy: mergeByKey, key: name}");
+
+    let merged = merge_yaml_with(&base, &override_yaml, &options);
+
+    let expected = load(
+        "containers:\n  - {name: app, image: app:2, port: 80}\n  - {name: sidecar, image: proxy:1}\n  - {name: extra, image: extra:1}",
+    );
+    assert!(deep_equal(&merged, &expected));
+}
+
+#[test]
+fn test_merge_options_from_yaml() {
+    let options = options(
+        "- {path: a, strategy: mergeByKey}\n- {path: a, strategy: bogus}\n- {path: a, strategy: mergeByKey, key: id}",
+    );
+    let path = yabe::path::YamlPath::root().child_key(&Yaml::String("a".into()));
+
+    assert_eq!(*options.strategy(&path), MergeStrategy::MergeByKey("id".into()));
+    assert_eq!(*options.strategy(&yabe::path::YamlPath::root()), MergeStrategy::Replace);
+}
+
+#[test]
+fn test_merge_null_deletes_keys() {
+    let base = load("a: 1\nb: {c: 1, d: 2}\ne: ~");
+    let override_yaml = load("a: ~\nb: {c: ~}\ne: ~\nf: ~");
+
+    let kept = merge_yaml(&base, &override_yaml);
+    let deleted = merge_yaml_with(&base, &override_yaml, &MergeOptions::default().with_null_mode(NullMode::Delete));
+
+    assert!(deep_equal(&kept, &load("a: ~\nb: {c: ~, d: 2}\ne: ~\nf: ~")));
+    assert!(deep_equal(&deleted, &load("b: {d: 2}\ne: ~\nf: ~")));
+}
\ No newline at end of file
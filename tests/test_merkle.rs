@@ -0,0 +1,121 @@
+use yabe::deep_equal::{deep_equal_with, EqualityPolicy};
+use yabe::diff::{
+    compute_diff_with, compute_diffs_with, diff_and_common_multiple_unhashed, diff_and_common_multiple_with, DiffOptions,
+};
+use yabe::merkle::MerkleIndex;
+use yabe::path::YamlPath;
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+#[test]
+fn test_equal_subtrees_hash_alike() {
+    let a = load("image:\n  repository: nginx\n  tag: '1.25'\nports: [80, 443]");
+    let b = load("ports: [80, 443]\nimage:\n  tag: '1.25'\n  repository: nginx");
+    let index = MerkleIndex::new(&[&a, &b], &EqualityPolicy::default());
+
+    assert!(index.equal(&a, &b, &YamlPath::root()));
+    assert!(index.equal(&a["image"], &b["image"], &YamlPath::root()));
+    assert_eq!(index.hash(&a["ports"]), index.hash(&b["ports"]));
+}
+
+#[test]
+fn test_different_subtrees_hash_apart() {
+    let a = load("image: {tag: '1.25'}\nports: [80, 443]\nreplicas: 1");
+    let b = load("image: {tag: '1.26'}\nports: [443, 80]\nreplicas: 1.0");
+    let index = MerkleIndex::new(&[&a, &b], &EqualityPolicy::default());
+
+    assert!(!index.equal(&a["image"], &b["image"], &YamlPath::root()));
+    assert!(!index.equal(&a["ports"], &b["ports"], &YamlPath::root()));
+    assert!(!index.equal(&a["replicas"], &b["replicas"], &YamlPath::root()));
+    assert!(!index.equal(&Yaml::Null, &Yaml::String("~".into()), &YamlPath::root()));
+}
+
+#[test]
+fn test_hashes_follow_the_equality_policy() {
+    let a = load("replicas: 1\nport: 80\nnote: ' a  b '\nannotations: {}\nname: x");
+    let b = load("replicas: 1.0\nport: '80'\nnote: a b\nname: x");
+    let policy = EqualityPolicy {
+        numeric: true,
+        coerce_strings: true,
+        ignore_whitespace: true,
+        empty_map_is_absent: true,
+        quantities: false,
+    };
+    let index = MerkleIndex::new(&[&a, &b], &policy);
+
+    for key in ["replicas", "port", "note"] {
+        assert!(deep_equal_with(&a[key], &b[key], &policy));
+        assert!(index.equal(&a[key], &b[key], &YamlPath::root()), "{}", key);
+    }
+    assert!(deep_equal_with(&a, &b, &policy));
+    assert!(index.equal(&a, &b, &YamlPath::root()));
+}
+
+#[test]
+fn test_quantities_hash_by_value_at_quantity_paths() {
+    let a = load("resources:\n  limits: {cpu: 1000m, memory: 1Gi}\ncpu: 1000m");
+    let b = load("resources:\n  limits: {cpu: 1, memory: 1024Mi}\ncpu: 1");
+    let policy = EqualityPolicy { quantities: true, ..EqualityPolicy::default() };
+    let index = MerkleIndex::new(&[&a, &b], &policy);
+
+    let at = |key: &str| YamlPath::root().child_key(&Yaml::String(key.into()));
+    assert!(index.equal(&a["resources"], &b["resources"], &at("resources")));
+    assert!(!index.equal(&a["cpu"], &b["cpu"], &at("cpu")));
+}
+
+#[test]
+fn test_matching_hashes_are_confirmed_by_the_values() {
+    let a = load("image: {tag: '1.25'}\nports: [80, 443]");
+    let b = load("image: {tag: '1.26'}\nports: [80, 443]");
+    let c = load("image: {tag: '1.25'}\nports: [443]");
+    let policy = EqualityPolicy::default();
+
+    // Without hashes every pair of values collides, and only the comparison tells them apart
+    let index = MerkleIndex::unhashed(&policy);
+    assert_eq!(index.hash(&a), index.hash(&b));
+    assert!(!index.equal(&a, &b, &YamlPath::root()));
+    assert!(index.equal(&a["ports"], &b["ports"], &YamlPath::root()));
+
+    let objs = [&a, &b, &c];
+    let options = DiffOptions::default();
+    let hashed = diff_and_common_multiple_with(&objs, 0.5, &options);
+    let unhashed = diff_and_common_multiple_unhashed(&objs, 0.5, &options);
+    assert_eq!(hashed, unhashed);
+    assert_eq!(hashed.0.unwrap().into_owned(), load("image: {tag: '1.25'}\nports: [80, 443]"));
+}
+#[test]
+fn test_index_over_a_shared_index() {
+    let base = load("image: {repository: nginx, tag: '1.25'}\nreplicas: 1");
+    let a = load("image: {tag: '1.25', repository: nginx}\nreplicas: 2");
+    let b = load("image: {repository: nginx, tag: '1.26'}\nreplicas: 1");
+    let shared = MerkleIndex::new(&[&base], &EqualityPolicy::default());
+    let index_a = MerkleIndex::over(&shared, &[&a]);
+    let index_b = MerkleIndex::over(&shared, &[&b]);
+
+    assert!(index_a.equal(&a["image"], &base["image"], &YamlPath::root()));
+    assert!(!index_a.equal(&a["replicas"], &base["replicas"], &YamlPath::root()));
+    assert!(!index_b.equal(&b["image"], &base["image"], &YamlPath::root()));
+    assert_eq!(index_a.hash(&base["image"]), shared.hash(&base["image"]));
+}
+
+#[test]
+fn test_diffs_against_one_document_match_single_diffs() {
+    let base = load("image: {repository: nginx, tag: '1.25'}\nports: [80]\nreplicas: 1");
+    let docs = [
+        load("image: {repository: nginx, tag: '1.26'}\nports: [80]\nreplicas: 1"),
+        load("image: {repository: nginx, tag: '1.25'}\nports: [80, 443]\nreplicas: 3"),
+        load("image: {repository: nginx, tag: '1.25'}\nports: [80]\nreplicas: 1"),
+    ];
+    let objs: Vec<&Yaml> = docs.iter().collect();
+    let options = DiffOptions::default();
+
+    let diffs = compute_diffs_with(&objs, &base, &options);
+    assert_eq!(diffs.len(), 3);
+    for (obj, diff) in objs.iter().zip(&diffs) {
+        assert_eq!(diff, &compute_diff_with(obj, &base, &options));
+    }
+    assert!(diffs[2].is_none());
+}
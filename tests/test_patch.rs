@@ -0,0 +1,62 @@
+use yabe::patch::{json_patch, merge_patch, operations_to_yaml, PatchOperation};
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+#[test]
+fn test_merge_patch() {
+    let base = load("a: 1\nb:\n  c: 1\n  d: 2\nlist: [1, 2, 3]\nsame: x");
+    let obj = load("a: 2\nb:\n  c: 1\nlist: [1, 2]\nsame: x\nnew: {e: 1}");
+
+    let patch = merge_patch(&obj, &base).unwrap();
+
+    assert_eq!(patch, load("a: 2\nb:\n  d: ~\nlist: [1, 2]\nnew: {e: 1}"));
+    assert!(merge_patch(&obj, &obj).is_none());
+}
+
+#[test]
+fn test_json_patch() {
+    let base = load("a: 1\nb:\n  c: 1\n  d: 2\nlist: [1, 2, 3]\nsame: x");
+    let obj = load("a: 2\nb:\n  c: 1\nlist: [1, 5]\nsame: x\nnew: {e: 1}");
+
+    let operations = json_patch(&obj, &base);
+
+    assert_eq!(
+        operations,
+        vec![
+            PatchOperation::Replace { path: "/a".into(), value: Yaml::Integer(2) },
+            PatchOperation::Remove { path: "/b/d".into() },
+            PatchOperation::Replace { path: "/list/1".into(), value: Yaml::Integer(5) },
+            PatchOperation::Remove { path: "/list/2".into() },
+            PatchOperation::Add { path: "/new".into(), value: load("{e: 1}") },
+        ]
+    );
+    assert!(json_patch(&obj, &obj).is_empty());
+}
+
+#[test]
+fn test_json_patch_pointers_and_arrays() {
+    let base = load("\"a/b\": {\"~c\": 1}\nlist: [1]");
+    let obj = load("\"a/b\": {\"~c\": 2}\nlist: [1, 2, 3]");
+
+    let operations = operations_to_yaml(&json_patch(&obj, &base));
+
+    let expected = load(
+        "- {op: replace, path: /a~1b/~0c, value: 2}\n- {op: add, path: /list/1, value: 2}\n- {op: add, path: /list/2, value: 3}",
+    );
+    assert_eq!(operations, expected);
+}
+
+#[test]
+fn test_patch_replaces_root() {
+    let obj = load("[1]");
+    let base = load("a: 1");
+
+    assert_eq!(merge_patch(&obj, &base), Some(obj.clone()));
+    assert_eq!(
+        json_patch(&obj, &base),
+        vec![PatchOperation::Replace { path: "".into(), value: obj }]
+    );
+}
\ No newline at end of file
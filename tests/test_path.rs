@@ -0,0 +1,44 @@
+use yabe::path::{PathPattern, YamlPath};
+use yaml_rust2::Yaml;
+
+fn path(keys: &[&str]) -> YamlPath {
+    keys.iter().fold(YamlPath::root(), |path, key| match key.parse::<usize>() {
+        Ok(index) => path.child_index(index),
+        Err(_) => path.child_key(&Yaml::String(key.to_string())),
+    })
+}
+
+#[test]
+fn test_path_display() {
+    assert_eq!(path(&["spec", "containers", "0", "name"]).to_string(), "spec.containers[0].name");
+    assert_eq!(
+        path(&["metadata", "annotations", "kubernetes.io/ingress.class"]).to_string(),
+        "metadata.annotations[\"kubernetes.io/ingress.class\"]"
+    );
+}
+
+#[test]
+fn test_pattern_matching() {
+    let pattern: PathPattern = "spec.containers[*].name".parse().unwrap();
+    assert!(pattern.matches(&path(&["spec", "containers", "3", "name"])));
+    assert!(!pattern.matches(&path(&["spec", "containers", "name"])));
+
+    let pattern: PathPattern = "**.resources.*".parse().unwrap();
+    assert!(pattern.matches(&path(&["resources", "limits"])));
+    assert!(pattern.matches(&path(&["app", "containers", "0", "resources", "requests"])));
+    assert!(!pattern.matches(&path(&["app", "resources"])));
+
+    let pattern: PathPattern = "annotations[\"a.b/c\"]".parse().unwrap();
+    assert!(pattern.matches(&path(&["annotations", "a.b/c"])));
+    assert!(pattern.matches_prefix_of(&path(&["annotations", "a.b/c", "x"])));
+}
+
+#[test]
+fn test_pattern_round_trip_and_errors() {
+    for s in ["", "a", "a.b[*].c", "**.image", "a[\"x.y\"]", "*.tag"] {
+        assert_eq!(s.parse::<PathPattern>().unwrap().to_string(), s);
+    }
+    assert!("a..b".parse::<PathPattern>().is_err());
+    assert!("a[".parse::<PathPattern>().is_err());
+    assert!("a.".parse::<PathPattern>().is_err());
+}
@@ -0,0 +1,96 @@
+use yabe::loader::load_document;
+use yabe::merge::{MergeOptions, MergeStrategy, NullMode};
+use yabe::path::YamlPath;
+use yabe::provenance::{merge_layers_traced, same_and_overridden, Origin};
+use yaml_rust2::Yaml;
+
+fn path(p: &str) -> YamlPath {
+    let mut path = YamlPath::root();
+    for key in p.split('.') {
+        path.push(yabe::path::PathSegment::Key(key.to_string()));
+    }
+    path
+}
+
+fn origin(file: &str, line: usize, value: Yaml) -> Origin {
+    Origin { file: file.to_string(), line: Some(line), value }
+}
+
+#[test]
+fn test_traces_leaves_to_layers() {
+    let chart = load_document("image:\n  repository: nginx\n  tag: '1.0'\nreplicas: 1\n").unwrap().unwrap();
+    let env = load_document("image:\n  tag: '1.1'\n").unwrap().unwrap();
+    let app = load_document("replicas: 3\nimage:\n  tag: '1.2'\n").unwrap().unwrap();
+
+    let (merged, provenance) =
+        merge_layers_traced(&[("chart.yaml", &chart), ("env.yaml", &env), ("app.yaml", &app)], &MergeOptions::default())
+            .unwrap();
+
+    assert_eq!(merged["image"]["tag"].as_str(), Some("1.2"));
+    assert_eq!(provenance.len(), 3);
+    assert_eq!(
+        provenance.get(&path("image.repository")).unwrap(),
+        [origin("chart.yaml", 2, Yaml::String("nginx".into()))]
+    );
+    assert_eq!(
+        provenance.get(&path("image.tag")).unwrap(),
+        [
+            origin("app.yaml", 3, Yaml::String("1.2".into())),
+            origin("env.yaml", 2, Yaml::String("1.1".into())),
+            origin("chart.yaml", 3, Yaml::String("1.0".into())),
+        ]
+    );
+    assert_eq!(provenance.get(&path("replicas")).unwrap()[0].to_string(), "app.yaml:1");
+}
+
+#[test]
+fn test_traces_replaced_subtrees_and_deletions() {
+    let chart = load_document("resources:\n  cpu: 1\n  memory: 1Gi\nnodeSelector:\n  zone: a\n").unwrap().unwrap();
+    let app = load_document("resources: none\nnodeSelector: ~\n").unwrap().unwrap();
+    let options = MergeOptions::default().with_null_mode(NullMode::Delete);
+
+    let (_, provenance) = merge_layers_traced(&[("chart.yaml", &chart), ("app.yaml", &app)], &options).unwrap();
+
+    let origins = provenance.get(&path("resources")).unwrap();
+    assert_eq!(origins.len(), 3);
+    assert_eq!(origins[0].to_string(), "app.yaml:1");
+    assert_eq!(origins[1].value, Yaml::Integer(1));
+    assert!(provenance.get(&path("nodeSelector.zone")).is_none());
+    assert!(provenance.get(&path("nodeSelector")).is_none());
+}
+
+#[test]
+fn test_traces_list_items_across_strategies() {
+    let base = load_document("env:\n  - name: A\n    value: '1'\n  - name: B\n    value: '2'\n").unwrap().unwrap();
+    let app = load_document("env:\n  - name: B\n    value: '3'\n  - name: C\n    value: '4'\n").unwrap().unwrap();
+    let rule: Vec<_> = vec![("env".parse().unwrap(), MergeStrategy::MergeByKey("name".into()))];
+    let options = MergeOptions::new(rule);
+
+    let (_, provenance) = merge_layers_traced(&[("base.yaml", &base), ("app.yaml", &app)], &options).unwrap();
+
+    let value = |i: usize| provenance.get(&path("env").child_index(i).child_key(&Yaml::String("value".into()))).unwrap();
+    assert_eq!(value(0), [origin("base.yaml", 3, Yaml::String("1".into()))]);
+    assert_eq!(value(1)[0], origin("app.yaml", 3, Yaml::String("3".into())));
+    assert_eq!(value(1)[1], origin("base.yaml", 5, Yaml::String("2".into())));
+    assert_eq!(value(2), [origin("app.yaml", 5, Yaml::String("4".into()))]);
+}
+
+#[test]
+fn test_separates_identical_lower_values_from_overridden_ones() {
+    let chart = load_document("replicas: 1
+").unwrap().unwrap();
+    let env = load_document("replicas: 3
+").unwrap().unwrap();
+    let app = load_document("replicas: 3
+").unwrap().unwrap();
+
+    let (_, provenance) =
+        merge_layers_traced(&[("chart.yaml", &chart), ("env.yaml", &env), ("app.yaml", &app)], &MergeOptions::default())
+            .unwrap();
+
+    let (effective, lower) = provenance.get(&path("replicas")).unwrap().split_first().unwrap();
+    assert_eq!(effective.to_string(), "app.yaml:1");
+    let (same, overridden) = same_and_overridden(&effective.value, lower);
+    assert_eq!(same, [&origin("env.yaml", 1, Yaml::Integer(3))]);
+    assert_eq!(overridden, [&origin("chart.yaml", 1, Yaml::Integer(1))]);
+}
\ No newline at end of file
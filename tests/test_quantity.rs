@@ -0,0 +1,91 @@
+use yabe::deep_equal::{deep_equal, EqualityPolicy};
+use yabe::diff::{compute_diff_with, diff_and_common_multiple_with, DiffOptions};
+use yabe::path::YamlPath;
+use yabe::quantity::{canonical_quantity, is_quantity_path, quantity_equal, Quantity};
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+fn quantities() -> DiffOptions {
+    DiffOptions {
+        equality: EqualityPolicy { quantities: true, ..EqualityPolicy::default() },
+        ..DiffOptions::default()
+    }
+}
+
+#[test]
+fn test_parse_quantities() {
+    let q = |s: &str| Quantity::parse(s).unwrap();
+
+    assert_eq!(q("1000m"), q("1"));
+    assert_eq!(q("0.5"), q("500m"));
+    assert_eq!(q("1Gi"), q("1024Mi"));
+    assert_eq!(q("1e3"), q("1k"));
+    assert_eq!(q(".25"), q("250m"));
+    assert_ne!(q("1G"), q("1Gi"));
+    assert!(Quantity::parse("1.5.0").is_none());
+    assert!(Quantity::parse("large").is_none());
+    assert!(Quantity::parse("1Xi").is_none());
+    assert!(Quantity::parse("0.1n").is_none());
+}
+
+#[test]
+fn test_canonical_form() {
+    let canonical = |s: &str| Quantity::parse(s).unwrap().to_string();
+
+    assert_eq!(canonical("1000m"), "1");
+    assert_eq!(canonical("0.5"), "500m");
+    assert_eq!(canonical("1024Mi"), "1Gi");
+    assert_eq!(canonical("1.5Gi"), "1536Mi");
+    assert_eq!(canonical("2000"), "2k");
+    assert_eq!(canonical("0Mi"), "0");
+    assert_eq!(canonical("-100m"), "-100m");
+    assert_eq!(Quantity::parse("1000m").unwrap().to_yaml(), Yaml::Integer(1));
+
+    let values = [Yaml::String("1073741824".into()), Yaml::String("1024Mi".into())];
+    assert_eq!(canonical_quantity(&values), Some(Yaml::String("1Gi".into())));
+}
+
+#[test]
+fn test_quantity_paths() {
+    let path = |p: &str| {
+        let mut path = YamlPath::root();
+        for key in p.split('.') {
+            path.push(yabe::path::PathSegment::Key(key.to_string()));
+        }
+        path
+    };
+
+    assert!(is_quantity_path(&path("app.resources.limits.cpu")));
+    assert!(is_quantity_path(&path("spec.hard.requests.memory")));
+    assert!(!is_quantity_path(&path("app.replicas")));
+    assert!(quantity_equal(&Yaml::Integer(1), &Yaml::String("1000m".into())));
+    assert!(!quantity_equal(&Yaml::String("1".into()), &Yaml::Null));
+}
+
+#[test]
+fn test_diff_compares_quantities() {
+    let helm_yaml = load("resources:\n  limits: {cpu: 1, memory: 1Gi}\nreplicas: 1");
+    let obj_yaml = load("resources:\n  limits: {cpu: 1000m, memory: 1024Mi}\nreplicas: 1.0");
+
+    assert!(compute_diff_with(&obj_yaml, &helm_yaml, &quantities()).is_some_and(|diff| deep_equal(&diff, &load("replicas: 1.0"))));
+    assert!(compute_diff_with(&obj_yaml, &helm_yaml, &DiffOptions::default())
+        .is_some_and(|diff| !diff["resources"].is_badvalue()));
+}
+
+#[test]
+fn test_common_hoists_canonical_quantities() {
+    let yaml1 = load("resources:\n  requests: {cpu: 1000m, memory: 1Gi}");
+    let yaml2 = load("resources:\n  requests: {cpu: 1, memory: 1024Mi}");
+    let yaml3 = load("resources:\n  requests: {cpu: 1000m, memory: 2Gi}");
+    let objs = vec![&yaml1, &yaml2, &yaml3];
+
+    let (base, diffs) = diff_and_common_multiple_with(&objs, 0.6, &quantities());
+
+    assert_eq!(base.unwrap().into_owned(), load("resources:\n  requests: {cpu: 1, memory: 1Gi}"));
+    assert!(diffs[0].is_none());
+    assert!(diffs[1].is_none());
+    assert_eq!(diffs[2].as_ref().unwrap().as_ref(), &load("resources:\n  requests: {memory: 2Gi}"));
+}
\ No newline at end of file
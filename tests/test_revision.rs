@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use yabe::revision::{read_revision, revision_path};
+
+fn git(repository: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success(), "git {:?}", args);
+}
+
+#[test]
+fn test_parse_revision_paths() {
+    assert_eq!(revision_path("main:values/prod.yaml"), Some(("main", "values/prod.yaml")));
+    assert_eq!(revision_path("v1.2.0:./base.yaml"), Some(("v1.2.0", "./base.yaml")));
+    assert_eq!(revision_path("HEAD~2:base.yaml"), Some(("HEAD~2", "base.yaml")));
+    assert_eq!(revision_path("values/prod.yaml"), None);
+    assert_eq!(revision_path(":base.yaml"), None);
+    assert_eq!(revision_path("main:"), None);
+    assert_eq!(revision_path("C:\\values\\prod.yaml"), None);
+}
+
+#[test]
+fn test_read_file_at_revision() {
+    let repository = std::env::temp_dir().join(format!("yabe-test-revision-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&repository);
+    fs::create_dir_all(repository.join("values")).unwrap();
+    git(&repository, &["init", "-q"]);
+    fs::write(repository.join("values/base.yaml"), "replicas: 1\n").unwrap();
+    git(&repository, &["add", "-A"]);
+    git(&repository, &["commit", "-q", "-m", "first"]);
+    git(&repository, &["tag", "v1"]);
+    fs::write(repository.join("values/base.yaml"), "replicas: 2\n").unwrap();
+    git(&repository, &["commit", "-q", "-am", "second"]);
+
+    assert_eq!(read_revision(&repository, "v1", "values/base.yaml").unwrap(), "replicas: 1\n");
+    assert_eq!(read_revision(&repository, "HEAD", "values/base.yaml").unwrap(), "replicas: 2\n");
+    assert!(read_revision(&repository, "v1", "values/missing.yaml").is_err());
+    fs::remove_dir_all(repository).unwrap();
+}
\ No newline at end of file
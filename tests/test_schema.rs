@@ -0,0 +1,192 @@
+use yabe::deep_equal::deep_equal;
+use yabe::schema::{infer_schema, validate, validate_with};
+use yabe::secrets::SecretPolicy;
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+/// The paths and messages of the violations of `values`.
+fn violations(schema: &Yaml, values: &str) -> Vec<String> {
+    validate(schema, &load(values)).iter().map(ToString::to_string).collect()
+}
+
+const SCHEMA: &str = r##"{
+  "type": "object",
+  "required": ["image"],
+  "properties": {
+    "replicaCount": {"type": "integer", "minimum": 1},
+    "image": {"$ref": "#/definitions/image"},
+    "service": {
+      "type": "object",
+      "properties": {"port": {"type": "integer", "maximum": 65535}, "type": {"enum": ["ClusterIP", "NodePort"]}},
+      "additionalProperties": false
+    },
+    "hosts": {"type": "array", "items": {"type": "string", "minLength": 1}, "uniqueItems": true}
+  },
+  "definitions": {
+    "image": {"type": "object", "required": ["repository"], "properties": {"tag": {"type": ["string", "null"], "pattern": "^v"}}}
+  }
+}"##;
+
+#[test]
+fn test_valid_values() {
+    let schema = load(SCHEMA);
+
+    let values = "replicaCount: 2\nimage: {repository: web, tag: v1}\nservice: {port: 80, type: NodePort}\nhosts: [a, b]";
+    assert!(violations(&schema, values).is_empty());
+    assert!(violations(&schema, "image: {repository: web, tag: ~}\nreplicaCount: 2.0").is_empty());
+}
+
+#[test]
+fn test_report_violations_by_path() {
+    let schema = load(SCHEMA);
+
+    let values = "replicaCount: 0\nimage: {tag: latest}\nservice: {port: 70000, type: LoadBalancer, name: web}\nhosts: [a, '', a]";
+    assert_eq!(
+        violations(&schema, values),
+        [
+            "replicaCount: 0 is less than the minimum of 1",
+            "image: required property repository is missing",
+            "image.tag: \"latest\" does not match ^v",
+            "service.port: 70000 is greater than the maximum of 65535",
+            "service.type: \"LoadBalancer\" is not one of \"ClusterIP\", \"NodePort\"",
+            "service.name: not an allowed property",
+            "hosts: has duplicate items",
+            "hosts[1]: is shorter than 1 characters",
+        ]
+    );
+    assert_eq!(
+        violations(&schema, "replicaCount: two"),
+        ["document root: required property image is missing", "replicaCount: expected integer, found string"]
+    );
+}
+
+#[test]
+fn test_combinations() {
+    let schema = load(
+        r#"{
+  "properties": {
+    "port": {"anyOf": [{"type": "integer"}, {"type": "string", "pattern": "^[0-9]+$"}]},
+    "mode": {"oneOf": [{"const": "a"}, {"type": "string", "maxLength": 1}]},
+    "name": {"not": {"const": "default"}},
+    "tls": {"if": {"properties": {"enabled": {"const": true}}}, "then": {"required": ["secret"]}}
+  }
+}"#,
+    );
+
+    assert!(violations(&schema, "port: '8080'\nmode: b\nname: web\ntls: {enabled: false}").is_empty());
+    assert_eq!(
+        violations(&schema, "port: http\nmode: a\nname: default\ntls: {enabled: true}"),
+        [
+            "port: does not match any of the anyOf schemas",
+            "mode: matches 2 of the oneOf schemas instead of one",
+            "name: matches the schema it must not",
+            "tls: required property secret is missing",
+        ]
+    );
+}
+
+#[test]
+fn test_reference_cycles_are_reported() {
+    let schema = load(r##"{"definitions": {"a": {"$ref": "#/definitions/a"}}, "$ref": "#/definitions/a"}"##);
+    assert_eq!(
+        violations(&schema, "a: 1"),
+        ["document root: the schema reference #/definitions/a refers back to itself"]
+    );
+
+    // A recursive schema is followed one level of the values at a time
+    let tree = load(
+        r##"{
+  "$ref": "#/definitions/node",
+  "definitions": {
+    "node": {"type": "object", "properties": {"name": {"type": "string"}, "children": {"type": "array", "items": {"$ref": "#/definitions/node"}}}},
+    "loop": {"anyOf": [{"$ref": "#/definitions/loop"}]}
+  },
+  "properties": {"other": {"$ref": "#/definitions/loop"}}
+}"##,
+    );
+    assert!(violations(&tree, "name: a\nchildren: [{name: b, children: [{name: c}]}]").is_empty());
+    assert_eq!(violations(&tree, "children: [{name: 1}]"), ["children[0].name: expected string, found integer"]);
+    assert_eq!(violations(&tree, "other: 1"), ["other: does not match any of the anyOf schemas"]);
+}
+
+#[test]
+fn test_infer_types_enums_and_required() {
+    let read_base = load("replicaCount: 1\nimage: {repository: nginx, tag: latest}\nresources: {}");
+    let prod = load("replicaCount: 3\nimage: {tag: v2}\nenv: prod\ntier: web\nport: 80");
+    let dev = load("replicaCount: 0.5\nimage: {tag: v1}\nenv: dev\ntier: web\nport: null");
+    let staging = load("image: {tag: v1}\nenv: staging\ntier: worker");
+
+    let schema = infer_schema(Some(&read_base), &[&prod, &dev, &staging], 5, &SecretPolicy::default());
+
+    let expected = load(
+        r##"{
+  "$schema": "http://json-schema.org/draft-07/schema#",
+  "type": "object",
+  "properties": {
+    "replicaCount": {"type": "number"},
+    "image": {
+      "type": "object",
+      "properties": {
+        "repository": {"type": "string"},
+        "tag": {"type": "string", "enum": ["latest", "v2", "v1"]}
+      },
+      "required": ["tag"]
+    },
+    "resources": {"type": "object", "properties": {}},
+    "env": {"type": "string"},
+    "tier": {"type": "string", "enum": ["web", "worker"]},
+    "port": {"type": ["integer", "null"]}
+  },
+  "required": ["image", "env", "tier"]
+}"##,
+    );
+    assert!(deep_equal(&schema, &expected), "{:?}", schema);
+
+    // The files the schema was inferred from are valid
+    for values in [&prod, &dev, &staging] {
+        assert!(validate(&schema, values).is_empty());
+    }
+}
+
+#[test]
+fn test_secret_values_are_redacted_in_violations() {
+    let schema = load(
+        r#"{"properties": {
+          "db": {"properties": {"password": {"pattern": "^[a-z]+$"}, "pin": {"maximum": 9999}, "user": {"enum": ["app"]}}}
+        }}"#,
+    );
+    let values = load("db: {password: Hunter2Secret, pin: 123456, user: root}");
+    let secrets = SecretPolicy::from_yaml(&load("paths: [db.pin]\nkeyNames: true"));
+
+    let messages: Vec<String> = validate_with(&schema, &values, &secrets).iter().map(ToString::to_string).collect();
+
+    assert_eq!(
+        messages,
+        [
+            "db.password: <redacted> does not match ^[a-z]+$",
+            "db.pin: <redacted> is greater than the maximum of 9999",
+            "db.user: \"root\" is not one of \"app\"",
+        ]
+    );
+    assert!(violations(&schema, "db: {password: Hunter2Secret}")[0].contains("Hunter2Secret"));
+}
+
+#[test]
+fn test_infer_array_items_without_secret_enums() {
+    let a = load("hosts: [a.example.com]\nports: [{name: http, port: 80}]\nauth: {token: abc}");
+    let b = load("hosts: [b.example.com]\nports: [{name: http, port: 80}, {name: metrics}]\nauth: {token: abc}");
+    let policy = SecretPolicy::from_yaml(&load("keyNames: true"));
+
+    let schema = infer_schema(None, &[&a, &b], 5, &policy);
+
+    let ports = &schema["properties"]["ports"];
+    assert_eq!(ports["type"].as_str(), Some("array"));
+    assert_eq!(ports["items"]["properties"]["port"]["type"].as_str(), Some("integer"));
+    assert!(deep_equal(&ports["items"]["required"], &load("[name]")));
+    assert!(schema["properties"]["hosts"]["items"]["enum"].is_badvalue());
+    assert_eq!(schema["properties"]["auth"]["properties"]["token"]["type"].as_str(), Some("string"));
+    assert!(schema["properties"]["auth"]["properties"]["token"]["enum"].is_badvalue());
+}
\ No newline at end of file
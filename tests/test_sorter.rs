@@ -1,7 +1,7 @@
 use yaml_rust2::{YamlLoader, Yaml, YamlEmitter};
 use std::fs;
 use log::warn;
-use yabe::sorter::sort_yaml;
+use yabe::sorter::{sort_yaml, sort_yaml_with_reference};
 
 // Function to initialize test configuration
 pub fn init_test_config(config_path: &str) -> Yaml {
@@ -193,4 +193,64 @@ test: yaml"#;
     }
 
     assert_eq!(out_str, result);
+}
+#[test]
+fn test_path_scoped_rules() {
+    let config = YamlLoader::load_from_str(
+        r#"
+preOrder: [name]
+rules:
+  - path: image
+    preOrder: [tag]
+  - path: ports
+    sortKey: port
+"#,
+    )
+    .unwrap()
+    .into_iter()
+    .next()
+    .unwrap();
+
+    let test_str = "tag: x\nname: a\nimage:\n  repository: r\n  tag: t\n  name: n\nports:\n  - port: b\n  - port: a";
+    let docs = YamlLoader::load_from_str(test_str).unwrap();
+    let processed_doc = sort_yaml(&docs[0], &config);
+
+    let mut out_str = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut out_str);
+        emitter.dump(&processed_doc).unwrap();
+    }
+
+    assert_eq!(
+        out_str,
+        "---\nname: a\nimage:\n  tag: t\n  name: n\n  repository: r\nports:\n  - port: a\n  - port: b\ntag: x"
+    );
+}
+
+
+#[test]
+fn test_reference_order() {
+    let config = init_test_config("tests/config.yaml");
+    let reference = YamlLoader::load_from_str(
+        "service:\n  type: ClusterIP\n  port: 80\nimage:\n  repository: nginx\n  tag: latest\n  pullPolicy: IfNotPresent",
+    )
+    .unwrap()
+    .into_iter()
+    .next()
+    .unwrap();
+
+    let test_str = "extra: 1\nimage:\n  pullPolicy: Always\n  tag: v1\n  b: 2\n  a: 1\nservice:\n  port: 8080\n  type: NodePort";
+    let docs = YamlLoader::load_from_str(test_str).unwrap();
+    let processed_doc = sort_yaml_with_reference(&docs[0], &config, &reference);
+
+    let mut out_str = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut out_str);
+        emitter.dump(&processed_doc).unwrap();
+    }
+
+    assert_eq!(
+        out_str,
+        "---\nservice:\n  type: NodePort\n  port: 8080\nimage:\n  tag: v1\n  pullPolicy: Always\n  a: 1\n  b: 2\nextra: 1"
+    );
 }
\ No newline at end of file
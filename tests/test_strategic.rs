@@ -0,0 +1,85 @@
+use yabe::deep_equal::deep_equal;
+use yabe::diff::{compute_diff_with, DiffOptions};
+use yabe::merge::{merge_yaml_with, MergeOptions};
+use yaml_rust2::{Yaml, YamlLoader};
+
+fn load(content: &str) -> Yaml {
+    YamlLoader::load_from_str(content).unwrap().into_iter().next().unwrap()
+}
+
+fn strategic() -> MergeOptions {
+    MergeOptions::default().with_strategic_merge()
+}
+
+#[test]
+fn test_merge_containers_by_name() {
+    let base = load(
+        "containers:\n  - name: app\n    image: app:1\n    ports:\n      - containerPort: 80\n        protocol: TCP\n  - name: sidecar\n    image: proxy:1\n",
+    );
+    let patch = load("containers:\n  - name: app\n    image: app:2\n    ports:\n      - containerPort: 80\n        name: http\n");
+
+    let merged = merge_yaml_with(&base, &patch, &strategic());
+
+    let expected = load(
+        "containers:\n  - name: app\n    image: app:2\n    ports:\n      - containerPort: 80\n        protocol: TCP\n        name: http\n  - name: sidecar\n    image: proxy:1\n",
+    );
+    assert!(deep_equal(&merged, &expected));
+}
+
+#[test]
+fn test_merge_patch_directives() {
+    let base = load(
+        "metadata:\n  labels: {a: '1', b: '2'}\n  annotations: {x: y}\nspec:\n  env:\n    - {name: A, value: '1'}\n    - {name: B, value: '2'}\n  strategy: {type: RollingUpdate, rollingUpdate: {maxSurge: 1}}\n",
+    );
+    let patch = load(
+        "metadata:\n  labels: {$patch: replace, c: '3'}\n  annotations: {$patch: delete}\nspec:\n  env:\n    - {name: A, $patch: delete}\n  strategy: {$retainKeys: [type], type: Recreate}\n",
+    );
+
+    let merged = merge_yaml_with(&base, &patch, &strategic());
+
+    let expected = load("metadata:\n  labels: {c: '3'}\nspec:\n  env:\n    - {name: B, value: '2'}\n  strategy: {type: Recreate}\n");
+    assert!(deep_equal(&merged, &expected));
+}
+
+#[test]
+fn test_merge_list_order_and_primitive_deletion() {
+    let base = load("env:\n  - {name: A, value: '1'}\n  - {name: B, value: '2'}\nmetadata:\n  finalizers: [a, b]\n");
+    let patch = load(
+        "$setElementOrder/env: [{name: B}, {name: A}]\nmetadata:\n  $deleteFromPrimitiveList/finalizers: [a]\n  finalizers: [c]\n",
+    );
+
+    let merged = merge_yaml_with(&base, &patch, &strategic());
+
+    let expected = load("env:\n  - {name: B, value: '2'}\n  - {name: A, value: '1'}\nmetadata:\n  finalizers: [b, c]\n");
+    assert_eq!(merged.into_owned(), expected);
+}
+
+#[test]
+fn test_diff_emits_directives() {
+    let options = DiffOptions { merge: strategic(), ..DiffOptions::default() };
+    let base = load("containers:\n  - {name: app, image: app:1}\n  - {name: old, image: old:1}\nmetadata:\n  finalizers: [a, b]\n");
+    let obj = load("containers:\n  - {name: app, image: app:2}\nmetadata:\n  finalizers: [b]\n");
+
+    let diff = compute_diff_with(&obj, &base, &options).unwrap();
+
+    let expected = load(
+        "containers:\n  - {name: app, image: app:2}\n  - {name: old, $patch: delete}\nmetadata:\n  $deleteFromPrimitiveList/finalizers: [a]\n",
+    );
+    assert!(deep_equal(&diff, &expected));
+}
+
+#[test]
+fn test_diff_round_trips() {
+    let options = DiffOptions { merge: strategic(), ..DiffOptions::default() };
+    let base = load(
+        "spec:\n  containers:\n    - name: app\n      image: app:1\n      env: [{name: A, value: '1'}, {name: B, value: '2'}]\n    - name: sidecar\n      image: proxy:1\n  replicas: 2\n",
+    );
+    let obj = load(
+        "spec:\n  containers:\n    - name: sidecar\n      image: proxy:2\n    - name: app\n      image: app:1\n      env: [{name: B, value: '3'}]\n",
+    );
+
+    let diff = compute_diff_with(&obj, &base, &options).unwrap();
+    let merged = merge_yaml_with(&base, &diff, &options.merge);
+
+    assert!(deep_equal(&merged, &obj));
+}
\ No newline at end of file
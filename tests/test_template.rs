@@ -0,0 +1,80 @@
+use yabe::template::{input_names, output_paths, shared_path, PathTemplate};
+
+fn template(s: &str) -> PathTemplate {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_render_placeholders() {
+    let default = template("{out}/{stem}_diff.{format}");
+    assert_eq!(default.render("values/prod.yaml", 0, "./out", "yaml"), "./out/prod_diff.yaml");
+    assert_eq!(default.render("values/prod.yaml", 0, "/tmp/out", "json"), "/tmp/out/prod_diff.json");
+
+    let grouped = template("{out}/{group}/{index}-{stem}.{format}");
+    assert_eq!(grouped.render("clusters/eu/prod.yaml", 2, "gen", "json"), "gen/eu/3-prod.json");
+    assert_eq!(grouped.render("main:clusters/us/dev.yaml", 0, "gen", "yaml"), "gen/us/1-dev.yaml");
+
+    let beside = template("{dir}/overrides.yaml");
+    assert_eq!(beside.render("clusters/eu/prod.yaml", 0, "out", "yaml"), "clusters/eu/overrides.yaml");
+    assert_eq!(beside.render("prod.yaml", 0, "out", "yaml"), "./overrides.yaml");
+}
+
+#[test]
+fn test_ext_is_the_extension_of_the_input_file() {
+    let same = template("{out}/{stem}.{ext}");
+    assert_eq!(same.render("values/prod.yml", 0, "out", "json"), "out/prod.yml");
+    assert_eq!(same.render("values/prod.yaml", 0, "out", "json"), "out/prod.yaml");
+}
+
+#[test]
+fn test_empty_placeholders_leave_out_their_component() {
+    let grouped = template("{group}/{stem}.yaml");
+    assert_eq!(grouped.render("prod.yaml", 0, "out", "yaml"), "prod.yaml");
+    assert_eq!(grouped.render("eu/prod.yaml", 0, "out", "yaml"), "eu/prod.yaml");
+
+    let nested = template("{out}/{group}/{stem}.yaml");
+    assert_eq!(nested.render("prod.yaml", 0, "out", "yaml"), "out/prod.yaml");
+    assert_eq!(template("/srv/{group}/{stem}.yaml").render("prod.yaml", 0, "out", "yaml"), "/srv/prod.yaml");
+}
+
+#[test]
+fn test_invalid_templates() {
+    assert!("{out}/{name}.yaml".parse::<PathTemplate>().is_err());
+    assert!("{out/{stem}.yaml".parse::<PathTemplate>().is_err());
+    assert!("out/base.yaml".parse::<PathTemplate>().is_ok());
+}
+
+#[test]
+fn test_output_paths_are_unique() {
+    let inputs = ["clusters/eu/prod.yaml", "clusters/us/prod.yaml"];
+
+    let paths = output_paths(&template("{out}/{group}/{stem}.{format}"), &inputs, "out", "yaml", &[]).unwrap();
+    assert_eq!(paths, ["out/eu/prod.yaml", "out/us/prod.yaml"]);
+
+    // The stem alone is the same for both inputs
+    assert!(output_paths(&template("{out}/{stem}.{format}"), &inputs, "out", "yaml", &[]).is_err());
+
+    // A diff must not overwrite the base
+    let reserved = ["./out/eu/prod.yaml"];
+    assert!(output_paths(&template("{out}/{group}/{stem}.{format}"), &inputs, "out", "yaml", &reserved).is_err());
+}
+
+#[test]
+fn test_shared_path_is_the_same_for_all_inputs() {
+    let same_dir = ["clusters/eu/prod.yaml", "clusters/eu/dev.yaml"];
+    let base = shared_path(&template("{dir}/base.yaml"), &same_dir, "out", "yaml").unwrap();
+    assert_eq!(base, "clusters/eu/base.yaml");
+
+    let other_dirs = ["clusters/eu/prod.yaml", "clusters/us/prod.yaml"];
+    assert!(shared_path(&template("{dir}/base.yaml"), &other_dirs, "out", "yaml").is_err());
+    assert!(shared_path(&template("{out}/{stem}.yaml"), &same_dir, "out", "yaml").is_err());
+}
+
+#[test]
+fn test_input_names_are_distinct_stems() {
+    let names = input_names(&["clusters/eu/prod.yaml", "HEAD~1:clusters/eu/dev.yaml"]).unwrap();
+    assert_eq!(names, ["prod", "dev"]);
+
+    let err = input_names(&["eu/prod.yaml", "us/prod.yaml"]).unwrap_err();
+    assert!(err.to_string().contains("eu/prod.yaml and us/prod.yaml would both be named prod"), "{}", err);
+}